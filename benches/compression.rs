@@ -0,0 +1,71 @@
+//! Compare storing a 1 MB JSON file with and without compression.
+//!
+//! Run using `cargo bench --bench compression`. The encrypted sizes are printed before the
+//! timings.
+use std::{ffi::OsString, fs, path::Path};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use dgruft::backend::{encrypted, file::FileData};
+
+const TARGET_SIZE: usize = 1024 * 1024;
+
+// Build roughly 1 MB of JSON resembling an exported log.
+fn json_content() -> Vec<u8> {
+    let mut json = String::from("[");
+    let mut i = 0;
+    while json.len() < TARGET_SIZE {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str(&format!(
+            r#"{{"id":{i},"user":"user_{}","event":"login","success":{},"latency_ms":{}}}"#,
+            i % 97,
+            i % 5 != 0,
+            (i * 37) % 1000
+        ));
+        i += 1;
+    }
+    json.push(']');
+    json.into_bytes()
+}
+
+fn store(dir: &Path, content: &[u8], compressed: bool) -> u64 {
+    let key = encrypted::new_key(None);
+    let name = OsString::from("bench.json");
+    let path = dir.join(&name);
+    let _ = fs::remove_file(&path);
+    let file_data = if compressed {
+        FileData::new_compressed_with_content_and_key("bench", &key, name, content, &path)
+    } else {
+        FileData::new_with_content_and_key("bench", &key, name, content, &path)
+    }
+    .unwrap();
+    file_data.file_size()
+}
+
+fn bench_compression(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let content = json_content();
+
+    println!("plaintext size:            {} bytes", content.len());
+    println!(
+        "encrypted size:            {} bytes",
+        store(dir.path(), &content, false)
+    );
+    println!(
+        "compressed encrypted size: {} bytes",
+        store(dir.path(), &content, true)
+    );
+
+    let mut group = c.benchmark_group("store 1 MB JSON");
+    group.bench_function("uncompressed", |b| {
+        b.iter(|| store(dir.path(), &content, false))
+    });
+    group.bench_function("compressed", |b| {
+        b.iter(|| store(dir.path(), &content, true))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_compression);
+criterion_main!(benches);
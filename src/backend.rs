@@ -1,38 +1,73 @@
 //! Backend API.
 use std::{
-    ffi::OsString,
+    collections::{BTreeMap, HashSet},
+    ffi::{OsStr, OsString},
+    fmt,
     fs::{self, create_dir, remove_dir_all, remove_file},
-    io::{self, Write},
-    path::PathBuf,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use color_eyre::eyre::{self, eyre};
+use crossterm::style::Stylize;
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+use serde::Serialize;
+use zeroize::{Zeroize, Zeroizing};
 
 pub mod account;
+pub mod attachment;
+pub mod breach;
 pub mod database;
 pub mod encrypted;
+pub mod export;
 pub mod file;
 pub mod hashed;
+pub mod history;
+pub mod keepass;
+pub mod migrations;
 pub mod password;
+pub mod recovery;
 mod sql_schemas;
 mod sql_statements;
 
-use crate::{error::Error, helpers};
-use account::{Account, SecureFields};
-use database::Database;
+use crate::{
+    config,
+    error::Error,
+    helpers::{
+        self,
+        password::{password_strength, PasswordStrength},
+    },
+};
+use account::{Account, KdfAlgorithm, SecureFields};
+use attachment::Attachment;
+use database::{Database, Table};
+use encrypted::KeyMaterial;
+use export::{ExportedAccount, ExportedFile, ExportedPassword, VaultExport};
 use file::FileData;
-use password::Password;
+use history::PasswordHistoryEntry;
+use password::{
+    parse_expiry_date, totp_seconds_remaining, DecryptedPasswordFields, ExpiryStatus, Password,
+    PasswordChanges, PasswordField, PasswordUpdate,
+};
+use recovery::RecoveryCode;
 
-const DATABASE_NAME: &str = "dgruft.db";
+const ATTACHMENTS_DIR_NAME: &str = ".attachments";
+const LOCK_FILE_NAME: &str = ".lock";
+const DELETED_FILE_SUFFIX: &str = ".deleted";
+const BREACH_CHECK_TIMEOUT_SECS: u64 = 10;
 
 fn database_path() -> PathBuf {
-    let mut path = helpers::get_data_dir();
-    path.push(DATABASE_NAME);
-    path
+    config::get().db_path.clone()
 }
 
 fn acc_path(username: &str) -> PathBuf {
-    let mut path = helpers::get_data_dir();
+    let mut path = config::get().data_dir.clone();
     path.push(username);
     path
 }
@@ -42,6 +77,17 @@ fn load_db() -> eyre::Result<Database> {
 }
 
 fn login(db: &mut Database, username: &str, password: &str) -> eyre::Result<SecureFields> {
+    let unlocked_account = authenticate(db, username, password)?;
+    // Failing to record the login shouldn't lock anyone out.
+    if let Err(err) = db.update_last_login(username, helpers::unix_timestamp()) {
+        eprintln!("Warning: could not record login time: {err}");
+    }
+    Ok(unlocked_account)
+}
+
+// Unlock an account without recording the login, for operations which must not write to the
+// database.
+fn authenticate(db: &Database, username: &str, password: &str) -> eyre::Result<SecureFields> {
     if let Some(b64account) = db.get_b64_account(username)? {
         let db_entry = Account::from_b64(b64account)?;
         Ok(db_entry.unlock(password)?)
@@ -50,8 +96,876 @@ fn login(db: &mut Database, username: &str, password: &str) -> eyre::Result<Secu
     }
 }
 
+/// Run the given function while holding an exclusive lock on the `dgruft` data directory, so that
+/// no other `dgruft` process can read or write at the same time. Use this for operations which
+/// change stored data.
+///
+/// The lock is released when the function returns.
+pub fn with_locked_file<F, R>(f: F) -> eyre::Result<R>
+where
+    F: FnOnce() -> eyre::Result<R>,
+{
+    let lock_file = open_lock_file()?;
+    lock_file.lock()?;
+    f()
+}
+
+/// Run the given function while holding a shared lock on the `dgruft` data directory. Other
+/// processes can read at the same time, but not write. Use this for operations which only read
+/// stored data.
+///
+/// The lock is released when the function returns.
+pub fn with_shared_lock<F, R>(f: F) -> eyre::Result<R>
+where
+    F: FnOnce() -> eyre::Result<R>,
+{
+    let lock_file = open_lock_file()?;
+    lock_file.lock_shared()?;
+    f()
+}
+
+// Open the lock file in the data directory, creating it if it doesn't exist.
+fn open_lock_file() -> eyre::Result<fs::File> {
+    let mut path = config::get().data_dir.clone();
+    fs::create_dir_all(&path)?;
+    path.push(LOCK_FILE_NAME);
+    Ok(fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?)
+}
+
+/// Count the stored passwords of all accounts. No login is required, since only the number of
+/// rows is read.
+pub fn password_count_all() -> eyre::Result<usize> {
+    Ok(load_db()?.count_rows(Table::Passwords)?)
+}
+
+/// Count the stored files of all accounts. No login is required, since only the number of rows is
+/// read.
+pub fn file_count_all() -> eyre::Result<usize> {
+    Ok(load_db()?.count_rows(Table::Files)?)
+}
+
+/// Get the database configuration value stored under the given key.
+/// Return [`Ok<None>`] if no value is stored under that key.
+pub fn get_config(key: &str) -> eyre::Result<Option<String>> {
+    Ok(load_db()?.get_config(key)?)
+}
+
+/// Store a database configuration value under the given key, replacing any existing value.
+pub fn set_config(key: &str, value: &str) -> eyre::Result<()> {
+    Ok(load_db()?.set_config(key, value)?)
+}
+
+/// Reclaim the space left unused in the database by deleted entries. If `backup` is given, first
+/// write a compacted copy of the database there.
+pub fn optimize(backup: Option<&Path>) -> eyre::Result<()> {
+    let db = load_db()?;
+    if let Some(backup) = backup {
+        db.vacuum_into(backup)?;
+    }
+    db.vacuum()?;
+    Ok(())
+}
+
+/// Write a verified snapshot of the database to the given path.
+pub fn backup(dest: &Path) -> eyre::Result<()> {
+    Ok(load_db()?.backup(dest)?)
+}
+
+/// Check the database for corruption, broken references between tables, and implausible
+/// encrypted fields. Return a description of every problem found.
+pub fn check() -> eyre::Result<Vec<String>> {
+    let db = load_db()?;
+    let mut problems = db.integrity_check()?;
+    problems.extend(db.foreign_key_check()?);
+    drop(db);
+    for issue in check_database_consistency()? {
+        problems.push(format!(
+            "{} entry {}: {}: {}.",
+            issue.table.name(),
+            issue.primary_key,
+            issue.field,
+            issue.issue
+        ));
+    }
+    Ok(problems)
+}
+
+/// Return the total size in bytes of an account's encrypted files. The files themselves aren't
+/// read; their sizes are recorded in the database.
+pub fn total_file_size_for_account(username: &str) -> eyre::Result<u64> {
+    match load_db()?.total_file_size(username)? {
+        Some(total) => Ok(total),
+        None => Err(Error::AccountNotFoundError(username.to_owned()).into()),
+    }
+}
+
+/// An overview of the state of the database, returned by [status].
+#[derive(Debug, Serialize)]
+pub struct VaultStatus {
+    /// Path of the database file.
+    pub db_path: PathBuf,
+    /// Size of the database file in bytes, not counting its write-ahead log.
+    pub db_size_bytes: u64,
+    /// Time the database file was last modified, as a Unix timestamp in seconds.
+    pub modified_at: Option<i64>,
+    /// Number of accounts.
+    pub accounts: usize,
+    /// Number of stored passwords across all accounts.
+    pub credentials: usize,
+    /// Number of stored files across all accounts.
+    pub files: usize,
+    /// Total size in bytes of the encrypted files across all accounts.
+    pub files_size_bytes: u64,
+    /// Schema version of the database.
+    pub schema_version: u32,
+    /// Whether the database is in write-ahead logging mode.
+    pub wal_mode: bool,
+    /// The problems reported by SQLite's integrity check. Empty if the database is not corrupted.
+    pub integrity_problems: Vec<String>,
+}
+
+/// Gather an overview of the state of the database. No login is required, since nothing is
+/// decrypted.
+pub fn status() -> eyre::Result<VaultStatus> {
+    let db = load_db()?;
+    let metadata = fs::metadata(db.path())?;
+    let modified_at = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs() as i64);
+    Ok(VaultStatus {
+        db_path: db.path().to_owned(),
+        db_size_bytes: metadata.len(),
+        modified_at,
+        accounts: db.count_rows(Table::UserCredentials)?,
+        credentials: db.count_rows(Table::Passwords)?,
+        files: db.count_rows(Table::Files)?,
+        files_size_bytes: db.total_file_size_all()?,
+        schema_version: db.schema_version()?,
+        wal_mode: db.journal_mode()?.eq_ignore_ascii_case("wal"),
+        integrity_problems: db.integrity_check()?,
+    })
+}
+
+/// Return the usernames of every account in the database.
+pub fn list_accounts() -> eyre::Result<Vec<String>> {
+    let mut usernames = vec![];
+    for b64_account in load_db()?.get_all_b64_accounts()? {
+        usernames.push(helpers::bytes_to_utf8(
+            &helpers::b64_to_bytes(&b64_account.b64_username)?,
+            "username",
+        )?);
+    }
+    Ok(usernames)
+}
+
+/// An account as listed by [list_account_summaries].
+#[derive(Debug, Serialize)]
+pub struct AccountSummary {
+    /// Account username.
+    pub username: String,
+    /// When the account was created.
+    pub created_at: DateTime<Utc>,
+    /// When the account last logged in, if it has logged in since it was created.
+    pub last_login: Option<DateTime<Utc>>,
+}
+
+/// List every account along with when it was created and last logged in.
+pub fn list_account_summaries() -> eyre::Result<Vec<AccountSummary>> {
+    let mut summaries = vec![];
+    for b64_account in load_db()?.get_all_b64_accounts()? {
+        let account = Account::from_b64(b64_account)?;
+        summaries.push(AccountSummary {
+            username: account.username().to_owned(),
+            created_at: account.created_at(),
+            last_login: account.last_login(),
+        });
+    }
+    Ok(summaries)
+}
+
+/// A problem with a database entry found by [check_database_consistency].
+#[derive(Debug)]
+pub struct ConsistencyIssue {
+    /// The table containing the problematic entry.
+    pub table: Table,
+    /// The primary key of the problematic entry, as stored in the database.
+    pub primary_key: String,
+    /// The field of the entry which has the problem.
+    pub field: &'static str,
+    /// A description of the problem.
+    pub issue: &'static str,
+}
+
+/// Check that every encrypted field stored in the database is a plausible ciphertext. Encrypted
+/// file contents are checked on disk.
+///
+/// An empty plaintext still produces a ciphertext of [encrypted::TAG_LENGTH] bytes, so anything
+/// shorter than that can only be the result of corruption.
+pub fn check_database_consistency() -> eyre::Result<Vec<ConsistencyIssue>> {
+    let db = load_db()?;
+    let mut issues: Vec<ConsistencyIssue> = vec![];
+
+    for password in db.get_all_b64_passwords()? {
+        let primary_key = format!(
+            "({}, {})",
+            password.b64_owner_username, password.b64_name_ciphertext
+        );
+        let fields = [
+            ("encrypted_name", &password.b64_name_ciphertext),
+            ("encrypted_username", &password.b64_username_ciphertext),
+            ("encrypted_content", &password.b64_content_ciphertext),
+            ("encrypted_notes", &password.b64_notes_ciphertext),
+        ];
+        let url_field = password
+            .b64_url_ciphertext
+            .as_ref()
+            .map(|b64_ciphertext| ("encrypted_url", b64_ciphertext));
+        let totp_secret_field = password
+            .b64_totp_secret_ciphertext
+            .as_ref()
+            .map(|b64_ciphertext| ("encrypted_totp_secret", b64_ciphertext));
+        for (field, b64_ciphertext) in fields.into_iter().chain(url_field).chain(totp_secret_field)
+        {
+            if let Some(issue) = check_b64_ciphertext(b64_ciphertext) {
+                issues.push(ConsistencyIssue {
+                    table: Table::Passwords,
+                    primary_key: primary_key.clone(),
+                    field,
+                    issue,
+                });
+            }
+        }
+    }
+
+    for file in db.get_all_b64_files()? {
+        let path = helpers::b64_to_bytes(&file.b64_path)
+            .and_then(|bytes| helpers::bytes_to_utf8(&bytes, "path"));
+        let problem = match path {
+            Ok(path) => match fs::metadata(path) {
+                Ok(metadata) if (metadata.len() as usize) < encrypted::TAG_LENGTH => Some((
+                    "content",
+                    "ciphertext is shorter than the authentication tag",
+                )),
+                Ok(_) => None,
+                Err(_) => Some(("content", "encrypted file could not be read")),
+            },
+            Err(_) => Some(("path", "path is not a valid base-64 UTF-8 string")),
+        };
+        if let Some((field, issue)) = problem {
+            issues.push(ConsistencyIssue {
+                table: Table::Files,
+                primary_key: file.b64_path,
+                field,
+                issue,
+            });
+        }
+    }
+
+    Ok(issues)
+}
+
+// Return a description of the problem with the given base-64 ciphertext, if any.
+fn check_b64_ciphertext(b64_ciphertext: &str) -> Option<&'static str> {
+    match helpers::b64_to_bytes(b64_ciphertext) {
+        Ok(bytes) if bytes.len() < encrypted::TAG_LENGTH => {
+            Some("ciphertext is shorter than the authentication tag")
+        }
+        Ok(_) => None,
+        Err(_) => Some("ciphertext is not valid base-64"),
+    }
+}
+
+/// The outcome of [emergency_export].
+#[derive(Debug, Default)]
+pub struct EmergencyExportReport {
+    /// Number of rows read and written to the output directory.
+    pub exported: usize,
+    /// Number of rows which could not be read.
+    pub failed: usize,
+    /// Descriptions of everything that went wrong during the export.
+    pub error_log: Vec<String>,
+}
+
+/// Export every readable row of every table to a JSON file per table in the given directory,
+/// exactly as stored— encrypted fields stay encrypted.
+///
+/// This is meant for salvaging a partially corrupted database. Rows are read one at a time, and
+/// rows which cannot be read are logged and skipped rather than aborting the export.
+pub fn emergency_export(out_dir: &Path) -> eyre::Result<EmergencyExportReport> {
+    let db = load_db()?;
+    let mut report = EmergencyExportReport::default();
+
+    for table in Table::ALL {
+        let num_rows = match db.count_rows(table) {
+            Ok(num_rows) => num_rows,
+            Err(err) => {
+                report
+                    .error_log
+                    .push(format!("{}: could not count rows: {err}", table.name()));
+                continue;
+            }
+        };
+
+        let mut json_rows: Vec<serde_json::Value> = vec![];
+        for offset in 0..num_rows {
+            match db.get_raw_row(table, offset) {
+                Ok(Some(row)) => {
+                    let json_row: serde_json::Map<String, serde_json::Value> = row
+                        .into_iter()
+                        .map(|(column, value)| (column, sql_value_to_json(value)))
+                        .collect();
+                    json_rows.push(json_row.into());
+                    report.exported += 1;
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    report.failed += 1;
+                    report.error_log.push(format!(
+                        "{}: could not read row {offset}: {err}",
+                        table.name()
+                    ));
+                }
+            }
+        }
+
+        let mut out_path = out_dir.to_path_buf();
+        out_path.push(format!("{}.json", table.name()));
+        fs::write(out_path, serde_json::to_string_pretty(&json_rows)?)?;
+    }
+
+    Ok(report)
+}
+
+/// Export an account with all of its passwords and files, decrypted, to a new file at the given
+/// path. The export is encrypted with a new random key, which is returned— the export cannot be
+/// read without it.
+///
+/// Password attachments are not exported.
+pub fn export_json(
+    username: &str,
+    password: &str,
+    output_path: &Path,
+) -> eyre::Result<KeyMaterial> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+    let key = unlocked_account.key();
+
+    let b64_account = match db.get_b64_account(username)? {
+        Some(b64_account) => b64_account,
+        None => return Err(Error::AccountNotFoundError(username.to_owned()).into()),
+    };
+    let mut exported_account = ExportedAccount::new(username, b64_account);
+
+    for b64_password in db.get_b64_passwords(username)?.unwrap_or_default() {
+        let stored_password = Password::from_b64(b64_password)?;
+        let decrypted = stored_password.unlock(key)?;
+        exported_account.passwords.push(ExportedPassword {
+            name: decrypted.name().to_owned(),
+            username: decrypted.username().to_owned(),
+            content: decrypted.content().to_owned(),
+            notes: decrypted.notes().to_owned(),
+            url: decrypted.url().map(str::to_owned),
+            totp_secret: stored_password
+                .totp_secret(key)?
+                .map(|secret| helpers::bytes_to_base32(&secret)),
+            category: stored_password.category().map(str::to_owned),
+            expires_at: stored_password.expires_at(),
+        });
+    }
+    for file in get_files(username)? {
+        let name = file
+            .name()
+            .to_str()
+            .ok_or_else(|| Error::NonUtf8FilePathError(file.name().to_string_lossy().into()))?
+            .to_owned();
+        exported_account.files.push(ExportedFile {
+            name,
+            b64_content: helpers::bytes_to_b64(&file.open_decrypted(key)?),
+        });
+    }
+
+    let export_key = encrypted::new_key(None);
+    let envelope = VaultExport {
+        accounts: vec![exported_account],
+    }
+    .encrypt(&export_key)?;
+    fs::File::create_new(output_path)?.write_all(envelope.as_bytes())?;
+    Ok(export_key)
+}
+
+/// The outcome of [import_json].
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    /// Number of accounts added to the database.
+    pub accounts_added: usize,
+    /// Number of passwords added to the database.
+    pub credentials_added: usize,
+    /// Number of files added to the database.
+    pub files_added: usize,
+    /// Number of accounts, passwords, and files skipped because they already exist.
+    pub conflicts_skipped: usize,
+}
+
+/// Import an export written by [export_json], decrypting it with the given key. The exported
+/// passwords and files are encrypted with the account key, which is unlocked with the given
+/// account password.
+///
+/// Accounts which don't exist yet are added with their original password. Passwords and files of
+/// an account which already exists are added if the existing account is the exported one, and
+/// their names are not taken; a different account under the same username is left untouched
+/// and all of its exported passwords and files are skipped.
+pub fn import_json(
+    input_path: &Path,
+    export_key: &KeyMaterial,
+    password: &str,
+) -> eyre::Result<ImportReport> {
+    let envelope_json = fs::read_to_string(input_path)?;
+    let export = VaultExport::decrypt(&envelope_json, export_key)?;
+
+    let mut db = load_db()?;
+    let mut report = ImportReport::default();
+    for exported_account in &export.accounts {
+        let username = &exported_account.username;
+        let b64_account = exported_account.to_b64_account();
+        let existing_account = db.get_b64_account(username)?;
+        if matches!(&existing_account, Some(existing) if !existing.same_credentials(&b64_account)) {
+            report.conflicts_skipped +=
+                1 + exported_account.passwords.len() + exported_account.files.len();
+            continue;
+        }
+        let unlocked_account =
+            Account::from_b64(exported_account.to_b64_account())?.unlock(password)?;
+        let key = unlocked_account.key();
+        if existing_account.is_none() {
+            db.add_new_account(b64_account)?;
+            let acc_dir = acc_path(username);
+            if !acc_dir.exists() {
+                create_dir(acc_dir)?;
+            }
+            report.accounts_added += 1;
+        }
+
+        for exported_password in &exported_account.passwords {
+            if password_exists(username, key, &exported_password.name)? {
+                report.conflicts_skipped += 1;
+                continue;
+            }
+            let mut new_password = Password::new_with_key(
+                username,
+                key,
+                &exported_password.name,
+                &exported_password.username,
+                &exported_password.content,
+                &exported_password.notes,
+            )?;
+            let mut changes = PasswordChanges::new();
+            if let Some(url) = &exported_password.url {
+                changes = changes.new_url(url);
+            }
+            if let Some(totp_secret) = &exported_password.totp_secret {
+                changes = changes.new_totp_secret(&helpers::base32_to_bytes(totp_secret)?);
+            }
+            if let Some(category) = &exported_password.category {
+                changes = changes.new_category(category);
+            }
+            if let Some(expires_at) = exported_password.expires_at {
+                changes = changes.new_expires_at(Some(expires_at));
+            }
+            new_password.apply_changes(key, &changes)?;
+            db.add_new_password(new_password.to_b64())?;
+            report.credentials_added += 1;
+        }
+
+        for exported_file in &exported_account.files {
+            let mut file_path = acc_path(username);
+            file_path.push(&exported_file.name);
+            if file_name_exists(&db, username, OsStr::new(&exported_file.name))?
+                || file_path.exists()
+            {
+                report.conflicts_skipped += 1;
+                continue;
+            }
+            let content = Zeroizing::new(helpers::b64_to_bytes(&exported_file.b64_content)?);
+            let file_data = FileData::new_with_content_and_key(
+                username,
+                key,
+                OsString::from(&exported_file.name),
+                &content,
+                &file_path,
+            )?;
+            if let Err(err) = db.add_new_file_data(file_data.to_b64()?) {
+                fs::remove_file(&file_path)?;
+                return Err(err.into());
+            }
+            report.files_added += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Import the entries of an unencrypted KeePass 2.x XML export as passwords of the given account.
+///
+/// Each entry's `Title`, `UserName`, and `Password` become the password's name, username, and
+/// content, its `URL` and `Notes` become the password's URL and notes, and the path of its group
+/// becomes the password's category. Entries whose titles are already used by the account's
+/// passwords are skipped.
+pub fn import_keepass(
+    xml_path: &Path,
+    target_account: &str,
+    target_password: &str,
+) -> eyre::Result<ImportReport> {
+    let xml = Zeroizing::new(fs::read_to_string(xml_path)?);
+    let entries = keepass::parse_keepass_xml(&xml)?;
+
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, target_account, target_password)?;
+    let key = unlocked_account.key();
+
+    let mut report = ImportReport::default();
+    for entry in &entries {
+        if password_exists(target_account, key, &entry.title)? {
+            report.conflicts_skipped += 1;
+            continue;
+        }
+        let mut new_password = Password::new_with_key(
+            target_account,
+            key,
+            &entry.title,
+            &entry.username,
+            &entry.password,
+            &entry.notes,
+        )?;
+        new_password.apply_changes(
+            key,
+            &PasswordChanges::new()
+                .new_url(&entry.url)
+                .new_category(&entry.category()),
+        )?;
+        db.add_new_password(new_password.to_b64())?;
+        report.credentials_added += 1;
+    }
+
+    Ok(report)
+}
+
+/// Write all of an account's passwords as unencrypted RFC 4180 CSV with the columns
+/// `name,username,password,notes,url,category,expires_at`. If `mask_passwords` is set, password
+/// content is replaced with asterisks.
+///
+/// The CSV is written to a new file at `output`, readable only by its owner, or to stdout if no
+/// path is given. The file is only created once the account has been unlocked.
+pub fn export_credentials_csv(
+    username: &str,
+    password: &str,
+    output: Option<&Path>,
+    mask_passwords: bool,
+) -> eyre::Result<()> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    match output {
+        Some(path) => write_credentials_csv(
+            &unlocked_account,
+            create_private_file(path)?,
+            mask_passwords,
+        ),
+        None => write_credentials_csv(&unlocked_account, io::stdout(), mask_passwords),
+    }
+}
+
+// Write all of an account's passwords to the given writer as CSV.
+fn write_credentials_csv(
+    unlocked_account: &SecureFields,
+    writer: impl Write,
+    mask_passwords: bool,
+) -> eyre::Result<()> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record([
+        "name",
+        "username",
+        "password",
+        "notes",
+        "url",
+        "category",
+        "expires_at",
+    ])?;
+    for stored_password in get_passwords(unlocked_account.username())? {
+        let decrypted = stored_password.unlock(unlocked_account.key())?;
+        let content = if mask_passwords {
+            "********"
+        } else {
+            decrypted.content()
+        };
+        csv_writer.write_record([
+            decrypted.name(),
+            decrypted.username(),
+            content,
+            decrypted.notes(),
+            decrypted.url().unwrap_or_default(),
+            stored_password.category().unwrap_or_default(),
+            &stored_password
+                .expires_at()
+                .map(|expires_at| expires_at.to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+// Create a new file which only its owner can read or write, for unencrypted output.
+fn create_private_file(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Create a password for each row of CSV read from the given reader. The first row must be a
+/// header naming the columns; `name` and `password` are required, while `username`, `notes`,
+/// `url`, `category`, and `expires_at` are optional. This reads the output of [export_credentials_csv].
+///
+/// Rows named like an existing password are skipped.
+pub fn import_credentials_csv(
+    username: &str,
+    password: &str,
+    reader: impl Read,
+) -> eyre::Result<ImportReport> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+    let key = unlocked_account.key();
+
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let headers = csv_reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let (Some(name_column), Some(content_column)) = (column("name"), column("password")) else {
+        return Err(eyre!(
+            "CSV header must name a \"name\" and a \"password\" column."
+        ));
+    };
+    let username_column = column("username");
+    let notes_column = column("notes");
+    let url_column = column("url");
+    let category_column = column("category");
+    let expires_column = column("expires_at");
+
+    let mut report = ImportReport::default();
+    for record_result in csv_reader.records() {
+        let record = record_result?;
+        let field = |column: Option<usize>| column.and_then(|i| record.get(i)).unwrap_or_default();
+        let name = field(Some(name_column));
+        if password_exists(unlocked_account.username(), key, name)? {
+            report.conflicts_skipped += 1;
+            continue;
+        }
+        let mut new_password = Password::new_with_key(
+            unlocked_account.username(),
+            key,
+            name,
+            field(username_column),
+            field(Some(content_column)),
+            field(notes_column),
+        )?;
+        new_password.apply_changes(
+            key,
+            &PasswordChanges::new()
+                .new_url(field(url_column))
+                .new_category(field(category_column))
+                .new_expires_at(parse_expiry_date(field(expires_column))?),
+        )?;
+        db.add_new_password(new_password.to_b64())?;
+        report.credentials_added += 1;
+    }
+
+    Ok(report)
+}
+
+/// The outcome of [drain_and_migrate].
+#[derive(Debug, Default)]
+pub struct MigrateReport {
+    /// Number of passwords copied to the new database.
+    pub passwords: usize,
+    /// Number of file entries copied to the new database.
+    pub files: usize,
+    /// Number of recovery codes copied to the new database.
+    pub recovery_codes: usize,
+    /// Number of password attachment entries copied to the new database.
+    pub attachments: usize,
+    /// Number of password history entries copied to the new database.
+    pub history_entries: usize,
+}
+
+/// Copy an account and all of its data from the database at `old_db_path` into a new database
+/// created at `new_db_path`, for schema changes too large to migrate in place.
+///
+/// The old database is opened read-only and is never written to, so it must already be at the
+/// current schema version. The new database must not already exist; if anything goes wrong, it is
+/// deleted again. Encrypted file and attachment data stays where it is on disk— only the database
+/// entries pointing to it are copied.
+pub fn drain_and_migrate(
+    old_db_path: &Path,
+    new_db_path: &Path,
+    owner: &str,
+    owner_password: &str,
+) -> eyre::Result<MigrateReport> {
+    let old_db = Database::connect_read_only(old_db_path)?;
+    let unlocked_account = authenticate(&old_db, owner, owner_password)?;
+
+    fs::File::create_new(new_db_path)?;
+    let result = Database::connect(new_db_path)
+        .map_err(eyre::Report::from)
+        .and_then(|mut new_db| migrate_account(&old_db, &mut new_db, unlocked_account.username()));
+    if result.is_err() {
+        eprintln!("Error migrating account— migration cancelled.");
+        remove_database_files(new_db_path)?;
+    }
+    result
+}
+
+// Copy all of an account's database entries from one database to another.
+fn migrate_account(
+    old_db: &Database,
+    new_db: &mut Database,
+    username: &str,
+) -> eyre::Result<MigrateReport> {
+    let mut report = MigrateReport::default();
+
+    let b64_account = match old_db.get_b64_account(username)? {
+        Some(b64_account) => b64_account,
+        None => return Err(Error::AccountNotFoundError(username.to_owned()).into()),
+    };
+    new_db.add_new_account(b64_account)?;
+
+    for b64_password in old_db.get_b64_passwords(username)?.unwrap_or_default() {
+        let b64_attachments =
+            old_db.get_b64_attachments(username, &b64_password.b64_name_ciphertext)?;
+        let b64_history =
+            old_db.get_b64_password_history(username, &b64_password.b64_name_ciphertext)?;
+        new_db.add_new_password(b64_password)?;
+        report.passwords += 1;
+        for b64_attachment in b64_attachments {
+            new_db.add_new_attachment(b64_attachment)?;
+            report.attachments += 1;
+        }
+        // Oldest first, so that entries changed in the same second keep their order.
+        for b64_entry in b64_history.into_iter().rev() {
+            new_db.add_new_password_history_entry(b64_entry)?;
+            report.history_entries += 1;
+        }
+    }
+
+    for b64_file_data in old_db.get_b64_files(username)?.unwrap_or_default() {
+        new_db.add_new_file_data(b64_file_data)?;
+        report.files += 1;
+    }
+
+    let b64_recovery_codes = old_db.get_b64_recovery_codes(username)?.unwrap_or_default();
+    report.recovery_codes = b64_recovery_codes.len();
+    new_db.replace_recovery_codes(username, b64_recovery_codes)?;
+
+    Ok(report)
+}
+
+// Convert a raw SQLite value to JSON. Blobs are converted to base-64 strings.
+fn sql_value_to_json(value: rusqlite::types::Value) -> serde_json::Value {
+    use rusqlite::types::Value;
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Integer(integer) => integer.into(),
+        Value::Real(real) => real.into(),
+        Value::Text(text) => text.into(),
+        Value::Blob(blob) => helpers::bytes_to_b64(&blob).into(),
+    }
+}
+
+/// Generate a set of single-use recovery codes for an account and store their hashes in the
+/// database. Return the plaintext codes so they can be shown to the user— they cannot be
+/// retrieved afterwards.
+///
+/// Any existing recovery codes of the account are invalidated; this is equivalent to
+/// [rotate_recovery_codes].
+pub fn generate_recovery_codes(username: &str, password: &str) -> eyre::Result<Vec<String>> {
+    rotate_recovery_codes(username, password)
+}
+
+/// Invalidate all existing recovery codes of an account and replace them with a fresh set. Return
+/// the new plaintext codes.
+pub fn rotate_recovery_codes(username: &str, password: &str) -> eyre::Result<Vec<String>> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let rotated_at = helpers::unix_timestamp();
+    let mut codes: Vec<String> = vec![];
+    let mut b64_recovery_codes = vec![];
+    for _ in 0..RecoveryCode::COUNT {
+        let (recovery_code, code) = RecoveryCode::generate(unlocked_account.username(), rotated_at);
+        b64_recovery_codes.push(recovery_code.to_b64());
+        codes.push(code);
+    }
+    db.replace_recovery_codes(unlocked_account.username(), b64_recovery_codes)?;
+
+    Ok(codes)
+}
+
+/// Get the time at which an account's recovery codes were last generated.
+/// Return [`Ok<None>`] if the account has no remaining recovery codes.
+pub fn recovery_codes_last_rotated(
+    username: &str,
+    password: &str,
+) -> eyre::Result<Option<SystemTime>> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let last_rotated_at = db
+        .get_b64_recovery_codes(unlocked_account.username())?
+        .unwrap_or_default()
+        .iter()
+        .map(|b64_recovery_code| b64_recovery_code.last_rotated_at)
+        .max();
+
+    Ok(last_rotated_at.map(|timestamp| UNIX_EPOCH + Duration::from_secs(timestamp as u64)))
+}
+
+/// Check the given recovery code against an account's stored recovery codes. If it matches, the
+/// code is consumed and `true` is returned.
+pub fn use_recovery_code(username: &str, code: &str) -> eyre::Result<bool> {
+    let mut db = load_db()?;
+
+    let b64_recovery_codes = match db.get_b64_recovery_codes(username)? {
+        Some(b64_recovery_codes) => b64_recovery_codes,
+        None => return Err(Error::AccountNotFoundError(username.to_owned()).into()),
+    };
+
+    for b64_recovery_code in b64_recovery_codes {
+        let recovery_code = RecoveryCode::from_b64(b64_recovery_code)?;
+        if recovery_code.check_match(code) {
+            db.delete_recovery_code(username, &recovery_code.hashed_code().hash_as_b64())?;
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
 /// Create a new account and store it in the database.
-pub fn new_account(username: String, password: String) -> eyre::Result<()> {
+pub fn new_account(
+    username: String,
+    password: String,
+    allow_weak: bool,
+    kdf: KdfAlgorithm,
+) -> eyre::Result<()> {
+    check_password_strength(&password, allow_weak)?;
     let confirm_password =
         rpassword::prompt_password(format!("Confirm Password for {}: ", username))?;
     if confirm_password != password {
@@ -61,7 +975,7 @@ pub fn new_account(username: String, password: String) -> eyre::Result<()> {
     let mut db = load_db()?;
 
     // Create Account.
-    let account = Account::new(&username, &password)?;
+    let account = Account::new_with_kdf(&username, &password, kdf)?;
 
     // Add to database.
     db.add_new_account(account.to_b64())?;
@@ -73,6 +987,95 @@ pub fn new_account(username: String, password: String) -> eyre::Result<()> {
     Ok(())
 }
 
+/// Create the data directory and database of a new vault, along with its first account. Fails if
+/// the database already exists.
+pub fn init_vault(
+    username: String,
+    password: String,
+    allow_weak: bool,
+    kdf: KdfAlgorithm,
+) -> eyre::Result<()> {
+    let db_path = database_path();
+    if db_path.exists() {
+        return Err(eyre!("A vault already exists at {db_path:?}."));
+    }
+    fs::create_dir_all(&config::get().data_dir)?;
+    if let Some(db_dir) = db_path.parent() {
+        fs::create_dir_all(db_dir)?;
+    }
+    // The database is only ever opened, never created, so create an empty one to open.
+    fs::File::create_new(&db_path)?;
+    if let Err(err) = new_account(username, password, allow_weak, kdf) {
+        remove_database_files(&db_path)?;
+        return Err(err);
+    }
+    Ok(())
+}
+
+// Remove the database at the given path along with its write-ahead log and shared-memory files,
+// if they exist.
+fn remove_database_files(db_path: &Path) -> io::Result<()> {
+    remove_file(db_path)?;
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar_path = db_path.as_os_str().to_owned();
+        sidecar_path.push(suffix);
+        match remove_file(&sidecar_path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The outcome of [change_account_password].
+#[derive(Debug, Default, Serialize)]
+pub struct ChangePasswordReport {
+    /// Number of stored passwords left untouched, since they are still encrypted with the same
+    /// account key.
+    pub credentials_unchanged: usize,
+    /// Number of stored files left untouched, since they are still encrypted with the same account
+    /// key.
+    pub files_unchanged: usize,
+}
+
+/// Change the password of an account. The account's encryption key is re-encrypted with the new
+/// password; stored passwords and files stay encrypted with the same key and are left untouched.
+///
+/// The new password is checked for strength first. The account is only updated in the database
+/// once everything else has succeeded, so the old password keeps working if anything goes wrong.
+pub fn change_account_password(
+    username: &str,
+    old_password: &str,
+    new_password: &str,
+    allow_weak: bool,
+) -> eyre::Result<ChangePasswordReport> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, old_password)?;
+    check_password_strength(new_password, allow_weak)?;
+
+    let mut account = match db.get_b64_account(unlocked_account.username())? {
+        Some(b64_account) => Account::from_b64(b64_account)?,
+        None => return Err(Error::AccountNotFoundError(username.to_owned()).into()),
+    };
+    if let Err(err) = account.set_password(&unlocked_account, new_password) {
+        eprintln!("Error re-encrypting account key— password unchanged.");
+        return Err(err.into());
+    }
+    if let Err(err) = db.update_account(account.to_b64()) {
+        eprintln!("Error updating account on database— password unchanged.");
+        return Err(err.into());
+    }
+
+    Ok(ChangePasswordReport {
+        credentials_unchanged: db
+            .count_rows_by_owner(Table::Passwords, username)?
+            .unwrap_or_default(),
+        files_unchanged: db
+            .count_rows_by_owner(Table::Files, username)?
+            .unwrap_or_default(),
+    })
+}
+
 /// Delete an existing account and all its files and passwords.
 pub fn delete_account(username: String, password: String, force: bool) -> eyre::Result<()> {
     let mut db = load_db()?;
@@ -137,7 +1140,8 @@ pub fn delete_account(username: String, password: String, force: bool) -> eyre::
     Ok(())
 }
 
-/// Create a new file, add its data to the database, and store it in the user directory.
+/// Create a new file, add its data to the database, and store it in the user directory. The new
+/// file is then opened in the default text editor; if editing fails, the file is removed again.
 pub fn new_file(username: String, password: String, filename: OsString) -> eyre::Result<()> {
     // Load account entry from db.
     let mut db = load_db()?;
@@ -147,8 +1151,13 @@ pub fn new_file(username: String, password: String, filename: OsString) -> eyre:
     let mut file_path = acc_path(&username);
     file_path.push(&filename);
 
+    // Files may have been moved out of the user directory, so check names as well as paths.
+    if file_name_exists(&db, unlocked_account.username(), &filename)? {
+        return Err(Error::FileAlreadyExistsError(file_path).into());
+    }
+
     // Create new file.
-    let file_data = FileData::new_with_key(
+    let mut file_data = FileData::new_with_key(
         unlocked_account.username(),
         unlocked_account.key(),
         filename,
@@ -164,77 +1173,259 @@ pub fn new_file(username: String, password: String, filename: OsString) -> eyre:
         return Err(err.into());
     }
 
+    // Write initial content in the editor— if err then undo file creation entirely.
+    let path_string = helpers::path_to_string(&file_path)?;
+    let edit_result = file_data
+        .edit(unlocked_account.key())
+        .map_err(eyre::Report::from)
+        .and_then(|_| {
+            Ok(db.update_file_content(
+                file_data.content_nonce(),
+                file_data.file_size(),
+                &path_string,
+            )?)
+        });
+    if let Err(err) = edit_result {
+        db.delete_file_data(&path_string)?;
+        fs::remove_file(&file_path)?;
+
+        eprintln!("Error editing new file— file creation cancelled.");
+        return Err(err);
+    }
+
     println!("File {:?} created successfully.", file_data.name());
     Ok(())
 }
 
-/// Decrypt and edit an existing file.
-pub fn open_file(username: String, password: String, filename: OsString) -> eyre::Result<()> {
+/// Store a copy of an existing plaintext file in the user directory and add its data to the
+/// database. The file keeps its name; the original is left untouched.
+pub fn store_file(username: &str, password: &str, source_path: &Path) -> eyre::Result<()> {
+    store_external_file(username, password, source_path, false)
+}
+
+/// Like [store_file], but compress the content of the file before encrypting it. This makes
+/// text-heavy files such as logs or JSON much smaller on disk. The file is decompressed
+/// transparently whenever it is opened.
+pub fn store_file_compressed(
+    username: &str,
+    password: &str,
+    source_path: &Path,
+) -> eyre::Result<()> {
+    store_external_file(username, password, source_path, true)
+}
+
+// Store a copy of an existing plaintext file, optionally compressed.
+fn store_external_file(
+    username: &str,
+    password: &str,
+    source_path: &Path,
+    compressed: bool,
+) -> eyre::Result<()> {
     // Load account entry from db.
     let mut db = load_db()?;
-    let unlocked_account = login(&mut db, &username, &password)?;
-
-    // Get file path.
-    let mut file_path = acc_path(&username);
-    file_path.push(&filename);
+    let unlocked_account = login(&mut db, username, password)?;
 
-    // Load file.
-    let mut file = match db.get_b64_file_data(&helpers::path_to_string(&file_path)?)? {
-        Some(b64_file_data) => FileData::from_b64(b64_file_data)?,
-        None => return Err(Error::FileNotFoundError(file_path).into()),
+    let filename = match source_path.file_name() {
+        Some(filename) => filename.to_owned(),
+        None => return Err(Error::FileNotFoundError(source_path.to_owned()).into()),
     };
+    let content = Zeroizing::new(fs::read(source_path)?);
 
-    // Load backup of file.
-    let backup = file.open_decrypted(unlocked_account.key())?;
+    // Get user directory.
+    let mut file_path = acc_path(username);
+    file_path.push(&filename);
 
-    // Edit file.
-    file.edit(unlocked_account.key())?;
+    // Files may have been moved out of the user directory, so check names as well as paths.
+    if file_name_exists(&db, unlocked_account.username(), &filename)? {
+        return Err(Error::FileAlreadyExistsError(file_path).into());
+    }
 
-    // Update file data to match new nonce. Undo changes if nonce change fails.
-    if let Err(err) =
-        db.update_file_content_nonce(file.content_nonce(), &helpers::path_to_string(&file_path)?)
-    {
-        FileData::encrypt_write_with_nonce(
+    // Create new file.
+    let file_data = if compressed {
+        FileData::new_compressed_with_content_and_key(
+            unlocked_account.username(),
+            unlocked_account.key(),
+            filename,
+            &content,
             &file_path,
-            &backup,
+        )?
+    } else {
+        FileData::new_with_content_and_key(
+            unlocked_account.username(),
             unlocked_account.key(),
-            file.content_nonce(),
-        )?;
-
-        eprintln!("Error updating file on database— deletion process cancelled.");
-        return Err(err.into());
+            filename,
+            &content,
+            &file_path,
+        )?
+    };
+
+    // Add to database— if err then undo file creation.
+    if let Err(err) = db.add_new_file_data(file_data.to_b64()?) {
+        // Undo change to disk.
+        fs::remove_file(&file_path)?;
+
+        eprintln!("Error creating file database entry— storing process cancelled.");
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
+/// Decrypt and edit an existing file. Compressed files are decompressed for editing and
+/// compressed again afterwards.
+pub fn open_file(username: String, password: String, filename: OsString) -> eyre::Result<()> {
+    // Load account entry from db.
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, &username, &password)?;
+
+    // Load file.
+    let mut file = find_file(&db, unlocked_account.username(), &filename)?;
+    let file_path = file.path().to_owned();
+    let old_nonce = *file.content_nonce();
+
+    // Load backup of file.
+    let backup = file.open_decrypted(unlocked_account.key())?;
+
+    // Edit file.
+    file.edit(unlocked_account.key())?;
+
+    // Update file data to match new nonce and size. Undo changes if the update fails.
+    if let Err(err) = db.update_file_content(
+        file.content_nonce(),
+        file.file_size(),
+        &helpers::path_to_string(&file_path)?,
+    ) {
+        FileData::encrypt_write_with_nonce(
+            &file_path,
+            &backup,
+            unlocked_account.key(),
+            &old_nonce,
+            file.compressed(),
+        )?;
+
+        eprintln!("Error updating file on database— edits cancelled.");
+        return Err(err.into());
+    };
+
+    println!("Edits to file {filename:?} saved.");
+    Ok(())
+}
+
+/// Re-encrypt an existing file with a freshly-generated nonce.
+pub fn reencrypt_file(username: String, password: String, filename: OsString) -> eyre::Result<()> {
+    // Load account entry from db.
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, &username, &password)?;
+
+    // Load file.
+    let mut file = find_file(&db, unlocked_account.username(), &filename)?;
+    let file_path = file.path().to_owned();
+    let old_nonce = *file.content_nonce();
+
+    // Load backup of file.
+    let backup = file.open_decrypted(unlocked_account.key())?;
+
+    // Re-encrypt file.
+    file.reencrypt(unlocked_account.key())?;
+
+    // Update file data to match new nonce. Undo changes if nonce change fails.
+    if let Err(err) =
+        db.update_file_content_nonce(file.content_nonce(), &helpers::path_to_string(&file_path)?)
+    {
+        FileData::encrypt_write_with_nonce(
+            &file_path,
+            &backup,
+            unlocked_account.key(),
+            &old_nonce,
+            file.compressed(),
+        )?;
+
+        eprintln!("Error updating file on database— re-encryption cancelled.");
+        return Err(err.into());
     };
 
-    println!("Edits to file {filename:?} saved.");
     Ok(())
 }
 
-/// Delete a file from the user directory and database.
+/// Re-encrypt all of an account's files with fresh nonces. Return the number of files
+/// re-encrypted.
+///
+/// Either all of the files are re-encrypted or none of them are; if anything goes wrong, every
+/// file which was already re-encrypted is restored.
+pub fn reencrypt_all_files(username: &str, password: &str) -> eyre::Result<u32> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    // Load backups of all files before changing any of them.
+    let mut files = get_files(unlocked_account.username())?;
+    let mut backups = vec![];
+    for file in &files {
+        backups.push((
+            file.open_decrypted(unlocked_account.key())?,
+            *file.content_nonce(),
+        ));
+    }
+
+    let result = (|| -> eyre::Result<u32> {
+        let mut updates = vec![];
+        for file in files.iter_mut() {
+            file.reencrypt(unlocked_account.key())?;
+            updates.push((*file.content_nonce(), helpers::path_to_string(file.path())?));
+        }
+        let num_files = updates.len() as u32;
+        db.update_file_content_nonces(updates)?;
+        Ok(num_files)
+    })();
+
+    // Undo all re-encryptions on failure. Try to restore every file even if some can't be.
+    if let Err(err) = result {
+        let mut restore_errors = vec![];
+        for (file, (backup, old_nonce)) in files.iter().zip(backups) {
+            if let Err(restore_err) = FileData::encrypt_write_with_nonce(
+                file.path(),
+                &backup,
+                unlocked_account.key(),
+                &old_nonce,
+                file.compressed(),
+            ) {
+                restore_errors.push(format!("{:?}: {restore_err}", file.path()));
+            }
+        }
+        eprintln!("Error re-encrypting files— re-encryption cancelled.");
+        if !restore_errors.is_empty() {
+            return Err(err.wrap_err(format!(
+                "Could not restore {} file(s):\n{}",
+                restore_errors.len(),
+                restore_errors.join("\n")
+            )));
+        }
+        return Err(err);
+    }
+    result
+}
+
+/// Delete a file from the user directory and database. Unless `force` is set, the deletion is
+/// confirmed on the command line first. Return whether the file was deleted.
 pub fn delete_file(
     username: String,
     password: String,
     filename: OsString,
     force: bool,
-) -> eyre::Result<()> {
+) -> eyre::Result<bool> {
     // Load account entry from db.
     let mut db = load_db()?;
     let unlocked_account = login(&mut db, &username, &password)?;
 
-    // Get file path.
-    let mut file_path = acc_path(unlocked_account.username());
-    file_path.push(&filename);
-
     // Load file.
-    let file = match db.get_b64_file_data(&helpers::path_to_string(&file_path)?)? {
-        Some(b64_file_data) => FileData::from_b64(b64_file_data)?,
-        None => return Err(Error::FileNotFoundError(file_path).into()),
-    };
+    let file = find_file(&db, unlocked_account.username(), &filename)?;
+    let file_path = file.path().to_owned();
 
     // CLI confirm deletion if not forced.
     if !force {
         print!(
-            "Really delete file \"{:?}\" at {:?}? [y/N] ",
+            "Really delete file {:?} owned by \"{}\" at {:?}? [y/N] ",
             file.name(),
+            file.owner_username(),
             file.path(),
         );
         let mut input = String::new();
@@ -244,58 +1435,188 @@ pub fn delete_file(
             Some('y') => {}
             _ => {
                 println!("File deletion cancelled.");
-                return Ok(());
+                return Ok(false);
             }
         }
     }
 
-    // Delete file database entry.
-    db.delete_file_data(&helpers::path_to_string(&file_path)?)?;
-
-    // Delete the file. Undo database changes if the file can't be deleted.
-    if let Err(err) = remove_file(&file_path) {
-        // Undo database changes.
-        db.add_new_file_data(file.to_b64()?)?;
-
+    // Delete file database entry and move the file aside. The database deletion is undone if the
+    // file can't be moved, and the file is only wiped once the deletion is committed, so the
+    // encrypted data is never lost while its entry still exists.
+    let mut deleted_path = file_path.clone().into_os_string();
+    deleted_path.push(DELETED_FILE_SUFFIX);
+    let deleted_path = PathBuf::from(deleted_path);
+    let delete_result = db.delete_file_data_with(
+        &helpers::path_to_string(&file_path)?,
+        || -> eyre::Result<()> {
+            fs::rename(&file_path, &deleted_path)?;
+            Ok(())
+        },
+    );
+    if let Err(err) = delete_result {
+        // The commit may have failed after the file was moved.
+        if deleted_path.exists() && !file_path.exists() {
+            fs::rename(&deleted_path, &file_path)?;
+        }
         eprintln!("Error deleting file— deletion process cancelled.");
-        return Err(err.into());
+        return Err(err);
     }
 
-    println!("File {:?} deleted successfully.", file.name());
-    Ok(())
+    // The file is already gone from the database, so failing to wipe it is only worth a warning.
+    if let Err(err) =
+        helpers::overwrite_with_zeros(&deleted_path).and_then(|_| remove_file(&deleted_path))
+    {
+        eprintln!(
+            "{}",
+            format!("Warning: could not wipe deleted file at {deleted_path:?}: {err}").yellow()
+        );
+    }
+
+    Ok(true)
 }
 
-/// Decrypt and list the names of this account's files.
-pub fn list_files(username: String, password: String) -> eyre::Result<()> {
+/// Return the data of all of this account's files.
+pub fn list_files(username: String, password: String) -> eyre::Result<Vec<FileData>> {
     // Load account entry from db.
     let mut db = load_db()?;
     let unlocked_account = login(&mut db, &username, &password)?;
 
-    // Load list of files.
-    let file_results =
-        if let Some(b64_files_data) = db.get_b64_files(unlocked_account.username())? {
-            b64_files_data.into_iter().map(FileData::from_b64)
-        } else {
-            return Err(Error::AccountNotFoundError(unlocked_account.username().to_owned()).into());
-        };
+    get_files(unlocked_account.username())
+}
 
-    let mut files: Vec<String> = vec![];
-    for file_result in file_results {
-        files.push(
-            file_result?
-                .path()
-                .to_owned()
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-        );
+/// Move an account's encrypted file out of the user directory into an external directory, e.g.
+/// a network share or an external drive. The file stays encrypted; its key and nonce remain in
+/// the database, which is updated to point to the new location.
+pub fn transfer_file_to_external(
+    username: &str,
+    password: &str,
+    filename: &OsStr,
+    external_dir: &Path,
+) -> eyre::Result<()> {
+    // Load account entry from db.
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    // Load file.
+    let file = find_file(&db, unlocked_account.username(), filename)?;
+    let mut new_path = external_dir.to_path_buf();
+    new_path.push(filename);
+    if new_path.exists() {
+        return Err(Error::FileAlreadyExistsError(new_path).into());
     }
 
-    println!("{}", files.join("\n"));
+    // Copy the ciphertext first so that the original is untouched if anything goes wrong.
+    fs::copy(file.path(), &new_path)?;
 
+    // Point the database entry to the new location— if err then undo the copy.
+    if let Err(err) = db.update_file_path(
+        &helpers::path_to_string(&new_path)?,
+        &helpers::path_to_string(file.path())?,
+    ) {
+        remove_file(&new_path)?;
+
+        eprintln!("Error updating file path on database— transfer cancelled.");
+        return Err(err.into());
+    }
+
+    remove_file(file.path())?;
     Ok(())
 }
 
+/// Load the [FileData] stored at the given path from the database.
+/// Return [`Ok<None>`] if no file with that path exists.
+///
+/// This only reads file metadata; the encrypted file itself is neither read nor decrypted.
+pub fn load_file_metadata(path: &Path) -> eyre::Result<Option<FileData>> {
+    let db = load_db()?;
+    match db.get_b64_file_data(&helpers::path_to_string(path)?)? {
+        Some(b64_file_data) => Ok(Some(FileData::from_b64(b64_file_data)?)),
+        None => Ok(None),
+    }
+}
+
+/// Return `true` iff the database has a file stored at the given path.
+pub fn file_exists(path: &Path) -> eyre::Result<bool> {
+    let db = load_db()?;
+    Ok(db.file_data_exists(&helpers::path_to_string(path)?)?)
+}
+
+/// The outcome of [bulk_export_files].
+#[derive(Debug, Default)]
+pub struct BulkExportReport {
+    /// Number of files decrypted and written to the destination directory.
+    pub exported: usize,
+    /// Number of files skipped because a file with that name already existed at the destination.
+    pub skipped: usize,
+    /// Paths of the stored files which could not be exported, alongside the reason why.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Decrypt all of an account's files and write them to the given directory under their original
+/// names. Existing files in the destination directory are skipped unless `overwrite` is set.
+///
+/// A failure to export one file does not stop the others from being exported.
+pub fn bulk_export_files(
+    username: &str,
+    password: &str,
+    dest_dir: &Path,
+    overwrite: bool,
+) -> eyre::Result<BulkExportReport> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let mut report = BulkExportReport::default();
+    for file in get_files(unlocked_account.username())? {
+        let mut dest_path = dest_dir.to_path_buf();
+        dest_path.push(file.name());
+
+        if !overwrite && dest_path.exists() {
+            report.skipped += 1;
+            continue;
+        }
+
+        let result = file
+            .open_decrypted(unlocked_account.key())
+            .map_err(|err| err.to_string())
+            .and_then(|content| fs::write(&dest_path, content).map_err(|err| err.to_string()));
+        match result {
+            Ok(()) => report.exported += 1,
+            Err(err) => report.failed.push((file.path().to_owned(), err)),
+        }
+    }
+
+    Ok(report)
+}
+
+// Find the file with the given name owned by the given account.
+fn find_file(db: &Database, username: &str, filename: &OsStr) -> eyre::Result<FileData> {
+    let b64_files_data = match db.get_b64_files(username)? {
+        Some(b64_files_data) => b64_files_data,
+        None => return Err(Error::AccountNotFoundError(username.to_owned()).into()),
+    };
+
+    for b64_file_data in b64_files_data {
+        let file_data = FileData::from_b64(b64_file_data)?;
+        if file_data.name() == filename {
+            return Ok(file_data);
+        }
+    }
+
+    Err(Error::FileNotFoundError(PathBuf::from(filename)).into())
+}
+
+/// Check whether the given account has a file with the given name. Errors other than the file not
+/// existing are returned.
+fn file_name_exists(db: &Database, username: &str, filename: &OsStr) -> eyre::Result<bool> {
+    match find_file(db, username, filename) {
+        Ok(_) => Ok(true),
+        Err(err) => match err.downcast_ref::<Error>() {
+            Some(Error::FileNotFoundError(_)) => Ok(false),
+            _ => Err(err),
+        },
+    }
+}
+
 /// Decrypt and get this account's files.
 fn get_files(username: &str) -> eyre::Result<Vec<FileData>> {
     let db = load_db()?;
@@ -315,68 +1636,810 @@ fn get_files(username: &str) -> eyre::Result<Vec<FileData>> {
     Ok(files)
 }
 
-/// Create a new password in the database.
+/// Create a new password in the database, optionally in the given comma-separated categories and
+/// expiring on the given `YYYY-MM-DD` date. The URL is prompted for unless one is given.
 pub fn new_password(
     username: String,
     password: String,
     passwordname: OsString,
+    allow_weak: bool,
+    url: Option<String>,
+    category: Option<String>,
+    expires_at: Option<String>,
 ) -> eyre::Result<()> {
-    // TODO
+    let password_name = passwordname.to_string_lossy();
+    let expires_at = match &expires_at {
+        Some(expires_at) => parse_expiry_date(expires_at)?,
+        None => None,
+    };
+
     // Load account entry from db.
     let mut db = load_db()?;
     let unlocked_account = login(&mut db, &username, &password)?;
 
-    // Create new password.
+    // Fail before prompting for anything if the name is taken.
+    if password_exists(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        &password_name,
+    )? {
+        return Err(Error::PasswordAlreadyExistsError(password_name.into_owned()).into());
+    }
 
-    // Add to database.
+    // Prompt for password fields.
+    let password_username = prompt_line("Username: ")?;
+    let content = rpassword::prompt_password(format!("Password for {password_name:?}: "))?;
+    check_password_strength(&content, allow_weak)?;
+    let confirm_content =
+        rpassword::prompt_password(format!("Confirm Password for {password_name:?}: "))?;
+    if confirm_content != content {
+        return Err(eyre!("Passwords for new password do not match."));
+    }
+    let notes = prompt_line("Notes: ")?;
+    let url = match url {
+        Some(url) => url,
+        None => prompt_line("URL: ")?,
+    };
+
+    // Create new password and add it to the database.
+    let fields = PasswordChanges::new()
+        .new_username(&password_username)
+        .new_content(&content)
+        .new_notes(&notes)
+        .new_url(&url)
+        .new_category(category.as_deref().unwrap_or_default())
+        .new_expires_at(expires_at);
+    let new_password = add_password(&mut db, &unlocked_account, &password_name, &fields)?;
 
     println!("Password {passwordname:?} created successfully.");
+    warn_if_password_reused(&unlocked_account, &new_password, &password_name, &content)?;
     Ok(())
 }
 
-/// Decrypt and edit an existing password.
-pub fn open_password(username: String, password: String, filename: OsString) -> eyre::Result<()> {
-    // TODO
+/// Decrypt and display an existing password, then prompt for changes to each of its fields. The
+/// password content is masked unless `reveal` is set. If a URL is given, the password's URL is
+/// set to it instead of being prompted for; an empty URL removes the password's URL. Likewise, if
+/// a category or a `YYYY-MM-DD` expiry date is given, the password is moved to that category or
+/// set to expire on that date.
+pub fn open_password(
+    username: String,
+    password: String,
+    passwordname: OsString,
+    reveal: bool,
+    url: Option<String>,
+    category: Option<String>,
+    expires_at: Option<String>,
+) -> eyre::Result<()> {
+    let expires_at = match &expires_at {
+        Some(expires_at) => Some(parse_expiry_date(expires_at)?),
+        None => None,
+    };
+
+    // Load account entry from db.
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, &username, &password)?;
+
+    // Load and decrypt password.
+    let mut stored_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        &passwordname.to_string_lossy(),
+    )?;
+    let unlocked_password = stored_password.unlock(unlocked_account.key())?;
+    print_password(&stored_password, &unlocked_password, reveal);
+
+    // Prompt for changes. Empty input keeps the current value.
+    println!("Enter new values, or leave blank to keep the current value.");
+    let mut changes = PasswordChanges::new();
+    let name = prompt_line("Name: ")?;
+    if !name.is_empty() {
+        if password_exists(unlocked_account.username(), unlocked_account.key(), &name)? {
+            return Err(Error::PasswordAlreadyExistsError(name).into());
+        }
+        changes = changes.new_name(&name);
+    }
+    let password_username = prompt_line("Username: ")?;
+    if !password_username.is_empty() {
+        changes = changes.new_username(&password_username);
+    }
+    let content = rpassword::prompt_password("Password: ")?;
+    if !content.is_empty() {
+        let confirm_content = rpassword::prompt_password("Confirm Password: ")?;
+        if confirm_content != content {
+            return Err(eyre!("Passwords do not match."));
+        }
+        changes = changes.new_content(&content);
+    }
+    let notes = prompt_line("Notes: ")?;
+    if !notes.is_empty() {
+        changes = changes.new_notes(&notes);
+    }
+    match url {
+        Some(url) => changes = changes.new_url(&url),
+        None => {
+            let url = prompt_line("URL: ")?;
+            if !url.is_empty() {
+                changes = changes.new_url(&url);
+            }
+        }
+    }
+    if let Some(category) = &category {
+        changes = changes.new_category(category);
+    }
+    if let Some(expires_at) = expires_at {
+        changes = changes.new_expires_at(expires_at);
+    }
+
+    if changes.is_empty() {
+        println!("Password {passwordname:?} unchanged.");
+        return Ok(());
+    }
+
+    // Re-encrypt changed fields, then replace the stored password in a single transaction. The
+    // old content is kept in the password's history.
+    let old_b64_name_ciphertext = stored_password.encrypted_name().ciphertext_as_b64();
+    let history = history_entries(&stored_password, &changes);
+    stored_password.apply_changes(unlocked_account.key(), &changes)?;
+    db.update_passwords_with_history(
+        vec![(old_b64_name_ciphertext, stored_password.to_b64())],
+        history,
+    )?;
+
+    println!("Password {passwordname:?} updated successfully.");
+    if let Some(content) = &changes.content {
+        let name = changes.name.as_deref().unwrap_or(unlocked_password.name());
+        warn_if_password_reused(&unlocked_account, &stored_password, name, content)?;
+    }
+    Ok(())
+}
+
+/// All fields of a stored password, as shown to the user. The password content is only set if
+/// it is revealed.
+#[derive(Debug, Serialize)]
+pub struct PasswordDetails {
+    /// Name of the password.
+    pub name: String,
+    /// Username associated with the password.
+    pub username: String,
+    /// Password content, or [None] if it is masked.
+    pub password: Option<String>,
+    /// Notes associated with the password.
+    pub notes: String,
+    /// URL associated with the password, if any.
+    pub url: Option<String>,
+    /// Category of the password, if any.
+    pub category: Option<String>,
+    /// Date the password expires, if any.
+    pub expires_at: Option<NaiveDate>,
+    /// When the password was created, as a Unix timestamp in seconds.
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub created_at: DateTime<Utc>,
+    /// When the password was last changed, as a Unix timestamp in seconds.
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub updated_at: DateTime<Utc>,
+}
+impl PasswordDetails {
+    /// Gather the fields of a stored password. The password content is masked unless `reveal`
+    /// is set.
+    pub fn new(
+        stored_password: &Password,
+        unlocked_password: &DecryptedPasswordFields,
+        reveal: bool,
+    ) -> Self {
+        Self {
+            name: unlocked_password.name().to_owned(),
+            username: unlocked_password.username().to_owned(),
+            password: reveal.then(|| unlocked_password.content().to_owned()),
+            notes: unlocked_password.notes().to_owned(),
+            url: unlocked_password.url().map(str::to_owned),
+            category: stored_password.category().map(str::to_owned),
+            expires_at: stored_password.expires_at(),
+            created_at: stored_password.created_at(),
+            updated_at: stored_password.updated_at(),
+        }
+    }
+}
+impl fmt::Display for PasswordDetails {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Name:     {}", self.name)?;
+        writeln!(f, "Username: {}", self.username)?;
+        writeln!(
+            f,
+            "Password: {}",
+            self.password.as_deref().unwrap_or("********")
+        )?;
+        writeln!(f, "Notes:    {}", self.notes)?;
+        writeln!(f, "URL:      {}", self.url.as_deref().unwrap_or_default())?;
+        writeln!(
+            f,
+            "Category: {}",
+            self.category.as_deref().unwrap_or_default()
+        )?;
+        writeln!(
+            f,
+            "Expires:  {}",
+            self.expires_at
+                .map(|expires_at| expires_at.to_string())
+                .unwrap_or_default()
+        )?;
+        writeln!(f, "Created:  {}", helpers::format_datetime(self.created_at))?;
+        writeln!(f, "Updated:  {}", helpers::format_datetime(self.updated_at))
+    }
+}
+impl Drop for PasswordDetails {
+    fn drop(&mut self) {
+        self.password.zeroize();
+    }
+}
+
+/// Decrypt a stored password without changing it, and return its fields. The password content
+/// is masked unless `reveal` is set. The account's last login time isn't updated.
+pub fn show_password(
+    username: &str,
+    password: &str,
+    passwordname: &str,
+    reveal: bool,
+) -> eyre::Result<PasswordDetails> {
+    let (stored_password, unlocked_password) = load_password(username, password, passwordname)?;
+    Ok(PasswordDetails::new(
+        &stored_password,
+        &unlocked_password,
+        reveal,
+    ))
+}
+
+/// Decrypt a single field of a stored password without changing it, and return it unmasked. See
+/// [PasswordField::from_name] for the field names. The account's last login time isn't updated.
+pub fn show_password_field(
+    username: &str,
+    password: &str,
+    passwordname: &str,
+    field_name: &str,
+) -> eyre::Result<String> {
+    let field = PasswordField::from_name(field_name)
+        .ok_or_else(|| eyre!("Password field \"{field_name}\" does not exist."))?;
+    let (_, unlocked_password) = load_password(username, password, passwordname)?;
+    Ok(unlocked_password.get(field).to_owned())
+}
+
+// Decrypt a stored password without logging in, so nothing is written to the database. Return
+// the stored password alongside its decrypted fields.
+fn load_password(
+    username: &str,
+    password: &str,
+    passwordname: &str,
+) -> eyre::Result<(Password, DecryptedPasswordFields)> {
+    let db = load_db()?;
+    let unlocked_account = authenticate(&db, username, password)?;
+
+    let stored_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        passwordname,
+    )?;
+    let unlocked_password = stored_password.unlock(unlocked_account.key())?;
+    Ok((stored_password, unlocked_password))
+}
+
+/// Print all fields of a stored password, one per line. The password content is masked unless
+/// `reveal` is set.
+pub fn print_password(
+    stored_password: &Password,
+    unlocked_password: &DecryptedPasswordFields,
+    reveal: bool,
+) {
+    print!(
+        "{}",
+        PasswordDetails::new(stored_password, unlocked_password, reveal)
+    );
+}
+
+/// Group the names of an account's stored passwords by shared content. Only groups of two or more
+/// passwords are returned. The names in each group are sorted, and so are the groups.
+///
+/// Empty passwords aren't counted as shared.
+pub fn find_duplicate_passwords(username: &str, password: &str) -> eyre::Result<Vec<Vec<String>>> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+    duplicate_password_groups(&unlocked_account)
+}
+
+// Group the names of an account's stored passwords by shared content.
+fn duplicate_password_groups(unlocked_account: &SecureFields) -> eyre::Result<Vec<Vec<String>>> {
+    let mut names_by_content: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for stored_password in get_passwords(unlocked_account.username())? {
+        let unlocked_password = stored_password.unlock(unlocked_account.key())?;
+        if unlocked_password.content().is_empty() {
+            continue;
+        }
+        names_by_content
+            .entry(unlocked_password.content().to_owned())
+            .or_default()
+            .push(unlocked_password.name().to_owned());
+    }
+
+    let mut groups: Vec<Vec<String>> = names_by_content
+        .into_iter()
+        .filter_map(|(mut content, mut names)| {
+            content.zeroize();
+            (names.len() > 1).then(|| {
+                names.sort();
+                names
+            })
+        })
+        .collect();
+    groups.sort();
+    Ok(groups)
+}
+
+/// Return how often the given password appears in the "Have I Been Pwned" database of breached
+/// passwords. Only the first five characters of its SHA-1 hash are sent; see [breach].
+pub fn check_password_breach(password: &str) -> eyre::Result<u64> {
+    let (prefix, suffix) = breach::hash_prefix_and_suffix(password);
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(BREACH_CHECK_TIMEOUT_SECS))
+        .build();
+    let response = agent
+        .get(&format!("{}{prefix}", breach::RANGE_URL))
+        .set("Add-Padding", "true")
+        .call()?
+        .into_string()?;
+    Ok(breach::count_in_range_response(&suffix, &response))
+}
+
+/// Check all stored passwords of an account against the "Have I Been Pwned" database. Returns
+/// the name of each password alongside its number of breaches, sorted by name.
+///
+/// Empty passwords aren't checked and have a count of [None]. If the lookup fails, e.g. because
+/// there is no network connection, a warning is printed and the remaining passwords are returned
+/// with a count of [None] too.
+pub fn audit_breaches(username: &str, password: &str) -> eyre::Result<Vec<(String, Option<u64>)>> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let mut unlocked_passwords = vec![];
+    for stored_password in get_passwords(unlocked_account.username())? {
+        unlocked_passwords.push(stored_password.unlock(unlocked_account.key())?);
+    }
+    unlocked_passwords.sort_by(|a, b| a.name().cmp(b.name()));
+
+    let mut offline = false;
+    let mut results = vec![];
+    for unlocked_password in unlocked_passwords {
+        let count = if offline || unlocked_password.content().is_empty() {
+            None
+        } else {
+            match check_password_breach(unlocked_password.content()) {
+                Ok(count) => Some(count),
+                Err(e) => {
+                    eprintln!(
+                        "{}",
+                        format!(
+                            "Warning: breach lookup failed, remaining passwords not checked: {e}"
+                        )
+                        .yellow()
+                    );
+                    offline = true;
+                    None
+                }
+            }
+        };
+        results.push((unlocked_password.name().to_owned(), count));
+    }
+    Ok(results)
+}
+
+// Warn if any stored password of the account other than the given one, which is named
+// `password_name`, has the given content.
+fn warn_if_password_reused(
+    unlocked_account: &SecureFields,
+    password: &Password,
+    password_name: &str,
+    content: &str,
+) -> eyre::Result<()> {
+    let other_names: Vec<String> = reused_password_names(unlocked_account, password, content)?
+        .iter()
+        .map(|name| format!("{name:?}"))
+        .collect();
+    if !other_names.is_empty() {
+        eprintln!(
+            "{}",
+            format!(
+                "Warning: password {password_name:?} is also used by {}.",
+                other_names.join(", ")
+            )
+            .yellow()
+        );
+    }
     Ok(())
 }
 
-/// Delete a password from the user directory and database.
+// Return the sorted names of the stored passwords of the account other than the given one which
+// have the given content. Passwords are told apart by their stored name ciphertext, so the given
+// password is left out even if it was just renamed.
+fn reused_password_names(
+    unlocked_account: &SecureFields,
+    password: &Password,
+    content: &str,
+) -> eyre::Result<Vec<String>> {
+    if content.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut names = vec![];
+    for stored_password in get_passwords(unlocked_account.username())? {
+        if stored_password.encrypted_name().ciphertext() == password.encrypted_name().ciphertext() {
+            continue;
+        }
+        let unlocked_password = stored_password.unlock(unlocked_account.key())?;
+        if unlocked_password.content() == content {
+            names.push(unlocked_password.name().to_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Delete a password and its attachments from the user directory and database. Unless `force` is
+/// set, the deletion is confirmed on the command line first. Return whether the password was
+/// deleted.
 pub fn delete_password(
     username: String,
     password: String,
     passwordname: OsString,
     force: bool,
-) -> eyre::Result<()> {
-    // TODO
-    Ok(())
+) -> eyre::Result<bool> {
+    // Load account entry from db.
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, &username, &password)?;
+
+    // Load password and its attachments.
+    let stored_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        &passwordname.to_string_lossy(),
+    )?;
+    let b64_name_ciphertext = stored_password.encrypted_name().ciphertext_as_b64();
+    let attachments = db.get_b64_attachments(unlocked_account.username(), &b64_name_ciphertext)?;
+
+    // CLI confirm deletion if not forced.
+    if !force {
+        print!(
+            "Really delete password {:?} with {} attachment(s)? [y/N] ",
+            passwordname,
+            attachments.len()
+        );
+        let mut input = String::new();
+        io::stdout().flush()?;
+        io::stdin().read_line(&mut input)?;
+        match input.to_lowercase().chars().next() {
+            Some('y') => {}
+            _ => {
+                println!("Password deletion cancelled.");
+                return Ok(false);
+            }
+        }
+    }
+
+    // Delete password database entry. Its attachment entries are deleted along with it.
+    db.delete_password(unlocked_account.username(), &b64_name_ciphertext)?;
+
+    // The attachment data can't be decrypted without its database entry, so failing to delete it
+    // is only worth a warning.
+    for b64_attachment in attachments {
+        let attachment = Attachment::from_b64(b64_attachment)?;
+        if let Err(err) = remove_file(attachment.path()) {
+            eprintln!(
+                "Warning: could not delete attachment data at {:?}: {err}",
+                attachment.path()
+            );
+        }
+    }
+
+    Ok(true)
 }
-/// Decrypt and list the names of this account's passwords.
-pub fn list_passwords(username: String, password: String) -> eyre::Result<()> {
+
+/// Return this account's stored passwords alongside their decrypted names. No other fields are
+/// decrypted. If a category is given, only passwords in that category are returned.
+///
+/// If `show_expired` or `show_expiring_soon` is set, only passwords which have expired or which
+/// expire within [password::EXPIRY_WARNING_DAYS] days respectively are returned.
+pub fn list_passwords(
+    username: String,
+    password: String,
+    category: Option<String>,
+    show_expired: bool,
+    show_expiring_soon: bool,
+) -> eyre::Result<Vec<(String, Password)>> {
     // Load account entry from db.
     let mut db = load_db()?;
     let unlocked_account = login(&mut db, &username, &password)?;
 
     // Load list of passwords.
-    let password_results =
-        if let Some(b64_passwords) = db.get_b64_passwords(unlocked_account.username())? {
-            b64_passwords.into_iter().map(Password::from_b64)
-        } else {
-            return Err(Error::AccountNotFoundError(unlocked_account.username().to_owned()).into());
-        };
+    let b64_passwords = match &category {
+        Some(category) => {
+            db.get_b64_passwords_by_category(unlocked_account.username(), category)?
+        }
+        None => db.get_b64_passwords(unlocked_account.username())?,
+    };
+    let password_results = if let Some(b64_passwords) = b64_passwords {
+        b64_passwords.into_iter().map(Password::from_b64)
+    } else {
+        return Err(Error::AccountNotFoundError(unlocked_account.username().to_owned()).into());
+    };
 
-    let mut passwords: Vec<String> = vec![];
+    let today = Local::now().date_naive();
+    let mut passwords: Vec<(String, Password)> = vec![];
     for password_result in password_results {
-        passwords.push(helpers::bytes_to_utf8(
-            &password_result?
+        let stored_password = password_result?;
+        let expiry_status = stored_password.expiry_status(today);
+        let shown = match expiry_status {
+            _ if !show_expired && !show_expiring_soon => true,
+            ExpiryStatus::Expired => show_expired,
+            ExpiryStatus::ExpiringSoon => show_expiring_soon,
+            ExpiryStatus::Valid => false,
+        };
+        if !shown {
+            continue;
+        }
+        passwords.push((
+            helpers::bytes_to_utf8(
+                &stored_password
+                    .encrypted_name()
+                    .decrypt(unlocked_account.key())?,
+                "password",
+            )?,
+            stored_password,
+        ));
+    }
+
+    Ok(passwords)
+}
+
+/// Re-encrypt every field of all of an account's passwords with fresh nonces. Return the number of
+/// passwords re-encrypted.
+///
+/// All passwords are updated in a single transaction; if any of them fail, none are changed.
+pub fn reencrypt_all_passwords(username: &str, password: &str) -> eyre::Result<u32> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let mut updates = vec![];
+    for mut stored_password in get_passwords(unlocked_account.username())? {
+        let old_b64_name_ciphertext = stored_password.encrypted_name().ciphertext_as_b64();
+        stored_password.reencrypt(unlocked_account.key())?;
+        updates.push((old_b64_name_ciphertext, stored_password.to_b64()));
+    }
+
+    let num_passwords = updates.len() as u32;
+    db.update_passwords(updates)?;
+    Ok(num_passwords)
+}
+
+/// The outcome of [batch_update_passwords].
+#[derive(Debug, Default)]
+pub struct BatchUpdateReport {
+    /// Number of stored passwords updated.
+    pub updated: usize,
+}
+
+/// Apply a set of [PasswordUpdate]s to an account's stored passwords.
+///
+/// Either all of the updates are applied or none of them are. In particular, nothing is changed if
+/// any of the passwords to update cannot be found, if a password is targeted more than once, or if
+/// the updates would leave two passwords with the same name. Updates without any changes are
+/// skipped.
+pub fn batch_update_passwords(
+    username: &str,
+    password: &str,
+    updates: Vec<PasswordUpdate>,
+) -> eyre::Result<BatchUpdateReport> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let mut targets = HashSet::new();
+    for update in &updates {
+        if !targets.insert(update.name.as_str()) {
+            return Err(eyre!(
+                "Password \"{}\" is updated more than once.",
+                update.name
+            ));
+        }
+    }
+
+    // Check the names every password will have once all renames are applied.
+    let mut final_names = HashSet::new();
+    for stored_password in get_passwords(unlocked_account.username())? {
+        let name = helpers::bytes_to_utf8(
+            &stored_password
                 .encrypted_name()
                 .decrypt(unlocked_account.key())?,
-            "password",
-        )?);
+            "password_name",
+        )?;
+        let final_name = updates
+            .iter()
+            .find(|update| update.name == name)
+            .and_then(|update| update.changes.name.clone())
+            .unwrap_or(name);
+        if final_names.contains(&final_name) {
+            return Err(Error::PasswordAlreadyExistsError(final_name).into());
+        }
+        final_names.insert(final_name);
+    }
+
+    let mut db_updates = vec![];
+    let mut history = vec![];
+    for update in updates {
+        if update.changes.is_empty() {
+            continue;
+        }
+        let mut stored_password = find_password(
+            unlocked_account.username(),
+            unlocked_account.key(),
+            &update.name,
+        )?;
+        let old_b64_name_ciphertext = stored_password.encrypted_name().ciphertext_as_b64();
+        history.extend(history_entries(&stored_password, &update.changes));
+        stored_password.apply_changes(unlocked_account.key(), &update.changes)?;
+        db_updates.push((old_b64_name_ciphertext, stored_password.to_b64()));
     }
 
-    println!("{}", passwords.join("\n"));
+    let report = BatchUpdateReport {
+        updated: db_updates.len(),
+    };
+    db.update_passwords_with_history(db_updates, history)?;
+    Ok(report)
+}
 
-    Ok(())
+// Return the history entry recording the current content of a stored password, if the given
+// changes replace it.
+fn history_entries(
+    stored_password: &Password,
+    changes: &PasswordChanges,
+) -> Vec<history::Base64PasswordHistoryEntry> {
+    match changes.content {
+        Some(_) => vec![PasswordHistoryEntry::new(stored_password, Utc::now()).to_b64()],
+        None => vec![],
+    }
+}
+
+/// Decrypt the former contents of a stored password, newest first, alongside the time at which
+/// each of them was replaced. Only the last [history::MAX_PASSWORD_HISTORY] contents are kept.
+pub fn get_password_history(
+    username: &str,
+    password: &str,
+    passwordname: &str,
+) -> eyre::Result<Vec<(DateTime<Utc>, String)>> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let stored_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        passwordname,
+    )?;
+    let mut history = vec![];
+    for b64_entry in db.get_b64_password_history(
+        unlocked_account.username(),
+        &stored_password.encrypted_name().ciphertext_as_b64(),
+    )? {
+        let entry = PasswordHistoryEntry::from_b64(b64_entry)?;
+        history.push((entry.changed_at(), entry.decrypt(unlocked_account.key())?));
+    }
+    Ok(history)
+}
+
+/// Set the TOTP secret of a stored password to the given base-32 secret, as shown by sites when
+/// setting up two-factor authentication. An empty secret removes the password's TOTP secret.
+/// Return whether the password has a TOTP secret afterwards.
+pub fn set_totp_secret(
+    username: &str,
+    password: &str,
+    passwordname: &str,
+    b32_secret: &str,
+) -> eyre::Result<bool> {
+    let secret = Zeroizing::new(helpers::base32_to_bytes(b32_secret)?);
+
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let mut stored_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        passwordname,
+    )?;
+    let old_b64_name_ciphertext = stored_password.encrypted_name().ciphertext_as_b64();
+    stored_password.apply_changes(
+        unlocked_account.key(),
+        &PasswordChanges::new().new_totp_secret(&secret),
+    )?;
+    db.update_passwords(vec![(old_b64_name_ciphertext, stored_password.to_b64())])?;
+    Ok(!secret.is_empty())
+}
+
+/// Generate the current TOTP code of a stored password. Return the code alongside the number of
+/// seconds for which it stays valid.
+pub fn generate_totp(
+    username: &str,
+    password: &str,
+    passwordname: &str,
+) -> eyre::Result<(String, u64)> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let stored_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        passwordname,
+    )?;
+    let now = helpers::unix_timestamp() as u64;
+    match stored_password.generate_totp(unlocked_account.key(), now)? {
+        Some(code) => Ok((code, totp_seconds_remaining(now))),
+        None => Err(eyre!("Password {passwordname:?} has no TOTP secret.")),
+    }
+}
+
+/// Where [copy_to_clipboard] put the copied value.
+pub enum CopiedValue {
+    /// The value is on the clipboard. Unless the configured `clipboard_clear_secs` is 0, the
+    /// thread clears the clipboard after that many seconds if it still holds the copied value.
+    /// Wait for it before exiting: on some platforms, the clipboard is emptied when the process
+    /// which set it exits.
+    Clipboard(Option<JoinHandle<()>>),
+    /// The clipboard couldn't be accessed, so the value is returned to be shown instead.
+    Unavailable(Zeroizing<String>),
+}
+
+/// Copy a field of a stored password to the clipboard without displaying it. If the clipboard
+/// can't be accessed, a warning is printed and the value is returned instead.
+pub fn copy_to_clipboard(
+    username: &str,
+    password: &str,
+    passwordname: &str,
+    field: PasswordField,
+) -> eyre::Result<CopiedValue> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let unlocked_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        passwordname,
+    )?
+    .unlock(unlocked_account.key())?;
+    let value = Zeroizing::new(unlocked_password.get(field).to_owned());
+
+    let copied = arboard::Clipboard::new().and_then(|mut clipboard| {
+        clipboard.set_text(value.as_str())?;
+        Ok(clipboard)
+    });
+    let mut clipboard = match copied {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format!(
+                    "Warning: could not access the clipboard ({e}). Showing the {field} instead."
+                )
+                .yellow()
+            );
+            return Ok(CopiedValue::Unavailable(value));
+        }
+    };
+
+    let clear_secs = config::get().clipboard_clear_secs;
+    if clear_secs == 0 {
+        return Ok(CopiedValue::Clipboard(None));
+    }
+    Ok(CopiedValue::Clipboard(Some(thread::spawn(move || {
+        thread::sleep(Duration::from_secs(clear_secs));
+        // Leave the clipboard alone if something else has been copied since.
+        if let Ok(mut text) = clipboard.get_text() {
+            if text == *value {
+                let _ = clipboard.clear();
+            }
+            text.zeroize();
+        }
+    }))))
 }
 
 /// Get the given account's passwords.
@@ -397,3 +2460,331 @@ fn get_passwords(username: &str) -> eyre::Result<Vec<Password>> {
 
     Ok(passwords)
 }
+
+/// Decrypt the given fields of a stored password and format them as shell `export` statements,
+/// one per line. `mapping` maps password field names to the names of the environment variables to
+/// export them as. See [password::DecryptedPasswordFields::field] for the valid field names.
+pub fn export_password_to_env(
+    username: &str,
+    password: &str,
+    password_name: &str,
+    mapping: &[(&str, &str)],
+) -> eyre::Result<String> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+    let unlocked_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        password_name,
+    )?
+    .unlock(unlocked_account.key())?;
+
+    let mut exports = String::new();
+    for (field_name, env_var_name) in mapping {
+        let value = match unlocked_password.field(field_name) {
+            Some(value) => value,
+            None => return Err(eyre!("Password field \"{field_name}\" does not exist.")),
+        };
+        if !helpers::is_env_var_name(env_var_name) {
+            return Err(eyre!(
+                "\"{env_var_name}\" is not a valid environment variable name."
+            ));
+        }
+        exports.push_str(&format!(
+            "export {env_var_name}={}\n",
+            helpers::shell_quote(value)
+        ));
+    }
+
+    Ok(exports)
+}
+
+/// Create a new stored password with the given name from the values of environment variables.
+/// `mapping` maps password field names (`username`, `content` or its alias `password`, `notes`,
+/// and `url`) to the names of the environment variables to read them from. Unmapped fields are
+/// left empty.
+pub fn create_password_from_env(
+    username: &str,
+    password: &str,
+    password_name: &str,
+    mapping: &[(&str, &str)],
+) -> eyre::Result<()> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+    if password_exists(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        password_name,
+    )? {
+        return Err(Error::PasswordAlreadyExistsError(password_name.to_owned()).into());
+    }
+
+    let mut fields = PasswordChanges::new();
+    for (field_name, env_var_name) in mapping {
+        let value = match std::env::var(env_var_name) {
+            Ok(value) => value,
+            Err(err) => return Err(eyre!("Could not read \"{env_var_name}\": {err}")),
+        };
+        fields = match *field_name {
+            "username" => fields.new_username(&value),
+            "content" | "password" => fields.new_content(&value),
+            "notes" => fields.new_notes(&value),
+            "url" => fields.new_url(&value),
+            _ => return Err(eyre!("Password field \"{field_name}\" cannot be set.")),
+        };
+    }
+
+    add_password(&mut db, &unlocked_account, password_name, &fields)?;
+    Ok(())
+}
+
+// Encrypt a new stored password of the given unlocked account with the given name and the fields
+// set in `fields`, and add it to the database. Fields which aren't set are left empty. Return the
+// new stored password.
+fn add_password(
+    db: &mut Database,
+    unlocked_account: &SecureFields,
+    password_name: &str,
+    fields: &PasswordChanges,
+) -> eyre::Result<Password> {
+    let mut new_password = Password::new_with_key(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        password_name,
+        fields.username.as_deref().unwrap_or_default(),
+        fields.content.as_deref().unwrap_or_default(),
+        fields.notes.as_deref().unwrap_or_default(),
+    )?;
+    new_password.apply_changes(
+        unlocked_account.key(),
+        &PasswordChanges {
+            url: fields.url.clone(),
+            category: fields.category.clone(),
+            expires_at: fields.expires_at,
+            ..Default::default()
+        },
+    )?;
+    db.add_new_password(new_password.to_b64())?;
+    Ok(new_password)
+}
+
+// Print a coloured strength indicator for a new password, failing if it is weak and weak
+// passwords aren't allowed.
+fn check_password_strength(password: &str, allow_weak: bool) -> eyre::Result<()> {
+    let strength = password_strength(password);
+    let indicator = match strength {
+        PasswordStrength::Weak => strength.to_string().red(),
+        PasswordStrength::Fair => strength.to_string().yellow(),
+        PasswordStrength::Strong => strength.to_string().green(),
+        PasswordStrength::VeryStrong => strength.to_string().cyan(),
+    };
+    println!("Password strength: {indicator}");
+    if strength == PasswordStrength::Weak && !allow_weak {
+        return Err(eyre!(
+            "Password is too weak. Use --allowweakpassword to use it anyway."
+        ));
+    }
+    Ok(())
+}
+
+// Print a prompt, then read one line of input without its trailing newline.
+fn prompt_line(prompt: &str) -> eyre::Result<String> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim_end_matches(['\n', '\r']).to_owned())
+}
+
+// Find the stored password with the given name owned by the given account.
+fn find_password(username: &str, key: &KeyMaterial, password_name: &str) -> eyre::Result<Password> {
+    for stored_password in get_passwords(username)? {
+        let name = helpers::bytes_to_utf8(
+            &stored_password.encrypted_name().decrypt(key)?,
+            "password_name",
+        )?;
+        if name == password_name {
+            return Ok(stored_password);
+        }
+    }
+
+    Err(Error::PasswordNotFoundError(password_name.to_owned()).into())
+}
+
+/// Check whether the given account has a password with the given name. Errors other than the
+/// password not existing are returned.
+fn password_exists(username: &str, key: &KeyMaterial, password_name: &str) -> eyre::Result<bool> {
+    match find_password(username, key, password_name) {
+        Ok(_) => Ok(true),
+        Err(err) => match err.downcast_ref::<Error>() {
+            Some(Error::PasswordNotFoundError(_)) => Ok(false),
+            _ => Err(err),
+        },
+    }
+}
+
+/// Get the given account's stored passwords whose names start with the given prefix, alongside
+/// their decrypted names. Useful for autocompletion.
+///
+/// Password names are encrypted, so every name is decrypted and filtered here rather than in the
+/// database.
+pub fn load_passwords_by_partial_name(
+    username: &str,
+    password: &str,
+    prefix: &str,
+) -> eyre::Result<Vec<(Password, String)>> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+
+    let mut matches = vec![];
+    for stored_password in get_passwords(unlocked_account.username())? {
+        let name = helpers::bytes_to_utf8(
+            &stored_password
+                .encrypted_name()
+                .decrypt(unlocked_account.key())?,
+            "password_name",
+        )?;
+        if name.starts_with(prefix) {
+            matches.push((stored_password, name));
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Encrypt some binary data and attach it to a stored password, e.g. a server's TLS certificate.
+pub fn add_password_attachment(
+    username: &str,
+    password: &str,
+    password_name: &str,
+    attachment_name: &str,
+    data: &[u8],
+) -> eyre::Result<()> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+    let stored_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        password_name,
+    )?;
+
+    // Attachments are stored under random names so that their names don't leak.
+    let mut attachment_path = acc_path(unlocked_account.username());
+    attachment_path.push(ATTACHMENTS_DIR_NAME);
+    fs::create_dir_all(&attachment_path)?;
+    let mut random_bytes = [0u8; 16];
+    ChaCha20Rng::from_entropy().fill_bytes(&mut random_bytes);
+    attachment_path.push(helpers::bytes_to_hex(&random_bytes));
+
+    let attachment = Attachment::new(
+        &stored_password,
+        unlocked_account.key(),
+        attachment_name,
+        data,
+        &attachment_path,
+    )?;
+
+    // Add to database— if err then undo attachment creation.
+    if let Err(err) = db.add_new_attachment(attachment.to_b64()?) {
+        remove_file(&attachment_path)?;
+
+        eprintln!("Error creating attachment database entry— attachment cancelled.");
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
+/// Get the attachments of a stored password. The attachment data is not decrypted.
+pub fn list_password_attachments(
+    username: &str,
+    password: &str,
+    password_name: &str,
+) -> eyre::Result<Vec<Attachment>> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+    get_attachments(&db, &unlocked_account, password_name)
+}
+
+/// Decrypt and return the data of an attachment of a stored password.
+pub fn download_password_attachment(
+    username: &str,
+    password: &str,
+    password_name: &str,
+    attachment_name: &str,
+) -> eyre::Result<Zeroizing<Vec<u8>>> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+    let attachment = find_attachment(&db, &unlocked_account, password_name, attachment_name)?;
+    Ok(attachment.open_decrypted(unlocked_account.key())?)
+}
+
+/// Delete an attachment of a stored password from the database and the user directory.
+pub fn remove_password_attachment(
+    username: &str,
+    password: &str,
+    password_name: &str,
+    attachment_name: &str,
+) -> eyre::Result<()> {
+    let mut db = load_db()?;
+    let unlocked_account = login(&mut db, username, password)?;
+    let attachment = find_attachment(&db, &unlocked_account, password_name, attachment_name)?;
+
+    // Delete attachment database entry.
+    let b64_password_name_ciphertext = helpers::bytes_to_b64(attachment.password_name_ciphertext());
+    if db
+        .delete_attachment(
+            unlocked_account.username(),
+            &b64_password_name_ciphertext,
+            attachment_name,
+        )?
+        .is_none()
+    {
+        return Err(Error::AttachmentNotFoundError(attachment_name.to_owned()).into());
+    }
+
+    // Delete the attachment data. Undo database changes if it can't be deleted.
+    if let Err(err) = remove_file(attachment.path()) {
+        db.add_new_attachment(attachment.to_b64()?)?;
+
+        eprintln!("Error deleting attachment— deletion process cancelled.");
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
+/// Get the attachments of one of the given account's stored passwords.
+fn get_attachments(
+    db: &Database,
+    unlocked_account: &SecureFields,
+    password_name: &str,
+) -> eyre::Result<Vec<Attachment>> {
+    let stored_password = find_password(
+        unlocked_account.username(),
+        unlocked_account.key(),
+        password_name,
+    )?;
+
+    let mut attachments: Vec<Attachment> = vec![];
+    for b64_attachment in db.get_b64_attachments(
+        unlocked_account.username(),
+        &stored_password.encrypted_name().ciphertext_as_b64(),
+    )? {
+        attachments.push(Attachment::from_b64(b64_attachment)?);
+    }
+    Ok(attachments)
+}
+
+/// Find an attachment of one of the given account's stored passwords by name.
+fn find_attachment(
+    db: &Database,
+    unlocked_account: &SecureFields,
+    password_name: &str,
+    attachment_name: &str,
+) -> eyre::Result<Attachment> {
+    get_attachments(db, unlocked_account, password_name)?
+        .into_iter()
+        .find(|attachment| attachment.name() == attachment_name)
+        .ok_or_else(|| Error::AttachmentNotFoundError(attachment_name.to_owned()).into())
+}
@@ -1,32 +1,247 @@
 //! Functionality for individual dgruft user accounts.
-use crate::backend::{encrypted, encrypted::Encrypted, hashed::Hashed};
+use std::fmt;
+
+use argon2::{Argon2, Params, Version};
+use chrono::{DateTime, Utc};
+use pbkdf2::pbkdf2_hmac;
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::backend::{
+    encrypted,
+    encrypted::{Encrypted, KeyMaterial},
+    hashed::Hashed,
+};
 use crate::error::Error;
 use crate::helpers;
 
+/// Stored in place of a [KdfAlgorithm] for accounts created before dgruft used Argon2id. These
+/// accounts derive their key with PBKDF2-HMAC-SHA256 and a 64-byte salt until their password is
+/// changed.
+pub const LEGACY_KDF_PARAMS: &str = "pbkdf2";
+
+// PBKDF2 iteration count of legacy accounts.
+const LEGACY_PBKDF2_ITERATIONS: u32 = 50_000;
+
+/// The algorithm, with its cost parameters, used to derive a key from an account password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum KdfAlgorithm {
+    /// Argon2id.
+    Argon2id {
+        /// Memory cost in KiB.
+        m_cost: u32,
+        /// Number of passes over the memory.
+        t_cost: u32,
+        /// Degree of parallelism.
+        p_cost: u32,
+    },
+    /// scrypt.
+    Scrypt {
+        /// CPU/memory cost. Must be a power of two greater than 1.
+        n: u64,
+        /// Block size.
+        r: u32,
+        /// Degree of parallelism.
+        p: u32,
+    },
+    /// PBKDF2-HMAC-SHA256, for environments that require FIPS 140 approved algorithms.
+    Pbkdf2HmacSha256 {
+        /// Number of iterations.
+        iterations: u32,
+    },
+}
+impl Default for KdfAlgorithm {
+    fn default() -> Self {
+        Self::default_argon2id()
+    }
+}
+impl KdfAlgorithm {
+    /// Argon2id with the default parameters of the `argon2` crate.
+    pub fn default_argon2id() -> Self {
+        Self::Argon2id {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+
+    /// scrypt with the recommended parameters of the `scrypt` crate.
+    pub fn default_scrypt() -> Self {
+        Self::Scrypt {
+            n: 1 << scrypt::Params::RECOMMENDED_LOG_N,
+            r: scrypt::Params::RECOMMENDED_R,
+            p: scrypt::Params::RECOMMENDED_P,
+        }
+    }
+
+    /// PBKDF2-HMAC-SHA256 with 600,000 iterations, as recommended by OWASP.
+    pub fn default_pbkdf2_hmac_sha256() -> Self {
+        Self::Pbkdf2HmacSha256 {
+            iterations: 600_000,
+        }
+    }
+
+    /// Read a [KdfAlgorithm] from the `<name>$<param>=<value>,...` format it is stored in, e.g.
+    /// `argon2id$m=19456,t=2,p=1`, `scrypt$n=131072,r=8,p=1`, or `pbkdf2-sha256$i=600000`. A
+    /// bare algorithm name gives that algorithm's default parameters.
+    pub fn parse(stored: &str) -> Result<Self, Error> {
+        let invalid = || {
+            Error::UnsupportedFormatError(format!(
+                "Invalid key derivation parameters \"{stored}\"."
+            ))
+        };
+        let (name, params) = match stored.split_once('$') {
+            Some((name, params)) => (name, params),
+            None => match stored {
+                "argon2id" => return Ok(Self::default_argon2id()),
+                "scrypt" => return Ok(Self::default_scrypt()),
+                "pbkdf2-sha256" => return Ok(Self::default_pbkdf2_hmac_sha256()),
+                _ => return Err(invalid()),
+            },
+        };
+        let param_names: &[&str] = match name {
+            "argon2id" => &["m", "t", "p"],
+            "scrypt" => &["n", "r", "p"],
+            "pbkdf2-sha256" => &["i"],
+            _ => return Err(invalid()),
+        };
+        let mut values = vec![None; param_names.len()];
+        for param in params.split(',') {
+            let (param_name, value) = param.split_once('=').ok_or_else(invalid)?;
+            let index = param_names
+                .iter()
+                .position(|name| *name == param_name)
+                .ok_or_else(invalid)?;
+            values[index] = Some(value.parse::<u64>().map_err(|_| invalid())?);
+        }
+        let values: Vec<u64> = values
+            .into_iter()
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+        let to_u32 = |value: u64| u32::try_from(value).map_err(|_| invalid());
+        match name {
+            "argon2id" => Ok(Self::Argon2id {
+                m_cost: to_u32(values[0])?,
+                t_cost: to_u32(values[1])?,
+                p_cost: to_u32(values[2])?,
+            }),
+            "scrypt" => Ok(Self::Scrypt {
+                n: values[0],
+                r: to_u32(values[1])?,
+                p: to_u32(values[2])?,
+            }),
+            _ => Ok(Self::Pbkdf2HmacSha256 {
+                iterations: to_u32(values[0])?,
+            }),
+        }
+    }
+
+    // Derive a 32-byte key from a password and salt using this algorithm.
+    fn derive_key(&self, password: &str, salt: &[u8]) -> Result<KeyMaterial, Error> {
+        let mut key = KeyMaterial::new([0u8; 32]);
+        match *self {
+            Self::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => {
+                let params = Params::new(m_cost, t_cost, p_cost, Some(32))
+                    .map_err(|e| Error::KeyDerivationError(e.to_string()))?;
+                Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password.as_bytes(), salt, key.as_mut_bytes())
+                    .map_err(|e| Error::KeyDerivationError(e.to_string()))?;
+            }
+            Self::Scrypt { n, r, p } => {
+                if n < 2 || !n.is_power_of_two() {
+                    return Err(Error::KeyDerivationError(format!(
+                        "scrypt n must be a power of two greater than 1, got {n}."
+                    )));
+                }
+                let params = scrypt::Params::new(n.trailing_zeros() as u8, r, p, 32)
+                    .map_err(|e| Error::KeyDerivationError(e.to_string()))?;
+                scrypt::scrypt(password.as_bytes(), salt, &params, key.as_mut_bytes())
+                    .map_err(|e| Error::KeyDerivationError(e.to_string()))?;
+            }
+            Self::Pbkdf2HmacSha256 { iterations } => {
+                if iterations == 0 {
+                    return Err(Error::KeyDerivationError(String::from(
+                        "PBKDF2 needs at least one iteration.",
+                    )));
+                }
+                pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, key.as_mut_bytes());
+            }
+        }
+        Ok(key)
+    }
+}
+impl fmt::Display for KdfAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => write!(f, "argon2id$m={m_cost},t={t_cost},p={p_cost}"),
+            Self::Scrypt { n, r, p } => write!(f, "scrypt$n={n},r={r},p={p}"),
+            Self::Pbkdf2HmacSha256 { iterations } => write!(f, "pbkdf2-sha256$i={iterations}"),
+        }
+    }
+}
+impl std::str::FromStr for KdfAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
 /// An account with a username, password, and encryption key.
+///
+/// With the `serde` feature, the password salt and the hash are serialized as base-64 strings.
+/// The encryption key stays encrypted.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Account {
     username: String,
-    password_salt: [u8; 64],
+    #[cfg_attr(feature = "serde", serde(with = "helpers::serde_b64"))]
+    password_salt: Vec<u8>,
+    kdf: Option<KdfAlgorithm>,
     dbl_hashed_password: Hashed,
     encrypted_key: Encrypted,
+    created_at: DateTime<Utc>,
+    last_login: Option<DateTime<Utc>>,
 }
 impl Account {
-    /// Create a new [Account] from a username and a password.
+    /// Create a new [Account] from a username and a password, using the default [KdfAlgorithm].
     pub fn new(username: &str, password: &str) -> Result<Self, Error> {
+        Self::new_with_kdf(username, password, KdfAlgorithm::default())
+    }
+
+    /// Create a new [Account] from a username and a password, using the given [KdfAlgorithm].
+    pub fn new_with_kdf(username: &str, password: &str, kdf: KdfAlgorithm) -> Result<Self, Error> {
         // Generate a random AES-256 encryption key
         let key = encrypted::new_key(None);
-        // Hash the password
-        let hashed_password = Hashed::new(password.as_bytes());
-        // Use the hashed password as the key to encrypt the encryption key
-        let encrypted_key = Encrypted::new(&key, hashed_password.hash())?;
-        // Hash the password again to store it
-        let dbl_hashed_password = Hashed::new(hashed_password.hash());
+        // Derive a key from the password with a random salt
+        let password_salt = new_password_salt();
+        let derived_key = kdf.derive_key(password, &password_salt)?;
+        // Use the derived key to encrypt the encryption key
+        let encrypted_key = Encrypted::new(key.as_bytes(), &derived_key)?;
+        // Hash the derived key to store it
+        let dbl_hashed_password = Hashed::new(derived_key.as_bytes());
         Ok(Self {
             username: username.to_string(),
-            password_salt: *hashed_password.salt(),
+            password_salt: password_salt.to_vec(),
+            kdf: Some(kdf),
             dbl_hashed_password,
             encrypted_key,
+            created_at: Utc::now(),
+            last_login: None,
         })
     }
 
@@ -36,22 +251,40 @@ impl Account {
             &helpers::b64_to_bytes(&b64_account.b64_username)?,
             "username",
         )?;
-        let password_salt: [u8; 64] =
-            helpers::b64_to_fixed(b64_account.b64_password_salt, "b64_password_salt")?;
+        let (password_salt, kdf) = if b64_account.kdf_params == LEGACY_KDF_PARAMS {
+            let salt: [u8; 64] =
+                helpers::b64_to_fixed(b64_account.b64_password_salt, "b64_password_salt")?;
+            (salt.to_vec(), None)
+        } else {
+            let salt: [u8; 16] =
+                helpers::b64_to_fixed(b64_account.b64_password_salt, "b64_password_salt")?;
+            (
+                salt.to_vec(),
+                Some(KdfAlgorithm::parse(&b64_account.kdf_params)?),
+            )
+        };
         let dbl_hashed_password = Hashed::from_b64(
             &b64_account.b64_dbl_hashed_password_hash,
             &b64_account.b64_dbl_hashed_password_salt,
         )?;
-        let encrypted_key = Encrypted::from_b64(
+        let encrypted_key = Encrypted::from_b64_parts(
             &b64_account.b64_encrypted_key_ciphertext,
             &b64_account.b64_encrypted_key_nonce,
         )?;
+        let created_at = helpers::timestamp_to_datetime(b64_account.created_at)?;
+        let last_login = match b64_account.last_login {
+            Some(last_login) => Some(helpers::timestamp_to_datetime(last_login)?),
+            None => None,
+        };
 
         Ok(Self {
             username,
             password_salt,
+            kdf,
             dbl_hashed_password,
             encrypted_key,
+            created_at,
+            last_login,
         })
     }
 
@@ -64,15 +297,58 @@ impl Account {
             b64_dbl_hashed_password_salt: self.dbl_hashed_password().salt_as_b64(),
             b64_encrypted_key_ciphertext: self.encrypted_key().ciphertext_as_b64(),
             b64_encrypted_key_nonce: self.encrypted_key().nonce_as_b64(),
+            kdf_params: match self.kdf() {
+                Some(kdf) => kdf.to_string(),
+                None => LEGACY_KDF_PARAMS.to_owned(),
+            },
+            created_at: self.created_at.timestamp(),
+            last_login: self.last_login.map(|last_login| last_login.timestamp()),
+        }
+    }
+
+    /// Derive the key that encrypts this [Account]'s encryption key from a password. This is the
+    /// only place a password is turned into a key.
+    pub fn derive_key(&self, password: &str) -> Result<KeyMaterial, Error> {
+        match self.kdf() {
+            Some(kdf) => kdf.derive_key(password, self.password_salt()),
+            None => KdfAlgorithm::Pbkdf2HmacSha256 {
+                iterations: LEGACY_PBKDF2_ITERATIONS,
+            }
+            .derive_key(password, self.password_salt()),
         }
     }
 
+    /// Change the password of this [Account]. The account's encryption key stays the same and is
+    /// re-encrypted with the new password, so nothing encrypted with it needs to change. The
+    /// account keeps its [KdfAlgorithm]; legacy PBKDF2 accounts move to the default one.
+    pub fn change_password(&mut self, old_password: &str, new_password: &str) -> Result<(), Error> {
+        let unlocked = self.unlock(old_password)?;
+        self.set_password(&unlocked, new_password)
+    }
+
+    /// Like [Account::change_password], but using the [SecureFields] already unlocked from this
+    /// [Account] instead of deriving the key of the old password again.
+    pub fn set_password(
+        &mut self,
+        unlocked: &SecureFields,
+        new_password: &str,
+    ) -> Result<(), Error> {
+        let password_salt = new_password_salt();
+        let kdf = self.kdf.unwrap_or_default();
+        let derived_key = kdf.derive_key(new_password, &password_salt)?;
+        self.encrypted_key = Encrypted::new(unlocked.key().as_bytes(), &derived_key)?;
+        self.dbl_hashed_password = Hashed::new(derived_key.as_bytes());
+        self.password_salt = password_salt.to_vec();
+        self.kdf = Some(kdf);
+        Ok(())
+    }
+
     /// Return true iff the entered password matches the password stored in this [Account].
     pub fn check_password_match(&self, password: &str) -> bool {
-        let hashed_password = Hashed::from_salt(password.as_bytes(), self.password_salt());
-        let dbl_hashed_password =
-            Hashed::from_salt(hashed_password.hash(), self.dbl_hashed_password.salt());
-        self.dbl_hashed_password.hash() == dbl_hashed_password.hash()
+        match self.derive_key(password) {
+            Ok(derived_key) => self.dbl_hashed_password.check_match(derived_key.as_bytes()),
+            Err(_) => false,
+        }
     }
 
     // GETTERS
@@ -82,11 +358,18 @@ impl Account {
         &self.username
     }
 
-    /// Return the password salt of this [Account].
-    pub fn password_salt(&self) -> &[u8; 64] {
+    /// Return the password salt of this [Account]. This is 16 bytes long, or 64 bytes long for
+    /// legacy PBKDF2 accounts.
+    pub fn password_salt(&self) -> &[u8] {
         &self.password_salt
     }
 
+    /// Return the [KdfAlgorithm] of this [Account], or [None] if it uses legacy PBKDF2 key
+    /// derivation.
+    pub fn kdf(&self) -> Option<&KdfAlgorithm> {
+        self.kdf.as_ref()
+    }
+
     /// Return the double-hashed password of this [Account].
     pub fn dbl_hashed_password(&self) -> &Hashed {
         &self.dbl_hashed_password
@@ -97,28 +380,34 @@ impl Account {
         &self.encrypted_key
     }
 
+    /// Get the time this [Account] was created.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// Get the time this [Account] last logged in, if it has logged in since it was created.
+    pub fn last_login(&self) -> Option<DateTime<Utc>> {
+        self.last_login
+    }
+
     /// Get all fields of this [Account], including the secure ones. Use with caution and
     /// restraint!
     pub fn unlock(&self, password: &str) -> Result<SecureFields, Error> {
-        let hashed_password = Hashed::from_salt(password.as_bytes(), self.password_salt());
+        let derived_key = self.derive_key(password)?;
         let dbl_hashed_password =
-            Hashed::from_salt(hashed_password.hash(), self.dbl_hashed_password.salt());
+            Hashed::from_salt(derived_key.as_bytes(), self.dbl_hashed_password.salt());
 
         // Check if password matches
         if dbl_hashed_password.hash() != self.dbl_hashed_password.hash() {
             Err(Error::IncorrectPasswordError)
         } else {
             // Password OK, continue collecting fields
-            let key: [u8; 32] = self
-                .encrypted_key()
-                .decrypt(hashed_password.hash())?
-                .try_into()
-                .unwrap();
+            let key = KeyMaterial::from_slice(&self.encrypted_key().decrypt(&derived_key)?)?;
 
             Ok(SecureFields {
                 username: self.username().to_owned(),
                 password: password.to_owned(),
-                hashed_password,
+                derived_key,
                 dbl_hashed_password,
                 key,
                 encrypted_key: self.encrypted_key().clone(),
@@ -126,6 +415,23 @@ impl Account {
         }
     }
 }
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Account {{ username: {:?}, created_at: {} }}",
+            self.username,
+            self.created_at.format("%Y-%m-%d")
+        )
+    }
+}
+
+// Generate a random salt for key derivation.
+fn new_password_salt() -> [u8; 16] {
+    let mut salt = [0u8; 16];
+    ChaCha20Rng::from_entropy().fill_bytes(&mut salt);
+    salt
+}
 
 /// All the fields of an [Account], including the ones only accessible by password. Use with
 /// caution and restraint.
@@ -133,9 +439,9 @@ impl Account {
 pub struct SecureFields {
     username: String,
     password: String,
-    hashed_password: Hashed,
+    derived_key: KeyMaterial,
     dbl_hashed_password: Hashed,
-    key: [u8; 32],
+    key: KeyMaterial,
     encrypted_key: Encrypted,
 }
 impl SecureFields {
@@ -147,16 +453,16 @@ impl SecureFields {
     pub fn password(&self) -> &str {
         &self.password
     }
-    /// Return the hashed_password of this [SecureFields].
-    pub fn hashed_password(&self) -> &Hashed {
-        &self.hashed_password
+    /// Return the key derived from the password of this [SecureFields].
+    pub fn derived_key(&self) -> &KeyMaterial {
+        &self.derived_key
     }
     /// Return the dbl_hashed_password of this [SecureFields].
     pub fn dbl_hashed_password(&self) -> &Hashed {
         &self.dbl_hashed_password
     }
     /// Return the key of this [SecureFields].
-    pub fn key(&self) -> &[u8; 32] {
+    pub fn key(&self) -> &KeyMaterial {
         &self.key
     }
     /// Return the encrypted_key of this [SecureFields].
@@ -180,10 +486,18 @@ pub struct Base64Account {
     pub b64_encrypted_key_ciphertext: String,
     /// Account encrypted key nonce in base-64 format.
     pub b64_encrypted_key_nonce: String,
+    /// Account key derivation parameters, either a formatted [KdfAlgorithm] or
+    /// [LEGACY_KDF_PARAMS].
+    pub kdf_params: String,
+    /// Account creation time as a Unix timestamp in seconds.
+    pub created_at: i64,
+    /// Account last login time as a Unix timestamp in seconds, if it has logged in since it was
+    /// created.
+    pub last_login: Option<i64>,
 }
 impl Base64Account {
     /// Output fields as tuple.
-    pub fn as_tuple(&self) -> (&str, &str, &str, &str, &str, &str) {
+    pub fn as_tuple(&self) -> (&str, &str, &str, &str, &str, &str, &str, i64, Option<i64>) {
         (
             &self.b64_username,
             &self.b64_password_salt,
@@ -191,19 +505,44 @@ impl Base64Account {
             &self.b64_dbl_hashed_password_salt,
             &self.b64_encrypted_key_ciphertext,
             &self.b64_encrypted_key_nonce,
+            &self.kdf_params,
+            self.created_at,
+            self.last_login,
         )
     }
+
+    /// Return true iff this account has the same username and credentials as the given one, so
+    /// that the same password unlocks the same key. Timestamps are not compared.
+    pub fn same_credentials(&self, other: &Base64Account) -> bool {
+        self.b64_username == other.b64_username
+            && self.b64_password_salt == other.b64_password_salt
+            && self.b64_dbl_hashed_password_hash == other.b64_dbl_hashed_password_hash
+            && self.b64_dbl_hashed_password_salt == other.b64_dbl_hashed_password_salt
+            && self.b64_encrypted_key_ciphertext == other.b64_encrypted_key_ciphertext
+            && self.b64_encrypted_key_nonce == other.b64_encrypted_key_nonce
+            && self.kdf_params == other.kdf_params
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use pretty_assertions::assert_eq;
+    use hex_literal::hex;
+    use pretty_assertions::{assert_eq, assert_ne};
 
     #[test]
     fn test_new_acc() {
         let my_account = Account::new("my_account", "my_password").unwrap();
         assert!(my_account.check_password_match("my_password"));
+        assert_eq!(16, my_account.password_salt().len());
+
+        // The same password gets a different salt, and so a different derived key, every time.
+        let my_other_account = Account::new("my_other_account", "my_password").unwrap();
+        assert_ne!(my_account.password_salt(), my_other_account.password_salt());
+        assert_ne!(
+            my_account.derive_key("my_password").unwrap(),
+            my_other_account.derive_key("my_password").unwrap()
+        );
 
         let incorrect_attempt = my_account.unlock("not my password").unwrap_err();
         if let Error::IncorrectPasswordError = incorrect_attempt {
@@ -213,27 +552,23 @@ mod tests {
         }
 
         let my_fields = my_account.unlock("my_password").unwrap();
-        let hashed_password = Hashed::from_salt(b"my_password", my_account.password_salt());
+        let derived_key = my_account.derive_key("my_password").unwrap();
         let dbl_hashed_password = Hashed::from_salt(
-            hashed_password.hash(),
+            derived_key.as_bytes(),
             my_account.dbl_hashed_password().salt(),
         );
-        let key: [u8; 32] = my_account
-            .encrypted_key()
-            .decrypt(hashed_password.hash())
-            .unwrap()
-            .try_into()
-            .unwrap();
+        let key =
+            KeyMaterial::from_slice(&my_account.encrypted_key().decrypt(&derived_key).unwrap())
+                .unwrap();
         let encrypted_key = Encrypted::from_nonce(
-            &key,
-            hashed_password.hash(),
+            key.as_bytes(),
+            &derived_key,
             my_fields.encrypted_key().nonce(),
         )
         .unwrap();
         assert_eq!("my_account", my_fields.username());
         assert_eq!("my_password", my_fields.password());
-        assert_eq!(hashed_password.hash(), my_fields.hashed_password().hash());
-        assert_eq!(hashed_password.salt(), my_fields.hashed_password().salt());
+        assert_eq!(&derived_key, my_fields.derived_key());
         assert_eq!(
             dbl_hashed_password.hash(),
             my_fields.dbl_hashed_password().hash()
@@ -253,24 +588,18 @@ mod tests {
     #[test]
     fn test_to_from_b64() {
         let my_account = Account::new("马克斯", "secretpassword123").unwrap();
-        let hashed_password = Hashed::from_salt(b"secretpassword123", my_account.password_salt());
+        let derived_key = my_account.derive_key("secretpassword123").unwrap();
         let dbl_hashed_password = Hashed::from_salt(
-            hashed_password.hash(),
+            derived_key.as_bytes(),
             my_account.dbl_hashed_password().salt(),
         );
-        let key = my_account
-            .encrypted_key()
-            .decrypt(hashed_password.hash())
-            .unwrap();
-        let encrypted_key = Encrypted::from_nonce(
-            &key,
-            hashed_password.hash(),
-            my_account.encrypted_key().nonce(),
-        )
-        .unwrap();
+        let key = my_account.encrypted_key().decrypt(&derived_key).unwrap();
+        let encrypted_key =
+            Encrypted::from_nonce(&key, &derived_key, my_account.encrypted_key().nonce()).unwrap();
 
         let my_account_b64 = my_account.to_b64();
         assert_eq!("6ams5YWL5pav", my_account_b64.b64_username);
+        assert_eq!("argon2id$m=19456,t=2,p=1", my_account_b64.kdf_params);
         assert_eq!(
             dbl_hashed_password.hash_as_b64(),
             my_account_b64.b64_dbl_hashed_password_hash
@@ -287,9 +616,17 @@ mod tests {
             encrypted_key.nonce_as_b64(),
             my_account_b64.b64_encrypted_key_nonce
         );
+        assert_eq!(
+            my_account.created_at().timestamp(),
+            my_account_b64.created_at
+        );
+        assert_eq!(None, my_account_b64.last_login);
 
         let my_account_2 = Account::from_b64(my_account_b64).unwrap();
         assert_eq!("马克斯", my_account_2.username());
+        assert_eq!(my_account.password_salt(), my_account_2.password_salt());
+        assert_eq!(my_account.kdf(), my_account_2.kdf());
+        assert!(my_account_2.check_password_match("secretpassword123"));
         assert_eq!(
             dbl_hashed_password.hash(),
             my_account_2.dbl_hashed_password().hash()
@@ -303,5 +640,262 @@ mod tests {
             my_account_2.encrypted_key.ciphertext()
         );
         assert_eq!(encrypted_key.nonce(), my_account_2.encrypted_key.nonce());
+        assert_eq!(
+            my_account.created_at().timestamp(),
+            my_account_2.created_at().timestamp()
+        );
+        assert_eq!(None, my_account_2.last_login());
+
+        let mut my_account_b64 = my_account_2.to_b64();
+        my_account_b64.last_login = Some(1_700_000_000);
+        let my_account_3 = Account::from_b64(my_account_b64).unwrap();
+        assert_eq!(
+            Some(1_700_000_000),
+            my_account_3
+                .last_login()
+                .map(|last_login| last_login.timestamp())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let json = serde_json::to_string(&my_account).unwrap();
+        assert!(json.contains(&helpers::bytes_to_b64(my_account.password_salt())));
+
+        let loaded: Account = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::to_string(&loaded).unwrap());
+        assert_eq!(my_account.created_at(), loaded.created_at());
+        assert_eq!(
+            my_account.unlock("my_password").unwrap().key(),
+            loaded.unlock("my_password").unwrap().key()
+        );
+        loaded.unlock("wrong_password").unwrap_err();
+    }
+
+    #[test]
+    fn test_display() {
+        let my_account = Account::new("alice", "my_password").unwrap();
+        assert_eq!(
+            format!(
+                "Account {{ username: \"alice\", created_at: {} }}",
+                my_account.created_at().format("%Y-%m-%d")
+            ),
+            my_account.to_string()
+        );
+    }
+
+    #[test]
+    fn test_change_password() {
+        let mut my_account = Account::new("my_account", "old_password").unwrap();
+        let key = my_account.unlock("old_password").unwrap().key().clone();
+
+        let wrong_attempt = my_account
+            .change_password("not my password", "new_password")
+            .unwrap_err();
+        if let Error::IncorrectPasswordError = wrong_attempt {
+        } else {
+            dbg!(&wrong_attempt);
+            panic!("Wrong error type");
+        }
+        assert!(my_account.check_password_match("old_password"));
+
+        my_account
+            .change_password("old_password", "new_password")
+            .unwrap();
+        assert!(!my_account.check_password_match("old_password"));
+        assert!(my_account.check_password_match("new_password"));
+        assert_eq!(&key, my_account.unlock("new_password").unwrap().key());
+    }
+
+    #[test]
+    fn test_legacy_pbkdf2_account() {
+        // Build an account the way dgruft did before Argon2id.
+        let key = encrypted::new_key(None);
+        let hashed_password = Hashed::new(b"legacy_password");
+        let legacy_b64 = Base64Account {
+            b64_username: helpers::bytes_to_b64(b"legacy_account"),
+            b64_password_salt: hashed_password.salt_as_b64(),
+            b64_dbl_hashed_password_hash: String::new(),
+            b64_dbl_hashed_password_salt: String::new(),
+            b64_encrypted_key_ciphertext: String::new(),
+            b64_encrypted_key_nonce: String::new(),
+            kdf_params: LEGACY_KDF_PARAMS.to_owned(),
+            created_at: 1_700_000_000,
+            last_login: None,
+        };
+        let dbl_hashed_password = Hashed::new(hashed_password.hash());
+        let encrypted_key =
+            Encrypted::new(key.as_bytes(), &KeyMaterial::new(*hashed_password.hash())).unwrap();
+        let legacy_b64 = Base64Account {
+            b64_dbl_hashed_password_hash: dbl_hashed_password.hash_as_b64(),
+            b64_dbl_hashed_password_salt: dbl_hashed_password.salt_as_b64(),
+            b64_encrypted_key_ciphertext: encrypted_key.ciphertext_as_b64(),
+            b64_encrypted_key_nonce: encrypted_key.nonce_as_b64(),
+            ..legacy_b64
+        };
+
+        let mut legacy_account = Account::from_b64(legacy_b64).unwrap();
+        assert_eq!(None, legacy_account.kdf());
+        assert_eq!(
+            hashed_password.hash(),
+            legacy_account
+                .derive_key("legacy_password")
+                .unwrap()
+                .as_bytes()
+        );
+        assert!(legacy_account.check_password_match("legacy_password"));
+        assert!(!legacy_account.check_password_match("not my password"));
+        assert_eq!(
+            &key,
+            legacy_account.unlock("legacy_password").unwrap().key()
+        );
+        assert_eq!(LEGACY_KDF_PARAMS, legacy_account.to_b64().kdf_params);
+
+        // Changing the password moves the account to Argon2id.
+        legacy_account
+            .change_password("legacy_password", "new_password")
+            .unwrap();
+        assert_eq!(Some(&KdfAlgorithm::default()), legacy_account.kdf());
+        assert_eq!(16, legacy_account.password_salt().len());
+        assert_eq!(&key, legacy_account.unlock("new_password").unwrap().key());
+    }
+
+    #[test]
+    fn test_kdf_algorithm_format() {
+        let argon2id = KdfAlgorithm::Argon2id {
+            m_cost: 65536,
+            t_cost: 3,
+            p_cost: 4,
+        };
+        assert_eq!("argon2id$m=65536,t=3,p=4", argon2id.to_string());
+        assert_eq!(
+            argon2id,
+            KdfAlgorithm::parse(&argon2id.to_string()).unwrap()
+        );
+        assert_eq!(
+            argon2id,
+            KdfAlgorithm::parse("argon2id$p=4,m=65536,t=3").unwrap()
+        );
+
+        let scrypt = KdfAlgorithm::Scrypt {
+            n: 1024,
+            r: 8,
+            p: 1,
+        };
+        assert_eq!("scrypt$n=1024,r=8,p=1", scrypt.to_string());
+        assert_eq!(scrypt, KdfAlgorithm::parse(&scrypt.to_string()).unwrap());
+
+        assert_eq!(
+            KdfAlgorithm::default_argon2id(),
+            KdfAlgorithm::parse("argon2id").unwrap()
+        );
+        assert_eq!(
+            KdfAlgorithm::default_scrypt(),
+            KdfAlgorithm::parse("scrypt").unwrap()
+        );
+        assert_eq!(
+            KdfAlgorithm::default_pbkdf2_hmac_sha256(),
+            KdfAlgorithm::parse("pbkdf2-sha256").unwrap()
+        );
+        assert_eq!(KdfAlgorithm::default_argon2id(), KdfAlgorithm::default());
+
+        let pbkdf2 = KdfAlgorithm::Pbkdf2HmacSha256 { iterations: 1000 };
+        assert_eq!("pbkdf2-sha256$i=1000", pbkdf2.to_string());
+        assert_eq!(pbkdf2, KdfAlgorithm::parse(&pbkdf2.to_string()).unwrap());
+
+        for invalid in [
+            "",
+            "pbkdf2",
+            "argon2id$m=65536,t=3",
+            "argon2id$m=65536,t=3,p=x",
+            "argon2id$m=65536,t=3,q=4",
+            "argon2id$m=65536,t=3,p=4294967296",
+            "scrypt$m=65536,t=3,p=4",
+            "bcrypt$n=1024,r=8,p=1",
+            "pbkdf2-sha256$i=",
+            "pbkdf2-sha256$i=1000,p=1",
+        ] {
+            assert!(KdfAlgorithm::parse(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn test_scrypt_account() {
+        let scrypt = KdfAlgorithm::Scrypt {
+            n: 1024,
+            r: 8,
+            p: 1,
+        };
+        let mut my_account = Account::new_with_kdf("my_account", "my_password", scrypt).unwrap();
+        assert_eq!(Some(&scrypt), my_account.kdf());
+        assert!(my_account.check_password_match("my_password"));
+        assert!(!my_account.check_password_match("not my password"));
+        let key = my_account.unlock("my_password").unwrap().key().clone();
+
+        let my_account_b64 = my_account.to_b64();
+        assert_eq!("scrypt$n=1024,r=8,p=1", my_account_b64.kdf_params);
+        let my_account_2 = Account::from_b64(my_account_b64).unwrap();
+        assert_eq!(&key, my_account_2.unlock("my_password").unwrap().key());
+
+        // Changing the password keeps the algorithm.
+        my_account
+            .change_password("my_password", "new_password")
+            .unwrap();
+        assert_eq!(Some(&scrypt), my_account.kdf());
+        assert_eq!(&key, my_account.unlock("new_password").unwrap().key());
+
+        let bad_n = KdfAlgorithm::Scrypt {
+            n: 1000,
+            r: 8,
+            p: 1,
+        };
+        if let Err(Error::KeyDerivationError(_)) = Account::new_with_kdf("a", "b", bad_n) {
+        } else {
+            panic!("Wrong error type");
+        }
+    }
+
+    #[test]
+    fn test_pbkdf2_hmac_sha256() {
+        // RFC 7914, section 11.
+        let rfc_kdf = KdfAlgorithm::Pbkdf2HmacSha256 { iterations: 1 };
+        assert_eq!(
+            hex!("55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"),
+            *rfc_kdf.derive_key("passwd", b"salt").unwrap().as_bytes()
+        );
+
+        let kdf = KdfAlgorithm::Pbkdf2HmacSha256 { iterations: 1000 };
+        let salt = new_password_salt();
+        let key = kdf.derive_key("my_password", &salt).unwrap();
+        assert_eq!(key, kdf.derive_key("my_password", &salt).unwrap());
+        assert_ne!(key, kdf.derive_key("not my password", &salt).unwrap());
+        assert_ne!(
+            key,
+            kdf.derive_key("my_password", &new_password_salt()).unwrap()
+        );
+        assert_ne!(
+            key,
+            KdfAlgorithm::Pbkdf2HmacSha256 { iterations: 1001 }
+                .derive_key("my_password", &salt)
+                .unwrap()
+        );
+
+        // The iteration count is stored with the account.
+        let my_account = Account::new_with_kdf("my_account", "my_password", kdf).unwrap();
+        let my_account_2 = Account::from_b64(my_account.to_b64()).unwrap();
+        assert_eq!(Some(&kdf), my_account_2.kdf());
+        assert_eq!(
+            my_account.derive_key("my_password").unwrap(),
+            my_account_2.derive_key("my_password").unwrap()
+        );
+        assert!(my_account_2.check_password_match("my_password"));
+
+        let no_iterations = KdfAlgorithm::Pbkdf2HmacSha256 { iterations: 0 };
+        if let Err(Error::KeyDerivationError(_)) = no_iterations.derive_key("my_password", &salt) {
+        } else {
+            panic!("Wrong error type");
+        }
     }
-}
\ No newline at end of file
+}
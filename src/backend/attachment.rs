@@ -0,0 +1,238 @@
+//! Functionality related to encrypted binary attachments of stored passwords.
+use std::{
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use zeroize::Zeroizing;
+
+use crate::{
+    backend::{
+        encrypted::{Encrypted, KeyMaterial},
+        password::Password,
+    },
+    error::Error,
+    helpers,
+};
+
+/// Metadata for an encrypted attachment of a stored [Password], e.g. a certificate or a PDF
+/// agreement. The encrypted attachment data itself is stored on disk.
+#[derive(Debug)]
+pub struct Attachment {
+    owner_username: String,
+    password_name_ciphertext: Vec<u8>,
+    name: String,
+    path: PathBuf,
+    nonce: [u8; 12],
+    size_bytes: u64,
+}
+impl Attachment {
+    /// Encrypt the given data with the given key and write it to a new file at the given path.
+    /// The attachment is associated with the given [Password].
+    pub fn new<P>(
+        password: &Password,
+        key: &KeyMaterial,
+        name: &str,
+        data: &[u8],
+        path: P,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        helpers::path_to_string(path.as_ref())?;
+        let encrypted_data = Encrypted::new(data, key)?;
+
+        let write_result = File::create_new(&path)
+            .and_then(|mut file| file.write_all(encrypted_data.ciphertext()));
+        if let Err(err) = write_result {
+            return match err.kind() {
+                ErrorKind::AlreadyExists => {
+                    Err(Error::FileAlreadyExistsError(PathBuf::from(path.as_ref())))
+                }
+                ErrorKind::NotFound => Err(Error::FileNotFoundError(PathBuf::from(path.as_ref()))),
+                ErrorKind::PermissionDenied => {
+                    Err(Error::PermissionDeniedError(PathBuf::from(path.as_ref())))
+                }
+                _ => Err(Error::UnhandledError(err.to_string())),
+            };
+        }
+
+        Ok(Self {
+            owner_username: password.owner_username().to_owned(),
+            password_name_ciphertext: password.encrypted_name().ciphertext().to_vec(),
+            name: name.to_owned(),
+            path: PathBuf::from(path.as_ref()),
+            nonce: *encrypted_data.nonce(),
+            size_bytes: data.len() as u64,
+        })
+    }
+
+    /// Read, then decrypt, the attachment data.
+    pub fn open_decrypted(&self, key: &KeyMaterial) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let ciphertext = match fs::read(&self.path) {
+            Ok(ciphertext) => ciphertext,
+            Err(err) => {
+                return match err.kind() {
+                    ErrorKind::NotFound => Err(Error::FileNotFoundError(self.path.clone())),
+                    ErrorKind::PermissionDenied => {
+                        Err(Error::PermissionDeniedError(self.path.clone()))
+                    }
+                    _ => Err(Error::UnhandledError(err.to_string())),
+                }
+            }
+        };
+        Encrypted::from_bytes(&ciphertext, &self.nonce).decrypt(key)
+    }
+
+    /// Load an [Attachment] from a [Base64Attachment]— a set of base-64-encoded strings.
+    pub fn from_b64(b64_attachment: Base64Attachment) -> Result<Self, Error> {
+        let owner_username = helpers::bytes_to_utf8(
+            &helpers::b64_to_bytes(&b64_attachment.b64_owner_username)?,
+            "owner_username",
+        )?;
+        let password_name_ciphertext =
+            helpers::b64_to_bytes(&b64_attachment.b64_password_name_ciphertext)?;
+        let name = helpers::bytes_to_utf8(
+            &helpers::b64_to_bytes(&b64_attachment.b64_name)?,
+            "attachment_name",
+        )?;
+        let path = PathBuf::from(helpers::bytes_to_utf8(
+            &helpers::b64_to_bytes(&b64_attachment.b64_path)?,
+            "attachment_path",
+        )?);
+        let nonce: [u8; 12] = helpers::b64_to_fixed(b64_attachment.b64_nonce, "attachment_nonce")?;
+
+        Ok(Self {
+            owner_username,
+            password_name_ciphertext,
+            name,
+            path,
+            nonce,
+            size_bytes: b64_attachment.size_bytes as u64,
+        })
+    }
+
+    /// Convert this [Attachment] to a [Base64Attachment] for storage.
+    pub fn to_b64(&self) -> Result<Base64Attachment, Error> {
+        let b64_path = match self.path.to_str() {
+            Some(path_str) => helpers::bytes_to_b64(path_str.as_bytes()),
+            None => return Err(Error::ToB64Error("attachment path string".to_owned())),
+        };
+
+        Ok(Base64Attachment {
+            b64_owner_username: helpers::bytes_to_b64(self.owner_username().as_bytes()),
+            b64_password_name_ciphertext: helpers::bytes_to_b64(self.password_name_ciphertext()),
+            b64_name: helpers::bytes_to_b64(self.name().as_bytes()),
+            b64_path,
+            b64_nonce: helpers::bytes_to_b64(self.nonce()),
+            size_bytes: self.size_bytes() as i64,
+        })
+    }
+
+    // GETTERS
+
+    /// Return the username of the account owning this [Attachment].
+    pub fn owner_username(&self) -> &str {
+        &self.owner_username
+    }
+
+    /// Return the name ciphertext of the [Password] this [Attachment] belongs to.
+    pub fn password_name_ciphertext(&self) -> &[u8] {
+        &self.password_name_ciphertext
+    }
+
+    /// Return the name of this [Attachment].
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the path at which the encrypted data of this [Attachment] is stored.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the nonce used to encrypt the data of this [Attachment].
+    pub fn nonce(&self) -> &[u8; 12] {
+        &self.nonce
+    }
+
+    /// Return the size of the unencrypted data of this [Attachment] in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        self.size_bytes
+    }
+}
+
+/// An [Attachment] converted for base-64 storage.
+#[derive(Debug)]
+pub struct Base64Attachment {
+    /// Owner username in base-64 format.
+    pub b64_owner_username: String,
+    /// Name ciphertext of the associated password in base-64 format.
+    pub b64_password_name_ciphertext: String,
+    /// Attachment name in base-64 format.
+    pub b64_name: String,
+    /// Encrypted data path in base-64 format.
+    pub b64_path: String,
+    /// Encrypted data nonce in base-64 format.
+    pub b64_nonce: String,
+    /// Size of the unencrypted data in bytes.
+    pub size_bytes: i64,
+}
+impl Base64Attachment {
+    /// Output fields as tuple.
+    pub fn as_tuple(&self) -> (&str, &str, &str, &str, &str, i64) {
+        (
+            &self.b64_owner_username,
+            &self.b64_password_name_ciphertext,
+            &self.b64_name,
+            &self.b64_path,
+            &self.b64_nonce,
+            self.size_bytes,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::account::Account;
+    use pretty_assertions::assert_eq;
+    use std::process::Command;
+
+    const TEST_DATA: &[u8] = b"-----BEGIN CERTIFICATE-----\x00\x01\x02\xff";
+
+    fn cleanup_test_file(path: &str) {
+        Command::new("rm").arg(path).status().expect("failed");
+    }
+
+    #[test]
+    fn test_read_write_b64() {
+        let test_file = "test_files/testattachment1";
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let key = my_account.unlock("my_password").unwrap().key().clone();
+        let my_password =
+            Password::new(&my_account, "my_password", "server", "root", "hunter2", "").unwrap();
+
+        let attachment =
+            Attachment::new(&my_password, &key, "cert.pem", TEST_DATA, test_file).unwrap();
+        assert_eq!(attachment.size_bytes(), TEST_DATA.len() as u64);
+        assert_eq!(TEST_DATA, &attachment.open_decrypted(&key).unwrap()[..]);
+
+        let loaded = Attachment::from_b64(attachment.to_b64().unwrap()).unwrap();
+        assert_eq!(loaded.owner_username(), "my_account");
+        assert_eq!(loaded.name(), "cert.pem");
+        assert_eq!(
+            loaded.password_name_ciphertext(),
+            my_password.encrypted_name().ciphertext()
+        );
+        assert_eq!(TEST_DATA, &loaded.open_decrypted(&key).unwrap()[..]);
+
+        let dupe = Attachment::new(&my_password, &key, "cert.pem", TEST_DATA, test_file);
+        if let Err(Error::FileAlreadyExistsError(_)) = dupe {
+        } else {
+            panic!("Wrong error type");
+        }
+        cleanup_test_file(test_file);
+    }
+}
@@ -0,0 +1,75 @@
+//! Lookups in the "Have I Been Pwned" database of breached passwords.
+//!
+//! Only the first five characters of the SHA-1 hash of a password are ever sent (k-anonymity). The
+//! service answers with the suffixes of all breached hashes sharing that prefix, which are then
+//! matched locally.
+use sha1::{Digest, Sha1};
+
+use crate::helpers;
+
+/// URL of the range endpoint. The hash prefix is appended to it.
+pub const RANGE_URL: &str = "https://api.pwnedpasswords.com/range/";
+
+/// Length of the hash prefix sent to the service.
+pub const PREFIX_LENGTH: usize = 5;
+
+/// Return the uppercase hexadecimal SHA-1 hash of the given password, split into the prefix
+/// which is sent to the service and the suffix which is matched locally.
+pub fn hash_prefix_and_suffix(password: &str) -> (String, String) {
+    let hash = helpers::bytes_to_hex(&Sha1::digest(password.as_bytes())).to_uppercase();
+    let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+    (prefix.to_owned(), suffix.to_owned())
+}
+
+/// Return the number of breaches of the given hash suffix in a range response.
+///
+/// Each line of the response has the form `SUFFIX:COUNT`. Returns 0 if the suffix isn't listed.
+/// Padding entries with a count of 0 are ignored the same way.
+pub fn count_in_range_response(suffix: &str, response: &str) -> u64 {
+    response
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(line_suffix, _)| line_suffix.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_hash_prefix_and_suffix() {
+        let (prefix, suffix) = hash_prefix_and_suffix("password");
+        assert_eq!("5BAA6", prefix);
+        assert_eq!("1E4C9B93F3F0682250B6CF8331B7EE68FD8", suffix);
+    }
+
+    #[test]
+    fn test_count_in_range_response() {
+        let response = "003D68EB55068C33ACE09247EE4C639306B:3\r\n\
+                        1E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004\r\n\
+                        01330C689E5D64F660D6947A93AD634EF8F:0\r\n";
+        assert_eq!(
+            10434004,
+            count_in_range_response("1E4C9B93F3F0682250B6CF8331B7EE68FD8", response)
+        );
+        assert_eq!(
+            10434004,
+            count_in_range_response("1e4c9b93f3f0682250b6cf8331b7ee68fd8", response)
+        );
+        assert_eq!(
+            0,
+            count_in_range_response("01330C689E5D64F660D6947A93AD634EF8F", response)
+        );
+        assert_eq!(
+            0,
+            count_in_range_response("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF", response)
+        );
+        assert_eq!(
+            0,
+            count_in_range_response("1E4C9B93F3F0682250B6CF8331B7EE68FD8", "")
+        );
+    }
+}
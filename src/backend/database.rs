@@ -1,23 +1,93 @@
 //! All functionality related to the [SQLite](https://www.sqlite.org/about.html) database dgruft uses for persistence.
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
-use std::usize;
 
-use rusqlite::{config::DbConfig, Connection, OpenFlags};
+use rusqlite::{
+    config::DbConfig, ffi, types::Value, Connection, DatabaseName, OpenFlags, OptionalExtension,
+    Row, ToSql,
+};
 
 use crate::{
     backend::{
-        account::Base64Account, file::Base64FileData, password::Base64Password, sql_schemas::*,
+        account::Base64Account,
+        attachment::Base64Attachment,
+        file::Base64FileData,
+        history::{Base64PasswordHistoryEntry, MAX_PASSWORD_HISTORY},
+        migrations::{self, SCHEMA_VERSION},
+        password::Base64Password,
+        recovery::Base64RecoveryCode,
+        sql_schemas::*,
         sql_statements::*,
     },
     helpers,
 };
 
+/// The tables of the `dgruft` database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    /// The `user_credentials` table, storing [crate::backend::account::Account]s.
+    UserCredentials,
+    /// The `passwords` table, storing [crate::backend::password::Password]s.
+    Passwords,
+    /// The `files` table, storing [crate::backend::file::FileData].
+    Files,
+    /// The `recovery_codes` table, storing [crate::backend::recovery::RecoveryCode]s.
+    RecoveryCodes,
+    /// The `password_attachments` table, storing [crate::backend::attachment::Attachment]s.
+    PasswordAttachments,
+    /// The `vault_config` table, storing per-database configuration values.
+    VaultConfig,
+    /// The `password_history` table, storing
+    /// [crate::backend::history::PasswordHistoryEntry]s.
+    PasswordHistory,
+}
+impl Table {
+    /// Every table of the `dgruft` database.
+    pub const ALL: [Table; 7] = [
+        Table::UserCredentials,
+        Table::Passwords,
+        Table::Files,
+        Table::RecoveryCodes,
+        Table::PasswordAttachments,
+        Table::VaultConfig,
+        Table::PasswordHistory,
+    ];
+
+    /// Return the SQL name of this [Table].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::UserCredentials => "user_credentials",
+            Table::Passwords => "passwords",
+            Table::Files => "files",
+            Table::RecoveryCodes => "recovery_codes",
+            Table::PasswordAttachments => "password_attachments",
+            Table::VaultConfig => "vault_config",
+            Table::PasswordHistory => "password_history",
+        }
+    }
+
+    /// Return the name of the column holding the owner username of each row of this [Table].
+    /// Return [None] if rows of this [Table] don't belong to an account.
+    pub fn owner_column(&self) -> Option<&'static str> {
+        match self {
+            Table::UserCredentials => Some("username"),
+            Table::Passwords
+            | Table::Files
+            | Table::RecoveryCodes
+            | Table::PasswordAttachments
+            | Table::PasswordHistory => Some("owner_username"),
+            Table::VaultConfig => None,
+        }
+    }
+}
+
 /// Connection interface to an SQLite database.
 #[derive(Debug)]
 pub struct Database {
     /// Path to .db file
     path: PathBuf,
+    /// Flags used to open the SQLite database connection
+    open_flags: OpenFlags,
     /// SQLite database connection
     connection: Connection,
 }
@@ -27,19 +97,88 @@ impl Database {
     where
         P: AsRef<Path> + AsRef<OsStr>,
     {
-        let connection = Connection::open_with_flags(
-            &path,
-            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
-        )?;
+        let open_flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let connection = Connection::open_with_flags(&path, open_flags)?;
 
         connection.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY, true)?;
+        // Let readers and a writer work at the same time, and only sync the WAL at checkpoints.
+        connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+            row.get::<usize, String>(0)
+        })?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+
+        // Databases created before the schema was versioned are at version 1.
+        connection.execute(CREATE_SCHEMA_VERSION, ())?;
+        let recorded_version = connection
+            .query_row(GET_SCHEMA_VERSION, [], |row| row.get::<usize, u32>(0))
+            .optional()?;
+        let stored_version = match recorded_version {
+            Some(version) => version,
+            None if connection.prepare(USER_CREDENTIALS_EXISTS)?.exists([])? => 1,
+            None => SCHEMA_VERSION,
+        };
+        if stored_version > SCHEMA_VERSION {
+            return Err(rusqlite::Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_CANTOPEN),
+                Some(format!(
+                    "Database schema version {stored_version} is newer than the supported version {SCHEMA_VERSION}. Update dgruft to open it."
+                )),
+            ));
+        }
 
         // Create tables if they don't exist
         connection.execute(CREATE_USER_CREDENTIALS, ())?;
         connection.execute(CREATE_PASSWORDS, ())?;
         connection.execute(CREATE_FILES, ())?;
+        connection.execute(CREATE_RECOVERY_CODES, ())?;
+        connection.execute(CREATE_PASSWORD_ATTACHMENTS, ())?;
+        connection.execute(CREATE_VAULT_CONFIG, ())?;
+        connection.execute(CREATE_PASSWORD_HISTORY, ())?;
+        if recorded_version.is_none() && stored_version == SCHEMA_VERSION {
+            // New databases are created at the current version.
+            connection.execute(SET_SCHEMA_VERSION, [SCHEMA_VERSION])?;
+        } else {
+            migrations::run_migrations(&connection, stored_version, SCHEMA_VERSION)?;
+        }
+        Ok(Self {
+            path: PathBuf::from(&path),
+            open_flags,
+            connection,
+        })
+    }
+
+    /// Open a read-only connection to the existing database at the given path.
+    ///
+    /// Unlike [Database::connect], nothing is written to the database: tables aren't created and
+    /// migrations aren't run. Return [Err] if the database isn't at the current schema version.
+    pub fn connect_read_only<P>(path: P) -> rusqlite::Result<Self>
+    where
+        P: AsRef<Path> + AsRef<OsStr>,
+    {
+        let open_flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let connection = Connection::open_with_flags(&path, open_flags)?;
+
+        // Databases created before the schema was versioned are at version 1.
+        let stored_version = if connection.prepare(SCHEMA_VERSION_EXISTS)?.exists([])? {
+            connection
+                .query_row(GET_SCHEMA_VERSION, [], |row| row.get::<usize, u32>(0))
+                .optional()?
+                .unwrap_or(1)
+        } else {
+            1
+        };
+        if stored_version != SCHEMA_VERSION {
+            return Err(rusqlite::Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_CANTOPEN),
+                Some(format!(
+                    "Database schema version {stored_version} differs from the supported version {SCHEMA_VERSION}. Open it with dgruft once to migrate it."
+                )),
+            ));
+        }
+
         Ok(Self {
             path: PathBuf::from(&path),
+            open_flags,
             connection,
         })
     }
@@ -52,24 +191,44 @@ impl Database {
         username: &str,
     ) -> rusqlite::Result<Option<Vec<Base64Password>>> {
         // Ensure account exists
-        if let Ok(None) = self.get_b64_account(username) {
+        if !self.account_exists(username)? {
             return Ok(None);
         };
 
         let mut statement = self.connection.prepare(GET_USER_PASSWORDS)?;
-        let rows = statement.query_map([helpers::bytes_to_b64(username.as_bytes())], |row| {
-            Ok(Base64Password {
-                b64_owner_username: row.get::<usize, String>(0)?,
-                b64_name_ciphertext: row.get::<usize, String>(1)?,
-                b64_username_ciphertext: row.get::<usize, String>(2)?,
-                b64_content_ciphertext: row.get::<usize, String>(3)?,
-                b64_notes_ciphertext: row.get::<usize, String>(4)?,
-                b64_name_nonce: row.get::<usize, String>(5)?,
-                b64_username_nonce: row.get::<usize, String>(6)?,
-                b64_content_nonce: row.get::<usize, String>(7)?,
-                b64_notes_nonce: row.get::<usize, String>(8)?,
-            })
-        })?;
+        let rows = statement.query_map(
+            [helpers::bytes_to_b64(username.as_bytes())],
+            b64_password_from_row,
+        )?;
+        let mut passwords = Vec::new();
+        for b64password_result in rows {
+            passwords.push(b64password_result?);
+        }
+        Ok(Some(passwords))
+    }
+
+    /// Retrieve a user's stored passwords in the given category from the database as a [Vec] of
+    /// [Base64Password]. Passwords in multiple categories are retrieved if any of them matches.
+    /// Return [`Ok<None>`] if no account with that username exists.
+    /// Return [Err] on a database error.
+    pub fn get_b64_passwords_by_category(
+        &self,
+        username: &str,
+        category: &str,
+    ) -> rusqlite::Result<Option<Vec<Base64Password>>> {
+        // Ensure account exists
+        if !self.account_exists(username)? {
+            return Ok(None);
+        };
+
+        let mut statement = self.connection.prepare(GET_USER_PASSWORDS_BY_CATEGORY)?;
+        let rows = statement.query_map(
+            [
+                helpers::bytes_to_b64(username.as_bytes()),
+                category.trim().to_owned(),
+            ],
+            b64_password_from_row,
+        )?;
         let mut passwords = Vec::new();
         for b64password_result in rows {
             passwords.push(b64password_result?);
@@ -77,14 +236,140 @@ impl Database {
         Ok(Some(passwords))
     }
 
+    /// Retrieve every stored password from the database as a [Vec] of [Base64Password], regardless
+    /// of owner.
+    /// Return [Err] on a database error.
+    pub fn get_all_b64_passwords(&self) -> rusqlite::Result<Vec<Base64Password>> {
+        let mut statement = self.connection.prepare(GET_ALL_PASSWORDS)?;
+        let rows = statement.query_map([], b64_password_from_row)?;
+        let mut passwords = Vec::new();
+        for b64password_result in rows {
+            passwords.push(b64password_result?);
+        }
+        Ok(passwords)
+    }
+
     /// Add a [Base64Password] to the `passwords` database table.
     /// Return [Err] if that password name + owner username combination already exists.
     pub fn add_new_password(&mut self, password: Base64Password) -> rusqlite::Result<()> {
         self.connection
-            .execute(INSERT_NEW_PASSWORD, password.as_tuple())?;
+            .execute(INSERT_NEW_PASSWORD, password_params(&password).as_slice())?;
+        Ok(())
+    }
+
+    /// Delete a given password from the `passwords` database table, along with its attachments.
+    /// Matches the owner username and the base-64 name ciphertext.
+    /// Return [`Ok<None>`] if no such password exists.
+    pub fn delete_password(
+        &mut self,
+        username: &str,
+        b64_name_ciphertext: &str,
+    ) -> rusqlite::Result<Option<()>> {
+        let num_rows = self.connection.execute(
+            DELETE_PASSWORD,
+            [
+                helpers::bytes_to_b64(username.as_bytes()),
+                b64_name_ciphertext.to_owned(),
+            ],
+        )?;
+        if num_rows == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Replace a set of stored passwords in the `passwords` database table. Each password is
+    /// matched by its owner username and the base-64 name ciphertext it is currently stored under.
+    /// Return [rusqlite::Error::QueryReturnedNoRows] and undoes the transaction iff any of the
+    /// passwords cannot be found.
+    pub fn update_passwords(
+        &mut self,
+        updates: Vec<(String, Base64Password)>,
+    ) -> rusqlite::Result<()> {
+        self.update_passwords_with_history(updates, vec![])
+    }
+
+    /// Like [Database::update_passwords], but also add the given entries to the
+    /// `password_history` database table in the same transaction. Each entry must belong to one
+    /// of the passwords as they were stored before the update. Only the newest
+    /// [MAX_PASSWORD_HISTORY] entries of each password are kept.
+    pub fn update_passwords_with_history(
+        &mut self,
+        updates: Vec<(String, Base64Password)>,
+        history: Vec<Base64PasswordHistoryEntry>,
+    ) -> rusqlite::Result<()> {
+        let tx = self.connection.transaction()?;
+        // Entries are added before their passwords are renamed, so that the rename cascades.
+        let mut owners = vec![];
+        for entry in &history {
+            tx.execute(INSERT_NEW_PASSWORD_HISTORY, entry.as_tuple())?;
+            if !owners.contains(&entry.b64_owner_username) {
+                owners.push(entry.b64_owner_username.clone());
+            }
+        }
+        for (old_b64_name_ciphertext, password) in updates {
+            let mut params = password_params(&password).to_vec();
+            params.push(&old_b64_name_ciphertext);
+            let num_changed = tx.execute(UPDATE_PASSWORD, params.as_slice())?;
+            if num_changed != 1 {
+                return Err(rusqlite::Error::QueryReturnedNoRows);
+            }
+        }
+        for b64_owner_username in owners {
+            tx.execute(
+                PRUNE_PASSWORD_HISTORY,
+                (b64_owner_username, MAX_PASSWORD_HISTORY),
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Add a [Base64PasswordHistoryEntry] to the `password_history` database table as it is,
+    /// without pruning older entries.
+    /// Return [Err] if the password does not exist.
+    pub fn add_new_password_history_entry(
+        &mut self,
+        entry: Base64PasswordHistoryEntry,
+    ) -> rusqlite::Result<()> {
+        self.connection
+            .execute(INSERT_NEW_PASSWORD_HISTORY, entry.as_tuple())?;
         Ok(())
     }
 
+    /// Retrieve the history of a stored password from the database as a [Vec] of
+    /// [Base64PasswordHistoryEntry], newest first. The password is identified by its owner
+    /// username and its base-64 name ciphertext.
+    /// Return [Err] on a database error.
+    pub fn get_b64_password_history(
+        &self,
+        username: &str,
+        b64_password_name_ciphertext: &str,
+    ) -> rusqlite::Result<Vec<Base64PasswordHistoryEntry>> {
+        let mut statement = self.connection.prepare(GET_PASSWORD_HISTORY)?;
+        let rows = statement.query_map(
+            [
+                helpers::bytes_to_b64(username.as_bytes()),
+                b64_password_name_ciphertext.to_owned(),
+            ],
+            |row| {
+                Ok(Base64PasswordHistoryEntry {
+                    b64_owner_username: row.get::<usize, String>(0)?,
+                    b64_password_name_ciphertext: row.get::<usize, String>(1)?,
+                    b64_content_ciphertext: row.get::<usize, String>(2)?,
+                    b64_content_nonce: row.get::<usize, String>(3)?,
+                    changed_at: row.get::<usize, i64>(4)?,
+                })
+            },
+        )?;
+        let mut history = Vec::new();
+        for b64_entry_result in rows {
+            history.push(b64_entry_result?);
+        }
+        Ok(history)
+    }
+
     /// Retrieve user account credentials from the database as a [Base64Account].
     /// Return [`Ok<None>`] if no account with that username exists.
     /// Return [Err] on a database error.
@@ -100,6 +385,9 @@ impl Database {
                     b64_dbl_hashed_password_salt: row.get::<usize, String>(3)?,
                     b64_encrypted_key_ciphertext: row.get::<usize, String>(4)?,
                     b64_encrypted_key_nonce: row.get::<usize, String>(5)?,
+                    kdf_params: row.get::<usize, String>(6)?,
+                    created_at: row.get::<usize, i64>(7)?,
+                    last_login: row.get::<usize, Option<i64>>(8)?,
                 })
             });
 
@@ -110,6 +398,69 @@ impl Database {
         }
     }
 
+    /// Replace the stored credentials of an existing account in the `user_credentials` database
+    /// table with the given [Base64Account], matched by username.
+    /// Return [rusqlite::Error::QueryReturnedNoRows] and undoes the transaction iff not exactly
+    /// one row would be changed.
+    pub fn update_account(&mut self, account: Base64Account) -> rusqlite::Result<()> {
+        let tx = self.connection.transaction()?;
+        let num_changed = tx.execute(UPDATE_ACCOUNT, account.as_tuple())?;
+        if num_changed != 1 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record the given Unix timestamp as the time of an account's last successful login.
+    /// Return [`Ok<None>`] if no account with that username exists.
+    pub fn update_last_login(
+        &mut self,
+        username: &str,
+        timestamp: i64,
+    ) -> rusqlite::Result<Option<()>> {
+        let num_rows = self.connection.execute(
+            UPDATE_ACCOUNT_LAST_LOGIN,
+            (helpers::bytes_to_b64(username.as_bytes()), timestamp),
+        )?;
+        if num_rows == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Retrieve every account from the database as a [Vec] of [Base64Account].
+    /// Return [Err] on a database error.
+    pub fn get_all_b64_accounts(&self) -> rusqlite::Result<Vec<Base64Account>> {
+        let mut statement = self.connection.prepare(GET_ALL_ACCOUNTS)?;
+        let rows = statement.query_map([], |row| {
+            Ok(Base64Account {
+                b64_username: row.get::<usize, String>(0)?,
+                b64_password_salt: row.get::<usize, String>(1)?,
+                b64_dbl_hashed_password_hash: row.get::<usize, String>(2)?,
+                b64_dbl_hashed_password_salt: row.get::<usize, String>(3)?,
+                b64_encrypted_key_ciphertext: row.get::<usize, String>(4)?,
+                b64_encrypted_key_nonce: row.get::<usize, String>(5)?,
+                kdf_params: row.get::<usize, String>(6)?,
+                created_at: row.get::<usize, i64>(7)?,
+                last_login: row.get::<usize, Option<i64>>(8)?,
+            })
+        })?;
+        let mut accounts = Vec::new();
+        for b64account_result in rows {
+            accounts.push(b64account_result?);
+        }
+        Ok(accounts)
+    }
+
+    /// Return true iff an account with the given username exists, without loading it.
+    pub fn account_exists(&self, username: &str) -> rusqlite::Result<bool> {
+        self.connection
+            .prepare(ACCOUNT_EXISTS)?
+            .exists([helpers::bytes_to_b64(username.as_bytes())])
+    }
+
     /// Add a [Base64Account] to the `user_credentials` database table.
     /// Return [Err] if that account already exists.
     pub fn add_new_account(&mut self, account: Base64Account) -> rusqlite::Result<()> {
@@ -137,19 +488,15 @@ impl Database {
     /// Return [Err] on a database error.
     pub fn get_b64_files(&self, username: &str) -> rusqlite::Result<Option<Vec<Base64FileData>>> {
         // Ensure account exists
-        if let Ok(None) = self.get_b64_account(username) {
+        if !self.account_exists(username)? {
             return Ok(None);
         };
 
         let mut statement = self.connection.prepare(GET_USER_FILES)?;
-        let rows = statement.query_map([helpers::bytes_to_b64(username.as_bytes())], |row| {
-            Ok(Base64FileData {
-                b64_path: row.get::<usize, String>(0)?,
-                b64_name: row.get::<usize, String>(1)?,
-                b64_owner_username: row.get::<usize, String>(2)?,
-                b64_content_nonce: row.get::<usize, String>(3)?,
-            })
-        })?;
+        let rows = statement.query_map(
+            [helpers::bytes_to_b64(username.as_bytes())],
+            b64_file_data_from_row,
+        )?;
         let mut files = Vec::new();
         for b64file_result in rows {
             files.push(b64file_result?);
@@ -157,21 +504,29 @@ impl Database {
         Ok(Some(files))
     }
 
+    /// Retrieve every stored file's data from the database as a [Vec] of [Base64FileData],
+    /// regardless of owner.
+    /// Return [Err] on a database error.
+    pub fn get_all_b64_files(&self) -> rusqlite::Result<Vec<Base64FileData>> {
+        let mut statement = self.connection.prepare(GET_ALL_FILES)?;
+        let rows = statement.query_map([], b64_file_data_from_row)?;
+        let mut files = Vec::new();
+        for b64file_result in rows {
+            files.push(b64file_result?);
+        }
+        Ok(files)
+    }
+
     /// Retrieve file data from the database as a [Base64FileData].
     /// Return [`Ok<None>`] if no file with that path exists.
     /// Return [Err] on a database error.
     pub fn get_b64_file_data(&self, path_string: &str) -> rusqlite::Result<Option<Base64FileData>> {
         let mut statement = self.connection.prepare(GET_FILE)?;
 
-        let file_data_result =
-            statement.query_row([helpers::bytes_to_b64(path_string.as_bytes())], |row| {
-                Ok(Base64FileData {
-                    b64_path: row.get::<usize, String>(0)?,
-                    b64_name: row.get::<usize, String>(1)?,
-                    b64_owner_username: row.get::<usize, String>(2)?,
-                    b64_content_nonce: row.get::<usize, String>(3)?,
-                })
-            });
+        let file_data_result = statement.query_row(
+            [helpers::bytes_to_b64(path_string.as_bytes())],
+            b64_file_data_from_row,
+        );
 
         match file_data_result {
             Ok(file_data) => Ok(Some(file_data)),
@@ -180,6 +535,14 @@ impl Database {
         }
     }
 
+    /// Return true iff a file with the given path exists in the `files` database table, without
+    /// loading its data.
+    pub fn file_data_exists(&self, path_string: &str) -> rusqlite::Result<bool> {
+        self.connection
+            .prepare(FILE_EXISTS)?
+            .exists([helpers::bytes_to_b64(path_string.as_bytes())])
+    }
+
     /// Add [Base64FileData] to the `files` database table.
     /// Return [Err] if that file path already exists.
     pub fn add_new_file_data(&mut self, b64_file_data: Base64FileData) -> rusqlite::Result<()> {
@@ -202,6 +565,47 @@ impl Database {
         }
     }
 
+    /// Add [Base64FileData] to the `files` database table, then run the given function, e.g. to
+    /// write the file to disk. The insertion is only committed if the function succeeds;
+    /// otherwise it is undone and the function's error is returned.
+    /// Return [Err] without running the function if that file path already exists.
+    pub fn add_new_file_data_with<F, E>(
+        &mut self,
+        b64_file_data: Base64FileData,
+        f: F,
+    ) -> Result<(), E>
+    where
+        F: FnOnce() -> Result<(), E>,
+        E: From<rusqlite::Error>,
+    {
+        let tx = self.connection.transaction()?;
+        tx.execute(INSERT_NEW_FILE, b64_file_data.as_tuple())?;
+        // Returning early drops the transaction, which rolls it back.
+        f()?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Delete a given file from the `files` database table, then run the given function, e.g. to
+    /// remove the file from disk. The deletion is only committed if the function succeeds;
+    /// otherwise it is undone and the function's error is returned.
+    /// Return [`Ok<None>`] without running the function if no such file exists.
+    pub fn delete_file_data_with<F, E>(&mut self, path_string: &str, f: F) -> Result<Option<()>, E>
+    where
+        F: FnOnce() -> Result<(), E>,
+        E: From<rusqlite::Error>,
+    {
+        let tx = self.connection.transaction()?;
+        let num_rows = tx.execute(DELETE_FILE, [helpers::bytes_to_b64(path_string.as_bytes())])?;
+        // Returning early drops the transaction, which rolls it back.
+        if num_rows == 0 {
+            return Ok(None);
+        }
+        f()?;
+        tx.commit()?;
+        Ok(Some(()))
+    }
+
     /// Update the content nonce of a file on the database.
     /// Return [rusqlite::Error::QueryReturnedNoRows] and undoes the transaction iff not exactly
     /// one row would be changed.
@@ -225,6 +629,418 @@ impl Database {
         Ok(())
     }
 
+    /// Update the content nonce and size of a file on the database after its content changed.
+    /// Return [rusqlite::Error::QueryReturnedNoRows] and undoes the transaction iff not exactly
+    /// one row would be changed.
+    pub fn update_file_content(
+        &mut self,
+        new_nonce: &[u8; 12],
+        file_size: u64,
+        path_string: &str,
+    ) -> rusqlite::Result<()> {
+        let tx = self.connection.transaction()?;
+        let num_changed = tx.execute(
+            UPDATE_FILE_CONTENT,
+            (
+                helpers::bytes_to_b64(new_nonce),
+                file_size as i64,
+                helpers::bytes_to_b64(path_string.as_bytes()),
+            ),
+        )?;
+        if num_changed != 1 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Return the total size in bytes of a user's encrypted files, without reading the files.
+    /// Return [`Ok<None>`] if no account with that username exists.
+    pub fn total_file_size(&self, username: &str) -> rusqlite::Result<Option<u64>> {
+        // Ensure account exists
+        if !self.account_exists(username)? {
+            return Ok(None);
+        };
+
+        let total = self.connection.query_row(
+            GET_USER_FILE_SIZE,
+            [helpers::bytes_to_b64(username.as_bytes())],
+            |row| row.get::<usize, i64>(0),
+        )?;
+        Ok(Some(total as u64))
+    }
+
+    /// Return the total size in bytes of every stored encrypted file, regardless of owner.
+    pub fn total_file_size_all(&self) -> rusqlite::Result<u64> {
+        let total = self
+            .connection
+            .query_row(GET_ALL_FILE_SIZE, [], |row| row.get::<usize, i64>(0))?;
+        Ok(total as u64)
+    }
+
+    /// Update the content nonces of a set of files in the `files` database table. Each file is
+    /// matched by its path.
+    /// Return [rusqlite::Error::QueryReturnedNoRows] and undoes the transaction iff any of the
+    /// files cannot be found.
+    pub fn update_file_content_nonces(
+        &mut self,
+        updates: Vec<([u8; 12], String)>,
+    ) -> rusqlite::Result<()> {
+        let tx = self.connection.transaction()?;
+        for (new_nonce, path_string) in updates {
+            let num_changed = tx.execute(
+                UPDATE_FILE_CONTENT_NONCE,
+                [
+                    helpers::bytes_to_b64(&new_nonce),
+                    helpers::bytes_to_b64(path_string.as_bytes()),
+                ],
+            )?;
+            if num_changed != 1 {
+                return Err(rusqlite::Error::QueryReturnedNoRows);
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Retrieve a user's recovery codes from the database as a [Vec] of [Base64RecoveryCode].
+    /// Return [`Ok<None>`] if no account with that username exists.
+    /// Return [Err] on a database error.
+    pub fn get_b64_recovery_codes(
+        &self,
+        username: &str,
+    ) -> rusqlite::Result<Option<Vec<Base64RecoveryCode>>> {
+        // Ensure account exists
+        if !self.account_exists(username)? {
+            return Ok(None);
+        };
+
+        let mut statement = self.connection.prepare(GET_USER_RECOVERY_CODES)?;
+        let rows = statement.query_map([helpers::bytes_to_b64(username.as_bytes())], |row| {
+            Ok(Base64RecoveryCode {
+                b64_owner_username: row.get::<usize, String>(0)?,
+                b64_code_hash: row.get::<usize, String>(1)?,
+                b64_code_salt: row.get::<usize, String>(2)?,
+                last_rotated_at: row.get::<usize, i64>(3)?,
+            })
+        })?;
+        let mut recovery_codes = Vec::new();
+        for b64recovery_code_result in rows {
+            recovery_codes.push(b64recovery_code_result?);
+        }
+        Ok(Some(recovery_codes))
+    }
+
+    /// Replace all of a user's recovery codes in the `recovery_codes` database table with the
+    /// given set of [Base64RecoveryCode]s.
+    /// Either all of the old codes are replaced or none of them are.
+    pub fn replace_recovery_codes(
+        &mut self,
+        username: &str,
+        recovery_codes: Vec<Base64RecoveryCode>,
+    ) -> rusqlite::Result<()> {
+        let tx = self.connection.transaction()?;
+        tx.execute(
+            DELETE_USER_RECOVERY_CODES,
+            [helpers::bytes_to_b64(username.as_bytes())],
+        )?;
+        for recovery_code in recovery_codes {
+            tx.execute(INSERT_NEW_RECOVERY_CODE, recovery_code.as_tuple())?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Delete a given recovery code from the `recovery_codes` database table.
+    /// Matches the username of the owner and the base-64 hash of the code.
+    /// Return [`Ok<None>`] if no such recovery code exists.
+    pub fn delete_recovery_code(
+        &mut self,
+        username: &str,
+        b64_code_hash: &str,
+    ) -> rusqlite::Result<Option<()>> {
+        let num_rows = self.connection.execute(
+            DELETE_RECOVERY_CODE,
+            [
+                helpers::bytes_to_b64(username.as_bytes()),
+                b64_code_hash.to_owned(),
+            ],
+        )?;
+        if num_rows == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Update the path of a file on the database.
+    /// Return [rusqlite::Error::QueryReturnedNoRows] and undoes the transaction iff not exactly
+    /// one row would be changed.
+    pub fn update_file_path(
+        &mut self,
+        new_path_string: &str,
+        path_string: &str,
+    ) -> rusqlite::Result<()> {
+        let tx = self.connection.transaction()?;
+        let num_changed = tx.execute(
+            UPDATE_FILE_PATH,
+            [
+                helpers::bytes_to_b64(new_path_string.as_bytes()),
+                helpers::bytes_to_b64(path_string.as_bytes()),
+            ],
+        )?;
+        if num_changed != 1 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Retrieve the attachments of a stored password from the database as a [Vec] of
+    /// [Base64Attachment]. The password is identified by its owner username and its base-64 name
+    /// ciphertext.
+    /// Return [Err] on a database error.
+    pub fn get_b64_attachments(
+        &self,
+        username: &str,
+        b64_password_name_ciphertext: &str,
+    ) -> rusqlite::Result<Vec<Base64Attachment>> {
+        let mut statement = self.connection.prepare(GET_PASSWORD_ATTACHMENTS)?;
+        let rows = statement.query_map(
+            [
+                helpers::bytes_to_b64(username.as_bytes()),
+                b64_password_name_ciphertext.to_owned(),
+            ],
+            |row| {
+                Ok(Base64Attachment {
+                    b64_owner_username: row.get::<usize, String>(0)?,
+                    b64_password_name_ciphertext: row.get::<usize, String>(1)?,
+                    b64_name: row.get::<usize, String>(2)?,
+                    b64_path: row.get::<usize, String>(3)?,
+                    b64_nonce: row.get::<usize, String>(4)?,
+                    size_bytes: row.get::<usize, i64>(5)?,
+                })
+            },
+        )?;
+        let mut attachments = Vec::new();
+        for b64attachment_result in rows {
+            attachments.push(b64attachment_result?);
+        }
+        Ok(attachments)
+    }
+
+    /// Add a [Base64Attachment] to the `password_attachments` database table.
+    /// Return [Err] if the password already has an attachment with that name, or if the password
+    /// does not exist.
+    pub fn add_new_attachment(&mut self, attachment: Base64Attachment) -> rusqlite::Result<()> {
+        self.connection
+            .execute(INSERT_NEW_ATTACHMENT, attachment.as_tuple())?;
+        Ok(())
+    }
+
+    /// Delete a given attachment from the `password_attachments` database table.
+    /// Matches the owner username, the base-64 password name ciphertext, and the attachment name.
+    /// Return [`Ok<None>`] if no such attachment exists.
+    pub fn delete_attachment(
+        &mut self,
+        username: &str,
+        b64_password_name_ciphertext: &str,
+        attachment_name: &str,
+    ) -> rusqlite::Result<Option<()>> {
+        let num_rows = self.connection.execute(
+            DELETE_ATTACHMENT,
+            [
+                helpers::bytes_to_b64(username.as_bytes()),
+                b64_password_name_ciphertext.to_owned(),
+                helpers::bytes_to_b64(attachment_name.as_bytes()),
+            ],
+        )?;
+        if num_rows == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Check that the connection to this [Database] is still usable without reading or changing
+    /// any data.
+    pub fn ping(&self) -> rusqlite::Result<()> {
+        self.connection.query_row("SELECT 1", [], |_| Ok(()))
+    }
+
+    /// Rebuild the database file, reclaiming the space left unused by deleted rows.
+    ///
+    /// SQLite can't vacuum inside a transaction. Every transaction borrows this [Database]
+    /// mutably, so none can be open while this runs.
+    pub fn vacuum(&self) -> rusqlite::Result<()> {
+        self.connection.execute(VACUUM, ())?;
+        Ok(())
+    }
+
+    /// Write a compacted copy of the database to the given path, e.g. as a backup. Fails if a file
+    /// already exists at that path.
+    pub fn vacuum_into<P>(&self, dest: P) -> rusqlite::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let dest = dest.as_ref();
+        let dest_str = dest
+            .to_str()
+            .ok_or_else(|| rusqlite::Error::InvalidPath(dest.to_path_buf()))?;
+        self.connection.execute(VACUUM_INTO, [dest_str])?;
+        Ok(())
+    }
+
+    /// Copy the database to the given path, overwriting any database already there. Unlike copying
+    /// the file, this is safe while other connections are writing to the database. The copy is
+    /// checked for corruption before returning.
+    pub fn backup<P>(&self, dest: P) -> rusqlite::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.connection
+            .backup(DatabaseName::Main, dest.as_ref(), None)?;
+        let problems = Database::connect(dest.as_ref())?.integrity_check()?;
+        if !problems.is_empty() {
+            return Err(rusqlite::Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_CORRUPT),
+                Some(problems.join("\n")),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Run SQLite's integrity check over the whole database and return the problems it reports.
+    /// An empty [Vec] means the database is not corrupted.
+    pub fn integrity_check(&self) -> rusqlite::Result<Vec<String>> {
+        let mut statement = self.connection.prepare(INTEGRITY_CHECK)?;
+        let rows = statement.query_map([], |row| row.get::<usize, String>(0))?;
+        let mut problems = Vec::new();
+        for row_result in rows {
+            let row = row_result?;
+            if row != "ok" {
+                problems.push(row);
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Find every row whose foreign key refers to a row that doesn't exist, e.g. a file owned by
+    /// a deleted account. An empty [Vec] means every reference is valid.
+    pub fn foreign_key_check(&self) -> rusqlite::Result<Vec<String>> {
+        let mut statement = self.connection.prepare(FOREIGN_KEY_CHECK)?;
+        let rows = statement.query_map([], |row| {
+            let table = row.get::<usize, String>(0)?;
+            let rowid = row.get::<usize, Option<i64>>(1)?;
+            let parent = row.get::<usize, String>(2)?;
+            Ok(match rowid {
+                Some(rowid) => format!(
+                    "Row {rowid} of table \"{table}\" refers to a missing row of table \"{parent}\"."
+                ),
+                None => format!(
+                    "A row of table \"{table}\" refers to a missing row of table \"{parent}\"."
+                ),
+            })
+        })?;
+        let mut problems = Vec::new();
+        for row_result in rows {
+            problems.push(row_result?);
+        }
+        Ok(problems)
+    }
+
+    /// Return the journal mode of the database, e.g. `wal`.
+    pub fn journal_mode(&self) -> rusqlite::Result<String> {
+        self.connection
+            .query_row(GET_JOURNAL_MODE, [], |row| row.get::<usize, String>(0))
+    }
+
+    /// Return the schema version of the database.
+    pub fn schema_version(&self) -> rusqlite::Result<u32> {
+        self.connection
+            .query_row(GET_SCHEMA_VERSION, [], |row| row.get::<usize, u32>(0))
+    }
+
+    /// Retrieve the configuration value stored under the given key.
+    /// Return [`Ok<None>`] if no value is stored under that key.
+    /// Return [Err] on a database error.
+    pub fn get_config(&self, key: &str) -> rusqlite::Result<Option<String>> {
+        let mut statement = self.connection.prepare(GET_CONFIG)?;
+        let mut rows = statement.query_map([key], |row| row.get::<usize, String>(0))?;
+        rows.next().transpose()
+    }
+
+    /// Store a configuration value under the given key, replacing any existing value.
+    pub fn set_config(&mut self, key: &str, value: &str) -> rusqlite::Result<()> {
+        self.connection.execute(SET_CONFIG, [key, value])?;
+        Ok(())
+    }
+
+    /// Count the rows of the given table.
+    pub fn count_rows(&self, table: Table) -> rusqlite::Result<usize> {
+        self.connection.query_row(
+            &format!("SELECT COUNT(*) FROM {}", table.name()),
+            [],
+            |row| row.get::<usize, usize>(0),
+        )
+    }
+
+    /// Count the rows of the given table owned by the given account.
+    /// Return [`Ok<None>`] if rows of that table don't belong to an account.
+    pub fn count_rows_by_owner(
+        &self,
+        table: Table,
+        username: &str,
+    ) -> rusqlite::Result<Option<usize>> {
+        let owner_column = match table.owner_column() {
+            Some(owner_column) => owner_column,
+            None => return Ok(None),
+        };
+        self.connection
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {} WHERE {owner_column} = ?1",
+                    table.name()
+                ),
+                [helpers::bytes_to_b64(username.as_bytes())],
+                |row| row.get::<usize, usize>(0),
+            )
+            .map(Some)
+    }
+
+    /// Read a single row of the given table as raw column names and values, without interpreting
+    /// them. Rows are ordered as SQLite stores them.
+    /// Return [`Ok<None>`] if the table has no row at that offset.
+    /// Return [Err] if the row could not be read.
+    pub fn get_raw_row(
+        &self,
+        table: Table,
+        offset: usize,
+    ) -> rusqlite::Result<Option<Vec<(String, Value)>>> {
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT * FROM {} LIMIT 1 OFFSET ?1", table.name()))?;
+        let column_names: Vec<String> = statement
+            .column_names()
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let row_result = statement.query_row([offset], |row| {
+            let mut values = Vec::new();
+            for i in 0..column_names.len() {
+                values.push(row.get::<usize, Value>(i)?);
+            }
+            Ok(values)
+        });
+
+        match row_result {
+            Ok(values) => Ok(Some(column_names.into_iter().zip(values).collect())),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Delete the contents of the given table.
     /// Return [Err] if that table does not exist.
     pub fn truncate_table(&mut self, table_name: &str) -> rusqlite::Result<()> {
@@ -239,6 +1055,76 @@ impl Database {
     pub fn path(&self) -> &Path {
         &self.path
     }
+
+    /// Get the flags with which the connection to this [Database] was opened.
+    pub fn open_flags(&self) -> OpenFlags {
+        self.open_flags
+    }
+
+    /// Return true iff the connection to this [Database] was opened read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.open_flags.contains(OpenFlags::SQLITE_OPEN_READ_ONLY)
+    }
+}
+
+// Read a stored password from a row with the columns of the `passwords` table, in order.
+fn b64_password_from_row(row: &Row) -> rusqlite::Result<Base64Password> {
+    Ok(Base64Password {
+        b64_owner_username: row.get::<usize, String>(0)?,
+        b64_name_ciphertext: row.get::<usize, String>(1)?,
+        b64_username_ciphertext: row.get::<usize, String>(2)?,
+        b64_content_ciphertext: row.get::<usize, String>(3)?,
+        b64_notes_ciphertext: row.get::<usize, String>(4)?,
+        b64_name_nonce: row.get::<usize, String>(5)?,
+        b64_username_nonce: row.get::<usize, String>(6)?,
+        b64_content_nonce: row.get::<usize, String>(7)?,
+        b64_notes_nonce: row.get::<usize, String>(8)?,
+        b64_url_ciphertext: row.get::<usize, Option<String>>(9)?,
+        b64_url_nonce: row.get::<usize, Option<String>>(10)?,
+        b64_totp_secret_ciphertext: row.get::<usize, Option<String>>(11)?,
+        b64_totp_secret_nonce: row.get::<usize, Option<String>>(12)?,
+        category: row.get::<usize, Option<String>>(13)?,
+        expires_at: row.get::<usize, Option<String>>(14)?,
+        created_at: row.get::<usize, i64>(15)?,
+        updated_at: row.get::<usize, i64>(16)?,
+    })
+}
+
+// Read stored file data from a row with the columns of the `files` table, in order.
+fn b64_file_data_from_row(row: &Row) -> rusqlite::Result<Base64FileData> {
+    Ok(Base64FileData {
+        b64_path: row.get::<usize, String>(0)?,
+        b64_name: row.get::<usize, String>(1)?,
+        b64_owner_username: row.get::<usize, String>(2)?,
+        b64_content_nonce: row.get::<usize, String>(3)?,
+        file_size: row.get::<usize, i64>(4)?,
+        extension: row.get::<usize, Option<String>>(5)?,
+        compressed: row.get::<usize, bool>(6)?,
+    })
+}
+
+// The statement parameters of a stored password, in the order of [Base64Password::as_tuple].
+// There are too many of them to pass the tuple itself.
+fn password_params(password: &Base64Password) -> [&dyn ToSql; 17] {
+    [
+        &password.b64_owner_username,
+        &password.b64_name_ciphertext,
+        &password.b64_username_ciphertext,
+        &password.b64_content_ciphertext,
+        &password.b64_notes_ciphertext,
+        &password.b64_name_nonce,
+        &password.b64_username_nonce,
+        &password.b64_content_nonce,
+        &password.b64_notes_nonce,
+        &password.b64_url_ciphertext,
+        &password.b64_url_nonce,
+        &password.b64_totp_secret_ciphertext,
+        &password.b64_totp_secret_nonce,
+        &password.category,
+        &password.expires_at,
+        &password.created_at,
+        &password.updated_at,
+    ]
 }
 
 #[cfg(test)]
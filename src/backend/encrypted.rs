@@ -1,66 +1,346 @@
 //! Functionality related to encryption.
+use std::{
+    fmt,
+    io::{Read, Write},
+    mem,
+};
+
 use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
-    Aes256Gcm, Key,
+    aead::{rand_core::RngCore, Aead, KeyInit, OsRng, Payload},
+    Aes256Gcm,
 };
+use chacha20poly1305::ChaCha20Poly1305;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::{error::Error, helpers};
 
+/// Length in bytes of the authentication tag appended to every ciphertext. AES-256-GCM and
+/// ChaCha20-Poly1305 both use 16-byte tags, so any valid ciphertext is at least this long, even if
+/// the plaintext was empty.
+pub const TAG_LENGTH: usize = 16;
+
+/// Current version of the stored format of an [Encrypted]'s nonce. AES-256-GCM values are stored
+/// without a version for compatibility with data written before the format was versioned; all
+/// other algorithms are stored as `<version>$<algorithm>$<base-64 nonce>`.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Size in bytes of the plaintext chunks [Encrypted::encrypt_stream] splits its input into.
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The bytes every stream written by [Encrypted::encrypt_stream] starts with.
+pub const STREAM_MAGIC: &[u8; 4] = b"DGRS";
+
+// Version of the stream format, stored right after STREAM_MAGIC.
+const STREAM_VERSION: u8 = 1;
+
+// Streams with larger chunks than this are rejected rather than read into memory.
+const MAX_STREAM_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// The authenticated encryption algorithms an [Encrypted] can use. Both take a 32-byte key and a
+/// 12-byte nonce.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Algorithm {
+    /// AES-256 in Galois/Counter Mode.
+    #[default]
+    Aes256Gcm,
+    /// The ChaCha20 stream cipher with a Poly1305 authenticator.
+    ChaCha20Poly1305,
+}
+impl Algorithm {
+    /// Return the name under which this [Algorithm] is stored.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Aes256Gcm => "aes256gcm",
+            Algorithm::ChaCha20Poly1305 => "chacha20poly1305",
+        }
+    }
+
+    /// Get the [Algorithm] stored under the given name.
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "aes256gcm" => Ok(Algorithm::Aes256Gcm),
+            "chacha20poly1305" => Ok(Algorithm::ChaCha20Poly1305),
+            _ => Err(Error::UnsupportedFormatError(format!(
+                "Unknown encryption algorithm \"{name}\"."
+            ))),
+        }
+    }
+}
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A 32-byte encryption key. The key is kept in a single place on the heap and overwritten with
+/// zeros when dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct KeyMaterial(Box<[u8; 32]>);
+impl KeyMaterial {
+    /// Wrap the given key bytes.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(Box::new(bytes))
+    }
+
+    /// Copy a key out of a slice, which must be exactly 32 bytes long.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 32 {
+            return Err(Error::DecryptionError(format!(
+                "Expected a 32-byte key, got {} bytes.",
+                bytes.len()
+            )));
+        }
+        let mut key = Self::new([0u8; 32]);
+        key.as_mut_bytes().copy_from_slice(bytes);
+        Ok(key)
+    }
+
+    /// Decode a base-64 key. The key is left out of any error message.
+    pub fn from_b64(b64_key: &str) -> Result<Self, Error> {
+        let bytes = Zeroizing::new(
+            helpers::b64_to_bytes(b64_key)
+                .map_err(|_| Error::DecryptionError("Key is not valid base-64.".to_owned()))?,
+        );
+        Self::from_slice(&bytes)
+    }
+
+    /// Return the bytes of this [KeyMaterial].
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Return the bytes of this [KeyMaterial] for writing a key into it in place.
+    pub fn as_mut_bytes(&mut self) -> &mut [u8; 32] {
+        &mut self.0
+    }
+}
+impl fmt::Debug for KeyMaterial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "KeyMaterial(..)")
+    }
+}
+impl Drop for KeyMaterial {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+impl ZeroizeOnDrop for KeyMaterial {}
+
 /// An encrypted string.
+///
+/// With the `serde` feature, the ciphertext and nonce are serialized as base-64 strings.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Encrypted {
+    #[cfg_attr(feature = "serde", serde(with = "helpers::serde_b64"))]
     ciphertext: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "helpers::serde_b64"))]
     nonce: [u8; 12],
+    algorithm: Algorithm,
 }
 impl Encrypted {
     /// Encrypt a given byte array using a key.
-    pub fn new(content: &[u8], key: &[u8; 32]) -> Result<Self, Error> {
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-        let cipher = Aes256Gcm::new(key.into());
-        match cipher.encrypt(&nonce, content) {
-            Ok(ciphertext) => Ok(Self {
-                ciphertext,
-                nonce: nonce.to_vec().try_into().unwrap(),
-            }),
-            Err(e) => Err(Error::EncryptionError(e.to_string())),
-        }
+    pub fn new(content: &[u8], key: &KeyMaterial) -> Result<Self, Error> {
+        Self::new_with_algorithm(content, key, Algorithm::default())
+    }
+
+    /// Encrypt a given byte array using a key and the given [Algorithm].
+    pub fn new_with_algorithm(
+        content: &[u8],
+        key: &KeyMaterial,
+        algorithm: Algorithm,
+    ) -> Result<Self, Error> {
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+        Self::from_nonce_with_algorithm(content, key, &nonce, algorithm)
     }
 
     /// Encrypt a given byte array using a key and a given nonce.
-    pub fn from_nonce(content: &[u8], key: &[u8; 32], nonce: &[u8; 12]) -> Result<Self, Error> {
-        let cipher = Aes256Gcm::new(key.into());
-        match cipher.encrypt(nonce.into(), content) {
-            Ok(ciphertext) => Ok(Self {
-                ciphertext,
-                nonce: nonce.to_vec().try_into().unwrap(),
-            }),
-            Err(e) => Err(Error::EncryptionError(e.to_string())),
-        }
+    pub fn from_nonce(content: &[u8], key: &KeyMaterial, nonce: &[u8; 12]) -> Result<Self, Error> {
+        Self::from_nonce_with_algorithm(content, key, nonce, Algorithm::default())
     }
 
-    /// Read an [Encrypted] from encrypted ciphertext.
+    /// Encrypt a given byte array using a key, a given nonce, and the given [Algorithm].
+    pub fn from_nonce_with_algorithm(
+        content: &[u8],
+        key: &KeyMaterial,
+        nonce: &[u8; 12],
+        algorithm: Algorithm,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            ciphertext: try_encrypt_bytes_key_nonce(content, key, nonce, algorithm)?,
+            nonce: *nonce,
+            algorithm,
+        })
+    }
+
+    /// Read an AES-256-GCM [Encrypted] from encrypted ciphertext.
     pub fn from_bytes(ciphertext: &[u8], nonce: &[u8; 12]) -> Self {
+        Self::from_bytes_with_algorithm(ciphertext, nonce, Algorithm::default())
+    }
+
+    /// Read an [Encrypted] from ciphertext encrypted with the given [Algorithm].
+    pub fn from_bytes_with_algorithm(
+        ciphertext: &[u8],
+        nonce: &[u8; 12],
+        algorithm: Algorithm,
+    ) -> Self {
         Self {
             ciphertext: ciphertext.to_vec(),
             nonce: *nonce,
+            algorithm,
         }
     }
 
-    /// Read an [Encrypted] from a base-64 string.
-    pub fn from_b64(b64_ciphertext: &str, b64_nonce: &str) -> Result<Self, Error> {
+    /// Read an [Encrypted] from its base-64 ciphertext and nonce. The [Algorithm] is read from the
+    /// stored nonce; see [FORMAT_VERSION].
+    pub fn from_b64_parts(b64_ciphertext: &str, b64_nonce: &str) -> Result<Self, Error> {
+        let (nonce, algorithm) = parse_b64_nonce(b64_nonce)?;
         Ok(Self {
             ciphertext: helpers::b64_to_bytes(b64_ciphertext)?,
-            nonce: helpers::b64_to_fixed::<&str, 12>(b64_nonce, "b64_nonce")?,
+            nonce,
+            algorithm,
         })
     }
 
-    /// Decrypt this [Encrypted] using its key.
-    pub fn decrypt(&self, key: &[u8]) -> Result<Vec<u8>, Error> {
-        let cipher = Aes256Gcm::new(key.into());
-        match cipher.decrypt(self.nonce().into(), self.ciphertext()) {
-            Ok(bytes) => Ok(bytes),
-            Err(e) => Err(Error::DecryptionError(e.to_string())),
+    /// Read an [Encrypted] from a single string written by [Encrypted::to_b64].
+    pub fn from_b64(b64: &str) -> Result<Self, Error> {
+        let (b64_url, algorithm) = split_algorithm_tag(b64)?;
+        let bytes = helpers::b64_url_to_bytes(b64_url)?;
+        if bytes.len() < 12 + TAG_LENGTH {
+            return Err(Error::InvalidLengthB64Error(
+                "b64".to_owned(),
+                12 + TAG_LENGTH,
+                bytes.len(),
+            ));
         }
+        let (nonce, ciphertext) = bytes.split_at(12);
+        Ok(Self::from_bytes_with_algorithm(
+            ciphertext,
+            nonce.try_into().unwrap(),
+            algorithm,
+        ))
+    }
+
+    /// Encrypt everything read from `reader` with AES-256-GCM and write it to `writer`, without
+    /// holding more than one chunk of it in memory at a time.
+    ///
+    /// The stream starts with a header of [STREAM_MAGIC], a version byte, and the chunk size as a
+    /// big-endian `u32`. The plaintext is then split into chunks of [STREAM_CHUNK_SIZE] bytes,
+    /// each encrypted separately with the given nonce XORed with the chunk's index. The last chunk
+    /// is marked as such in its associated data, so a stream which was cut short can't be
+    /// decrypted. Never use the same nonce for two streams.
+    pub fn encrypt_stream(
+        mut reader: impl Read,
+        key: &KeyMaterial,
+        nonce: &[u8; 12],
+        mut writer: impl Write,
+    ) -> Result<(), Error> {
+        let cipher = Aes256Gcm::new(key.as_bytes().into());
+        let mut header = STREAM_MAGIC.to_vec();
+        header.push(STREAM_VERSION);
+        header.extend_from_slice(&(STREAM_CHUNK_SIZE as u32).to_be_bytes());
+        writer.write_all(&header).map_err(stream_error)?;
+
+        let mut chunk = Zeroizing::new(Vec::with_capacity(STREAM_CHUNK_SIZE));
+        let mut next_chunk = Zeroizing::new(Vec::with_capacity(STREAM_CHUNK_SIZE));
+        read_chunk(&mut reader, &mut chunk, STREAM_CHUNK_SIZE)?;
+        for index in 0.. {
+            // The input is only known to be over once the next read comes up empty.
+            let last = chunk.len() < STREAM_CHUNK_SIZE || {
+                read_chunk(&mut reader, &mut next_chunk, STREAM_CHUNK_SIZE)?;
+                next_chunk.is_empty()
+            };
+            let payload = Payload {
+                msg: &chunk,
+                aad: &[last as u8],
+            };
+            let ciphertext = cipher
+                .encrypt(&stream_chunk_nonce(nonce, index).into(), payload)
+                .map_err(|e| Error::EncryptionError(e.to_string()))?;
+            writer.write_all(&ciphertext).map_err(stream_error)?;
+            if last {
+                break;
+            }
+            mem::swap(&mut chunk, &mut next_chunk);
+        }
+        writer.flush().map_err(stream_error)
+    }
+
+    /// Decrypt a stream written by [Encrypted::encrypt_stream] from `reader` and write the
+    /// plaintext to `writer` one chunk at a time.
+    ///
+    /// Each chunk is authenticated before it is written, but if a later chunk fails, the chunks
+    /// before it have already been written; discard everything written on error.
+    pub fn decrypt_stream(
+        mut reader: impl Read,
+        key: &KeyMaterial,
+        nonce: &[u8; 12],
+        mut writer: impl Write,
+    ) -> Result<(), Error> {
+        let cipher = Aes256Gcm::new(key.as_bytes().into());
+        let mut header = [0u8; 9];
+        reader.read_exact(&mut header).map_err(|_| {
+            Error::DecryptionError("Stream is too short to have a header.".to_owned())
+        })?;
+        if &header[..4] != STREAM_MAGIC {
+            return Err(Error::UnsupportedFormatError(
+                "Not an encrypted dgruft stream.".to_owned(),
+            ));
+        }
+        if header[4] != STREAM_VERSION {
+            return Err(Error::UnsupportedFormatError(format!(
+                "Unknown stream format version \"{}\".",
+                header[4]
+            )));
+        }
+        let chunk_size = u32::from_be_bytes([header[5], header[6], header[7], header[8]]) as usize;
+        if chunk_size == 0 || chunk_size > MAX_STREAM_CHUNK_SIZE {
+            return Err(Error::UnsupportedFormatError(format!(
+                "Unsupported stream chunk size {chunk_size}."
+            )));
+        }
+
+        let encrypted_chunk_size = chunk_size + TAG_LENGTH;
+        let mut chunk = Vec::with_capacity(encrypted_chunk_size);
+        let mut next_chunk = Vec::with_capacity(encrypted_chunk_size);
+        read_chunk(&mut reader, &mut chunk, encrypted_chunk_size)?;
+        for index in 0.. {
+            let last = chunk.len() < encrypted_chunk_size || {
+                read_chunk(&mut reader, &mut next_chunk, encrypted_chunk_size)?;
+                next_chunk.is_empty()
+            };
+            let payload = Payload {
+                msg: &chunk,
+                aad: &[last as u8],
+            };
+            let plaintext = Zeroizing::new(
+                cipher
+                    .decrypt(&stream_chunk_nonce(nonce, index).into(), payload)
+                    .map_err(|e| Error::DecryptionError(e.to_string()))?,
+            );
+            writer.write_all(&plaintext).map_err(stream_error)?;
+            if last {
+                break;
+            }
+            mem::swap(&mut chunk, &mut next_chunk);
+        }
+        writer.flush().map_err(stream_error)
+    }
+
+    /// Decrypt this [Encrypted] using its key. The plaintext is overwritten with zeros when
+    /// dropped.
+    pub fn decrypt(&self, key: &KeyMaterial) -> Result<Zeroizing<Vec<u8>>, Error> {
+        try_decrypt_bytes(self.ciphertext(), key, self.nonce(), self.algorithm)
+    }
+
+    /// Decrypt this [Encrypted], then encrypt the result again under the same key and
+    /// [Algorithm] with a fresh nonce.
+    pub fn reencrypt(&self, key: &KeyMaterial) -> Result<Self, Error> {
+        Self::new_with_algorithm(&self.decrypt(key)?, key, self.algorithm)
     }
 
     // GETTERS
@@ -80,43 +360,184 @@ impl Encrypted {
         &self.nonce
     }
 
-    /// Return the nonce of this [Encrypted] as a base-64 string.
+    /// Return the nonce of this [Encrypted] as a base-64 string, tagged with the [Algorithm]
+    /// and [FORMAT_VERSION] unless the algorithm is AES-256-GCM.
     pub fn nonce_as_b64(&self) -> String {
-        helpers::bytes_to_b64(&self.nonce)
+        let b64_nonce = helpers::bytes_to_b64(&self.nonce);
+        match self.algorithm {
+            Algorithm::Aes256Gcm => b64_nonce,
+            algorithm => format!("{FORMAT_VERSION}${algorithm}${b64_nonce}"),
+        }
+    }
+
+    /// Return the [Algorithm] used to encrypt this [Encrypted].
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Return the nonce followed by the ciphertext of this [Encrypted] as a single URL-safe
+    /// base-64 string, which can be copied and pasted as-is. Like [Encrypted::nonce_as_b64], the
+    /// string is tagged with the [Algorithm] and [FORMAT_VERSION] unless the algorithm is
+    /// AES-256-GCM.
+    pub fn to_b64(&self) -> String {
+        let mut bytes = Zeroizing::new(Vec::with_capacity(12 + self.ciphertext.len()));
+        bytes.extend_from_slice(&self.nonce);
+        bytes.extend_from_slice(&self.ciphertext);
+        let b64_url = helpers::bytes_to_b64_url(&bytes);
+        match self.algorithm {
+            Algorithm::Aes256Gcm => b64_url,
+            algorithm => format!("{FORMAT_VERSION}${algorithm}${b64_url}"),
+        }
+    }
+}
+impl Zeroize for Encrypted {
+    fn zeroize(&mut self) {
+        self.ciphertext.zeroize();
+        self.nonce.zeroize();
+    }
+}
+
+// Encrypt the given bytes with the given key, nonce, and algorithm.
+fn try_encrypt_bytes_key_nonce(
+    content: &[u8],
+    key: &KeyMaterial,
+    nonce: &[u8; 12],
+    algorithm: Algorithm,
+) -> Result<Vec<u8>, Error> {
+    let key = key.as_bytes();
+    let result = match algorithm {
+        Algorithm::Aes256Gcm => Aes256Gcm::new(key.into()).encrypt(nonce.into(), content),
+        Algorithm::ChaCha20Poly1305 => {
+            ChaCha20Poly1305::new(key.into()).encrypt(nonce.into(), content)
+        }
+    };
+    result.map_err(|e| Error::EncryptionError(e.to_string()))
+}
+
+// Decrypt the given bytes with the given key, nonce, and algorithm.
+fn try_decrypt_bytes(
+    ciphertext: &[u8],
+    key: &KeyMaterial,
+    nonce: &[u8; 12],
+    algorithm: Algorithm,
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let key = key.as_bytes();
+    let result = match algorithm {
+        Algorithm::Aes256Gcm => Aes256Gcm::new(key.into()).decrypt(nonce.into(), ciphertext),
+        Algorithm::ChaCha20Poly1305 => {
+            ChaCha20Poly1305::new(key.into()).decrypt(nonce.into(), ciphertext)
+        }
+    };
+    result
+        .map(Zeroizing::new)
+        .map_err(|e| Error::DecryptionError(e.to_string()))
+}
+
+// Derive the nonce of a stream chunk from the stream's nonce and the chunk's index.
+fn stream_chunk_nonce(nonce: &[u8; 12], index: u64) -> [u8; 12] {
+    let mut chunk_nonce = *nonce;
+    for (nonce_byte, index_byte) in chunk_nonce[4..].iter_mut().zip(index.to_be_bytes()) {
+        *nonce_byte ^= index_byte;
     }
+    chunk_nonce
+}
+
+// Read up to `len` bytes into `buf`, replacing its contents. Fewer bytes are only read at the end
+// of the input.
+fn read_chunk(reader: &mut impl Read, buf: &mut Vec<u8>, len: usize) -> Result<(), Error> {
+    buf.clear();
+    reader
+        .take(len as u64)
+        .read_to_end(buf)
+        .map_err(stream_error)?;
+    Ok(())
+}
+
+fn stream_error(err: std::io::Error) -> Error {
+    Error::UnhandledError(err.to_string())
+}
+
+// Read a stored nonce and the algorithm it was used with.
+fn parse_b64_nonce(b64_nonce: &str) -> Result<([u8; 12], Algorithm), Error> {
+    let (b64_nonce, algorithm) = split_algorithm_tag(b64_nonce)?;
+    Ok((
+        helpers::b64_to_fixed::<&str, 12>(b64_nonce, "b64_nonce")?,
+        algorithm,
+    ))
+}
+
+// Split a base-64 string into the string itself and the algorithm it is tagged with. Untagged
+// strings are AES-256-GCM.
+fn split_algorithm_tag(tagged: &str) -> Result<(&str, Algorithm), Error> {
+    let Some((version, rest)) = tagged.split_once('$') else {
+        // Unversioned, written before other algorithms were supported.
+        return Ok((tagged, Algorithm::Aes256Gcm));
+    };
+    if version != FORMAT_VERSION.to_string() {
+        return Err(Error::UnsupportedFormatError(format!(
+            "Unknown encryption format version \"{version}\"."
+        )));
+    }
+    let Some((name, b64)) = rest.split_once('$') else {
+        return Err(Error::UnsupportedFormatError(format!(
+            "Missing encryption algorithm in \"{tagged}\"."
+        )));
+    };
+    Ok((b64, Algorithm::from_name(name)?))
 }
 
 /// Generate a new key to be used for AES-256 encryption & decryption.
-pub fn new_key(slice: Option<&[u8; 32]>) -> [u8; 32] {
+pub fn new_key(slice: Option<&[u8; 32]>) -> KeyMaterial {
     if let Some(slice) = slice {
         // Generate key from slice
-        let key: &Key<Aes256Gcm> = slice.into();
-        key.to_vec().try_into().unwrap()
+        KeyMaterial::new(*slice)
     } else {
         // Randomly generate key
-        Aes256Gcm::generate_key(OsRng).into()
+        let mut key = KeyMaterial::new([0u8; 32]);
+        OsRng.fill_bytes(key.as_mut_bytes());
+        key
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use pretty_assertions::assert_eq;
+    use pretty_assertions::{assert_eq, assert_ne};
 
     #[test]
     fn test_new_key() {
         let key_1 = super::new_key(None);
-        let key_2 = super::new_key(Some(&key_1));
+        let key_2 = super::new_key(Some(key_1.as_bytes()));
         assert_eq!(key_1, key_2);
     }
 
+    #[test]
+    fn test_key_material() {
+        let key = new_key(None);
+        assert_eq!("KeyMaterial(..)", format!("{key:?}"));
+        assert_eq!(key, KeyMaterial::from_slice(key.as_bytes()).unwrap());
+        if let Err(Error::DecryptionError(_)) = KeyMaterial::from_slice(&[0u8; 31]) {
+        } else {
+            panic!("Wrong error type");
+        }
+    }
+
+    #[test]
+    fn test_zeroize() {
+        let key = new_key(None);
+        let mut encrypted = Encrypted::new(b"Hello, world!", &key).unwrap();
+        encrypted.zeroize();
+        assert!(encrypted.ciphertext().is_empty());
+        assert_eq!(&[0u8; 12], encrypted.nonce());
+    }
+
     #[test]
     fn test_aes256() {
         let plaintext = b"Hello, world!";
         let key = new_key(None);
         let encrypted = Encrypted::new(plaintext, &key).unwrap();
         let decrypted_text = encrypted.decrypt(&key).unwrap();
-        assert_eq!(&plaintext[..], decrypted_text);
+        assert_eq!(&plaintext[..], *decrypted_text);
     }
 
     #[test]
@@ -125,10 +546,21 @@ mod tests {
         let key = new_key(None);
         let encrypted = Encrypted::new(plaintext.as_bytes(), &key).unwrap();
         let decrypted_text = encrypted.decrypt(&key).unwrap();
-        assert_eq!(plaintext.as_bytes(), decrypted_text);
+        assert_eq!(plaintext.as_bytes(), *decrypted_text);
         assert_eq!("你好", std::str::from_utf8(&decrypted_text).unwrap());
     }
 
+    #[test]
+    fn test_reencrypt() {
+        let plaintext = b"Hello again, world!";
+        let key = new_key(None);
+        let encrypted_1 = Encrypted::new(plaintext, &key).unwrap();
+        let encrypted_2 = encrypted_1.reencrypt(&key).unwrap();
+        assert_ne!(encrypted_1.nonce(), encrypted_2.nonce());
+        assert_ne!(encrypted_1.ciphertext(), encrypted_2.ciphertext());
+        assert_eq!(&plaintext[..], *encrypted_2.decrypt(&key).unwrap());
+    }
+
     #[test]
     fn test_to_from_b64() {
         let plaintext = "привет";
@@ -137,11 +569,214 @@ mod tests {
 
         let ciphertext_b64 = encrypted_1.ciphertext_as_b64();
         let nonce_b64 = encrypted_1.nonce_as_b64();
-        let encrypted_2 = Encrypted::from_b64(&ciphertext_b64, &nonce_b64).unwrap();
+        let encrypted_2 = Encrypted::from_b64_parts(&ciphertext_b64, &nonce_b64).unwrap();
 
         let decrypted_1 = encrypted_1.decrypt(&key).unwrap();
         let decrypted_2 = encrypted_2.decrypt(&key).unwrap();
 
         assert_eq!(decrypted_1, decrypted_2);
     }
+
+    #[test]
+    fn test_to_from_combined_b64() {
+        let plaintext = "привет";
+        let key = new_key(None);
+        let aes = Encrypted::new(plaintext.as_bytes(), &key).unwrap();
+        let b64 = aes.to_b64();
+        assert!(b64
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        let aes_2 = Encrypted::from_b64(&b64).unwrap();
+        assert_eq!(aes.nonce(), aes_2.nonce());
+        assert_eq!(plaintext.as_bytes(), &aes_2.decrypt(&key).unwrap()[..]);
+
+        let chacha =
+            Encrypted::new_with_algorithm(plaintext.as_bytes(), &key, Algorithm::ChaCha20Poly1305)
+                .unwrap();
+        let chacha_2 = Encrypted::from_b64(&chacha.to_b64()).unwrap();
+        assert_eq!(Algorithm::ChaCha20Poly1305, chacha_2.algorithm());
+        assert_eq!(plaintext.as_bytes(), &chacha_2.decrypt(&key).unwrap()[..]);
+
+        // Too short to hold a nonce and a tag.
+        if let Err(Error::InvalidLengthB64Error(..)) =
+            Encrypted::from_b64(&helpers::bytes_to_b64_url(&[0u8; 20]))
+        {
+        } else {
+            panic!("Wrong error type");
+        }
+        assert!(Encrypted::from_b64("not base 64!").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let key = new_key(None);
+        let encrypted =
+            Encrypted::new_with_algorithm(b"serde", &key, Algorithm::ChaCha20Poly1305).unwrap();
+        let json = serde_json::to_string(&encrypted).unwrap();
+        assert!(json.contains(&encrypted.ciphertext_as_b64()));
+        assert!(json.contains(&helpers::bytes_to_b64(encrypted.nonce())));
+
+        let loaded: Encrypted = serde_json::from_str(&json).unwrap();
+        assert_eq!(encrypted.nonce(), loaded.nonce());
+        assert_eq!(Algorithm::ChaCha20Poly1305, loaded.algorithm());
+        assert_eq!(b"serde", &loaded.decrypt(&key).unwrap()[..]);
+
+        // A nonce of the wrong length is rejected.
+        let bad_json = json.replace(
+            &helpers::bytes_to_b64(encrypted.nonce()),
+            &helpers::bytes_to_b64(&[0u8; 11]),
+        );
+        assert!(serde_json::from_str::<Encrypted>(&bad_json).is_err());
+    }
+
+    #[test]
+    fn test_chacha20poly1305() {
+        let plaintext = b"Hello, ChaCha!";
+        let key = new_key(None);
+        let encrypted =
+            Encrypted::new_with_algorithm(plaintext, &key, Algorithm::ChaCha20Poly1305).unwrap();
+        assert_eq!(Algorithm::ChaCha20Poly1305, encrypted.algorithm());
+        assert_eq!(&plaintext[..], *encrypted.decrypt(&key).unwrap());
+
+        let aes = Encrypted::from_nonce(plaintext, &key, encrypted.nonce()).unwrap();
+        assert_ne!(aes.ciphertext(), encrypted.ciphertext());
+
+        let reencrypted = encrypted.reencrypt(&key).unwrap();
+        assert_eq!(Algorithm::ChaCha20Poly1305, reencrypted.algorithm());
+        assert_eq!(&plaintext[..], *reencrypted.decrypt(&key).unwrap());
+    }
+
+    #[test]
+    fn test_b64_format() {
+        let plaintext = b"versioned";
+        let key = new_key(None);
+
+        // AES-256-GCM keeps the unversioned format.
+        let aes = Encrypted::new(plaintext, &key).unwrap();
+        assert_eq!(helpers::bytes_to_b64(aes.nonce()), aes.nonce_as_b64());
+        let aes_2 =
+            Encrypted::from_b64_parts(&aes.ciphertext_as_b64(), &aes.nonce_as_b64()).unwrap();
+        assert_eq!(Algorithm::Aes256Gcm, aes_2.algorithm());
+        assert_eq!(&plaintext[..], *aes_2.decrypt(&key).unwrap());
+
+        let chacha =
+            Encrypted::new_with_algorithm(plaintext, &key, Algorithm::ChaCha20Poly1305).unwrap();
+        assert_eq!(
+            format!(
+                "1$chacha20poly1305${}",
+                helpers::bytes_to_b64(chacha.nonce())
+            ),
+            chacha.nonce_as_b64()
+        );
+        let chacha_2 =
+            Encrypted::from_b64_parts(&chacha.ciphertext_as_b64(), &chacha.nonce_as_b64()).unwrap();
+        assert_eq!(Algorithm::ChaCha20Poly1305, chacha_2.algorithm());
+        assert_eq!(&plaintext[..], *chacha_2.decrypt(&key).unwrap());
+
+        let b64_nonce = helpers::bytes_to_b64(chacha.nonce());
+        for bad_nonce in [
+            format!("2$chacha20poly1305${b64_nonce}"),
+            format!("1$rot13${b64_nonce}"),
+            format!("1${b64_nonce}"),
+        ] {
+            if let Err(Error::UnsupportedFormatError(_)) =
+                Encrypted::from_b64_parts(&chacha.ciphertext_as_b64(), &bad_nonce)
+            {
+            } else {
+                panic!("Expected UnsupportedFormatError for {bad_nonce:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_key_from_b64() {
+        let key = new_key(None);
+        let b64_key = helpers::bytes_to_b64(key.as_bytes());
+        assert_eq!(KeyMaterial::from_b64(&b64_key).unwrap(), key);
+
+        let short_key = helpers::bytes_to_b64(&[0u8; 16]);
+        assert!(KeyMaterial::from_b64(&short_key).is_err());
+        if let Err(Error::DecryptionError(message)) = KeyMaterial::from_b64("not a key!") {
+            assert!(!message.contains("not a key!"));
+        } else {
+            panic!("Wrong error type");
+        }
+    }
+
+    #[test]
+    fn test_stream_round_trip() {
+        let key = new_key(None);
+        let nonce = [7u8; 12];
+        for len in [
+            0,
+            1,
+            STREAM_CHUNK_SIZE - 1,
+            STREAM_CHUNK_SIZE,
+            STREAM_CHUNK_SIZE + 1,
+            3 * STREAM_CHUNK_SIZE + 100,
+        ] {
+            let plaintext: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let mut ciphertext = vec![];
+            Encrypted::encrypt_stream(&plaintext[..], &key, &nonce, &mut ciphertext).unwrap();
+            let num_chunks = len / STREAM_CHUNK_SIZE + 1;
+            assert!(ciphertext.len() <= 9 + len + num_chunks * TAG_LENGTH);
+            assert_eq!(STREAM_MAGIC, &ciphertext[..4]);
+
+            let mut decrypted = vec![];
+            Encrypted::decrypt_stream(&ciphertext[..], &key, &nonce, &mut decrypted).unwrap();
+            assert_eq!(plaintext, decrypted);
+
+            let mut decrypted = vec![];
+            assert!(
+                Encrypted::decrypt_stream(&ciphertext[..], &key, &[8u8; 12], &mut decrypted)
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_stream_tampering() {
+        let key = new_key(None);
+        let nonce = [7u8; 12];
+        let plaintext = vec![42u8; 2 * STREAM_CHUNK_SIZE + 10];
+        let mut ciphertext = vec![];
+        Encrypted::encrypt_stream(&plaintext[..], &key, &nonce, &mut ciphertext).unwrap();
+        let encrypted_chunk_size = STREAM_CHUNK_SIZE + TAG_LENGTH;
+
+        // Cutting the stream off after a whole chunk is detected.
+        let truncated = &ciphertext[..9 + 2 * encrypted_chunk_size];
+        assert!(Encrypted::decrypt_stream(truncated, &key, &nonce, &mut vec![]).is_err());
+        assert!(Encrypted::decrypt_stream(&ciphertext[..9], &key, &nonce, &mut vec![]).is_err());
+        assert!(Encrypted::decrypt_stream(&ciphertext[..5], &key, &nonce, &mut vec![]).is_err());
+
+        // So is swapping chunks around.
+        let mut swapped = ciphertext[..9].to_vec();
+        swapped
+            .extend_from_slice(&ciphertext[9 + encrypted_chunk_size..9 + 2 * encrypted_chunk_size]);
+        swapped.extend_from_slice(&ciphertext[9..9 + encrypted_chunk_size]);
+        swapped.extend_from_slice(&ciphertext[9 + 2 * encrypted_chunk_size..]);
+        assert!(Encrypted::decrypt_stream(&swapped[..], &key, &nonce, &mut vec![]).is_err());
+
+        let mut flipped = ciphertext.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert!(Encrypted::decrypt_stream(&flipped[..], &key, &nonce, &mut vec![]).is_err());
+
+        let mut bad_magic = ciphertext.clone();
+        bad_magic[0] = b'X';
+        if let Err(Error::UnsupportedFormatError(_)) =
+            Encrypted::decrypt_stream(&bad_magic[..], &key, &nonce, &mut vec![])
+        {
+        } else {
+            panic!("Wrong error type");
+        }
+        let mut huge_chunks = ciphertext.clone();
+        huge_chunks[5..9].copy_from_slice(&u32::MAX.to_be_bytes());
+        if let Err(Error::UnsupportedFormatError(_)) =
+            Encrypted::decrypt_stream(&huge_chunks[..], &key, &nonce, &mut vec![])
+        {
+        } else {
+            panic!("Wrong error type");
+        }
+    }
 }
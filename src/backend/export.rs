@@ -0,0 +1,225 @@
+//! Portable, encrypted exports of `dgruft` accounts, for moving them between machines.
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    backend::{
+        account::Base64Account,
+        encrypted::{Encrypted, KeyMaterial},
+    },
+    error::Error,
+    helpers,
+};
+
+/// The version of the export format written by this build of `dgruft`.
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// The decrypted contents of an export.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VaultExport {
+    /// Every exported account.
+    pub accounts: Vec<ExportedAccount>,
+}
+impl VaultExport {
+    /// Serialize this [VaultExport] to JSON, encrypt it with the given key, and wrap the result in
+    /// a versioned JSON envelope.
+    pub fn encrypt(&self, key: &KeyMaterial) -> Result<String, Error> {
+        let json = Zeroizing::new(
+            serde_json::to_vec(self).map_err(|err| Error::EncryptionError(err.to_string()))?,
+        );
+        let encrypted = Encrypted::new(&json, key)?;
+        let envelope = ExportEnvelope {
+            format_version: EXPORT_FORMAT_VERSION,
+            nonce: encrypted.nonce_as_b64(),
+            ciphertext: encrypted.ciphertext_as_b64(),
+        };
+        serde_json::to_string_pretty(&envelope)
+            .map_err(|err| Error::EncryptionError(err.to_string()))
+    }
+
+    /// Read a versioned JSON envelope written by [VaultExport::encrypt] and decrypt it with the
+    /// given key.
+    pub fn decrypt(envelope_json: &str, key: &KeyMaterial) -> Result<Self, Error> {
+        let envelope: ExportEnvelope = serde_json::from_str(envelope_json)
+            .map_err(|err| Error::UnsupportedFormatError(format!("Invalid export: {err}")))?;
+        if envelope.format_version != EXPORT_FORMAT_VERSION {
+            return Err(Error::UnsupportedFormatError(format!(
+                "Export format version {} is not supported.",
+                envelope.format_version
+            )));
+        }
+        let json =
+            Encrypted::from_b64_parts(&envelope.ciphertext, &envelope.nonce)?.decrypt(key)?;
+        serde_json::from_slice(&json).map_err(|err| Error::DecryptionError(err.to_string()))
+    }
+}
+
+/// An exported account alongside its decrypted passwords and files. The account credentials are
+/// exported exactly as stored, so the account keeps its password. The account key itself is not
+/// exported; it is unlocked with the account password on import.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportedAccount {
+    /// Account username.
+    pub username: String,
+    /// See [Base64Account::b64_password_salt].
+    pub b64_password_salt: String,
+    /// See [Base64Account::b64_dbl_hashed_password_hash].
+    pub b64_dbl_hashed_password_hash: String,
+    /// See [Base64Account::b64_dbl_hashed_password_salt].
+    pub b64_dbl_hashed_password_salt: String,
+    /// See [Base64Account::b64_encrypted_key_ciphertext].
+    pub b64_encrypted_key_ciphertext: String,
+    /// See [Base64Account::b64_encrypted_key_nonce].
+    pub b64_encrypted_key_nonce: String,
+    /// See [Base64Account::kdf_params].
+    pub kdf_params: String,
+    /// See [Base64Account::created_at]. Missing from exports written before accounts had
+    /// timestamps.
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    /// The account's passwords.
+    pub passwords: Vec<ExportedPassword>,
+    /// The account's files.
+    pub files: Vec<ExportedFile>,
+}
+impl ExportedAccount {
+    /// Create an [ExportedAccount] without any passwords or files from a stored account.
+    pub fn new(username: &str, b64_account: Base64Account) -> Self {
+        Self {
+            username: username.to_owned(),
+            b64_password_salt: b64_account.b64_password_salt,
+            b64_dbl_hashed_password_hash: b64_account.b64_dbl_hashed_password_hash,
+            b64_dbl_hashed_password_salt: b64_account.b64_dbl_hashed_password_salt,
+            b64_encrypted_key_ciphertext: b64_account.b64_encrypted_key_ciphertext,
+            b64_encrypted_key_nonce: b64_account.b64_encrypted_key_nonce,
+            kdf_params: b64_account.kdf_params,
+            created_at: Some(b64_account.created_at),
+            passwords: vec![],
+            files: vec![],
+        }
+    }
+
+    /// Convert the exported account credentials back into a [Base64Account] for storage. Accounts
+    /// exported without a creation time are dated to now, and imported accounts haven't logged
+    /// in yet.
+    pub fn to_b64_account(&self) -> Base64Account {
+        Base64Account {
+            b64_username: helpers::bytes_to_b64(self.username.as_bytes()),
+            b64_password_salt: self.b64_password_salt.clone(),
+            b64_dbl_hashed_password_hash: self.b64_dbl_hashed_password_hash.clone(),
+            b64_dbl_hashed_password_salt: self.b64_dbl_hashed_password_salt.clone(),
+            b64_encrypted_key_ciphertext: self.b64_encrypted_key_ciphertext.clone(),
+            b64_encrypted_key_nonce: self.b64_encrypted_key_nonce.clone(),
+            kdf_params: self.kdf_params.clone(),
+            created_at: self.created_at.unwrap_or_else(helpers::unix_timestamp),
+            last_login: None,
+        }
+    }
+}
+/// An exported, decrypted password.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportedPassword {
+    /// Password name.
+    pub name: String,
+    /// Password username.
+    pub username: String,
+    /// Password content.
+    pub content: String,
+    /// Password notes.
+    pub notes: String,
+    /// Password URL. Missing from exports written before passwords had URLs.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Password TOTP secret in base-32 format. Missing from exports written before passwords had
+    /// TOTP secrets.
+    #[serde(default)]
+    pub totp_secret: Option<String>,
+    /// Password category. Missing from exports written before passwords had categories.
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Password expiry date. Missing from exports written before passwords could expire.
+    #[serde(default)]
+    pub expires_at: Option<NaiveDate>,
+}
+impl Drop for ExportedPassword {
+    fn drop(&mut self) {
+        self.name.zeroize();
+        self.username.zeroize();
+        self.content.zeroize();
+        self.notes.zeroize();
+        self.url.zeroize();
+        self.totp_secret.zeroize();
+    }
+}
+
+/// An exported, decrypted file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportedFile {
+    /// File name.
+    pub name: String,
+    /// File content in base-64 format.
+    pub b64_content: String,
+}
+impl Drop for ExportedFile {
+    fn drop(&mut self) {
+        self.b64_content.zeroize();
+    }
+}
+
+/// The on-disk form of an export: an encrypted [VaultExport] tagged with the export format
+/// version.
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportEnvelope {
+    format_version: u32,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::encrypted;
+
+    #[test]
+    fn test_encrypt_envelope() {
+        let key = encrypted::new_key(None);
+        let mut password = ExportedPassword::default();
+        password.name.push_str("server");
+        password.content.push_str("hunter2");
+        let mut account = ExportedAccount::default();
+        account.username.push_str("export_account");
+        account.passwords.push(password);
+        let export = VaultExport {
+            accounts: vec![account],
+        };
+
+        let envelope_json = export.encrypt(&key).unwrap();
+        assert!(!envelope_json.contains("hunter2"));
+        assert!(!envelope_json.contains("export_account"));
+        let envelope: ExportEnvelope = serde_json::from_str(&envelope_json).unwrap();
+        assert_eq!(envelope.format_version, EXPORT_FORMAT_VERSION);
+
+        let decrypted = VaultExport::decrypt(&envelope_json, &key).unwrap();
+        assert_eq!(decrypted.accounts[0].username, "export_account");
+        assert_eq!(decrypted.accounts[0].passwords[0].content, "hunter2");
+
+        let wrong_key = encrypted::new_key(None);
+        assert!(VaultExport::decrypt(&envelope_json, &wrong_key).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let key = encrypted::new_key(None);
+        let envelope_json = VaultExport::default().encrypt(&key).unwrap();
+        let future_json = envelope_json.replace(
+            &format!("\"format_version\": {EXPORT_FORMAT_VERSION}"),
+            &format!("\"format_version\": {}", EXPORT_FORMAT_VERSION + 1),
+        );
+        assert_ne!(envelope_json, future_json);
+        if let Err(Error::UnsupportedFormatError(_)) = VaultExport::decrypt(&future_json, &key) {
+        } else {
+            panic!("Wrong error type");
+        }
+    }
+}
@@ -1,24 +1,55 @@
 //! Functionality related to reading and writing encrypted files.
 use std::{
     ffi::{OsStr, OsString},
-    fs::{File, OpenOptions},
-    io::{ErrorKind, Read, Write},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Seek, Write},
     path::{Path, PathBuf},
 };
 
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use zeroize::Zeroizing;
+
 use crate::{
-    backend::{account::Account, encrypted::Encrypted},
+    backend::{
+        account::Account,
+        encrypted::{Encrypted, KeyMaterial, STREAM_MAGIC},
+    },
+    config,
     error::Error,
     helpers,
 };
 
+/// The zstd compression level of compressed files.
+pub const ZSTD_LEVEL: i32 = 3;
+
 /// Metadata for an encrypted file accessible through `dgruft`.
+///
+/// The extension of the file is taken from its name when it is created, so that the file type is
+/// known without decrypting anything.
+///
+/// The content of a compressed file is compressed with zstd before it is encrypted, and
+/// decompressed after it is decrypted.
+///
+/// Content of at least the configured `stream_threshold_bytes` is encrypted in chunks with
+/// [Encrypted::encrypt_stream], using the content nonce as the stream nonce.
+///
+/// With the `serde` feature, the content nonce is serialized as a base-64 string. Only the
+/// metadata is serialized; the encrypted file itself is not.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FileData {
     path: PathBuf,
     name: OsString,
     owner_username: String,
+    #[cfg_attr(feature = "serde", serde(with = "helpers::serde_b64"))]
     content_nonce: [u8; 12],
+    file_size: u64,
+    extension: Option<String>,
+    compressed: bool,
 }
 impl FileData {
     /// Create a new empty [FileData].
@@ -34,7 +65,7 @@ impl FileData {
     /// Non-UTF-8 filesystem encodings are unsupported.
     pub fn new_with_key<P>(
         username: &str,
-        key: &[u8; 32],
+        key: &KeyMaterial,
         name: OsString,
         path: P,
     ) -> Result<Self, Error>
@@ -48,11 +79,42 @@ impl FileData {
     /// Non-UTF-8 filesystem encodings are unsupported.
     pub fn new_with_content_and_key<P>(
         username: &str,
-        key: &[u8; 32],
+        key: &KeyMaterial,
+        name: OsString,
+        content: &[u8],
+        path: P,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::create(username, key, name, content, path, false)
+    }
+
+    /// Create a new compressed [FileData] using the given content and key. The content is
+    /// compressed before it is encrypted, which makes text-heavy files much smaller on disk.
+    /// Non-UTF-8 filesystem encodings are unsupported.
+    pub fn new_compressed_with_content_and_key<P>(
+        username: &str,
+        key: &KeyMaterial,
         name: OsString,
         content: &[u8],
         path: P,
     ) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::create(username, key, name, content, path, true)
+    }
+
+    // Helper function to create a new file with the given content.
+    fn create<P>(
+        username: &str,
+        key: &KeyMaterial,
+        name: OsString,
+        content: &[u8],
+        path: P,
+        compressed: bool,
+    ) -> Result<Self, Error>
     where
         P: AsRef<Path>,
     {
@@ -64,8 +126,8 @@ impl FileData {
         };
 
         // Create file, handle file creation errors
-        let content_nonce = match File::create_new(&path) {
-            Ok(_) => Self::encrypt_then_write(&path, content, key)?,
+        let (content_nonce, file_size) = match File::create_new(&path) {
+            Ok(_) => Self::encrypt_then_write(&path, content, key, compressed)?,
             Err(err) => match err.kind() {
                 ErrorKind::AlreadyExists => {
                     return Err(Error::FileAlreadyExistsError(PathBuf::from(path.as_ref())))
@@ -79,9 +141,12 @@ impl FileData {
 
         Ok(Self {
             path: PathBuf::from(path.as_ref()),
+            extension: extension_of(&name),
             name,
             owner_username: username.to_owned(),
             content_nonce,
+            file_size,
+            compressed,
         })
     }
 
@@ -98,16 +163,26 @@ impl FileData {
         P: AsRef<Path>,
     {
         // Get encryption key.
-        let key = *account.unlock(password)?.key();
-        Self::new_with_content_and_key(account.username(), &key, name, content, path)
+        let unlocked = account.unlock(password)?;
+        Self::new_with_content_and_key(account.username(), unlocked.key(), name, content, path)
     }
 
     /// Decrypt then edit the file pointed to by this [FileData] in the computer's default text editor. The file
     /// is then re-encrypted and saved after editing.
-    pub fn edit(&mut self, key: &[u8; 32]) -> Result<(), Error> {
+    ///
+    /// The decrypted content is edited in a temporary file inside a private temporary directory.
+    /// The temporary file has the extension of this [FileData], so that editors can pick the right
+    /// mode for it. It is overwritten with zeros and deleted afterwards, whether or not the edit
+    /// succeeded.
+    pub fn edit(&mut self, key: &KeyMaterial) -> Result<(), Error> {
         let decrypted_bytes = self.open_decrypted(key)?;
 
-        let edited_bytes = match edit::edit_bytes(decrypted_bytes) {
+        let temp_file = WipedTempFile::new(&self.temp_file_name(), &decrypted_bytes)?;
+        let edit_result =
+            edit::edit_file(temp_file.path()).and_then(|_| fs::read(temp_file.path()));
+        drop(temp_file);
+
+        let edited_bytes = match edit_result {
             Ok(bytes) => bytes,
             Err(err) => match err.kind() {
                 ErrorKind::InvalidData => {
@@ -120,27 +195,35 @@ impl FileData {
             },
         };
 
-        let content_nonce = Self::encrypt_then_write(&self.path, &edited_bytes, key)?;
+        let (content_nonce, file_size) =
+            Self::encrypt_then_write(&self.path, &edited_bytes, key, self.compressed)?;
 
         self.content_nonce = content_nonce;
+        self.file_size = file_size;
 
         Ok(())
     }
 
-    /// Open, then decrypt, the file at the path defined by this [FileData].
-    pub fn open_decrypted(&self, key: &[u8; 32]) -> Result<Vec<u8>, Error> {
-        let mut file = Self::open_file(&self.path)?;
-        let mut encrypted_bytes: Vec<u8> = vec![];
-        if let Err(err) = file.read_to_end(&mut encrypted_bytes) {
-            match err.kind() {
-                ErrorKind::PermissionDenied => {
-                    return Err(Error::PermissionDeniedError(self.path.clone()));
-                }
-                _ => return Err(Error::UnhandledError(err.to_string())),
-            }
+    /// Decrypt the file pointed to by this [FileData], then re-encrypt it with a fresh nonce and
+    /// overwrite it.
+    pub fn reencrypt(&mut self, key: &KeyMaterial) -> Result<(), Error> {
+        let decrypted_bytes = self.open_decrypted(key)?;
+        (self.content_nonce, self.file_size) =
+            Self::encrypt_then_write(&self.path, &decrypted_bytes, key, self.compressed)?;
+        Ok(())
+    }
+
+    /// Open, then decrypt, the file at the path defined by this [FileData]. The content of a
+    /// compressed file is decompressed as well.
+    pub fn open_decrypted(&self, key: &KeyMaterial) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let decrypted_bytes = self.decrypt_file(key)?;
+        if !self.compressed {
+            return Ok(decrypted_bytes);
+        }
+        match zstd::decode_all(&decrypted_bytes[..]) {
+            Ok(decompressed_bytes) => Ok(Zeroizing::new(decompressed_bytes)),
+            Err(err) => Err(Error::DecryptionError(err.to_string())),
         }
-        let encrypted_content = Encrypted::from_bytes(&encrypted_bytes, &self.content_nonce);
-        encrypted_content.decrypt(key)
     }
 
     /// Load [FileData] from [Base64FileData]— a set of base-64-encoded strings.
@@ -166,6 +249,9 @@ impl FileData {
             name,
             owner_username,
             content_nonce,
+            file_size: b64_file_data.file_size as u64,
+            extension: b64_file_data.extension,
+            compressed: b64_file_data.compressed,
         })
     }
 
@@ -185,9 +271,26 @@ impl FileData {
             b64_name,
             b64_owner_username: helpers::bytes_to_b64(self.owner_username().as_bytes()),
             b64_content_nonce: helpers::bytes_to_b64(self.content_nonce()),
+            file_size: self.file_size() as i64,
+            extension: self.extension.clone(),
+            compressed: self.compressed,
         })
     }
 
+    // Helper function to name the temporary file the file is edited in. The name of the file
+    // normally already ends with its extension.
+    fn temp_file_name(&self) -> OsString {
+        match &self.extension {
+            Some(extension) if extension_of(&self.name).as_ref() != Some(extension) => {
+                let mut temp_file_name = self.name.clone();
+                temp_file_name.push(".");
+                temp_file_name.push(extension);
+                temp_file_name
+            }
+            _ => self.name.clone(),
+        }
+    }
+
     // Helper function to open file.
     fn open_file<P>(path: P) -> Result<File, Error>
     where
@@ -210,50 +313,118 @@ impl FileData {
         }
     }
 
-    /// Encrypt the given content with the given key and nonce, then write it to the file.
+    /// Encrypt the given content with the given key and nonce, then write it to the file. The
+    /// content is compressed first if `compressed` is set.
     pub fn encrypt_write_with_nonce<P>(
         path: P,
         content: &[u8],
-        key: &[u8; 32],
+        key: &KeyMaterial,
         nonce: &[u8; 12],
+        compressed: bool,
     ) -> Result<(), Error>
     where
         P: AsRef<Path>,
     {
-        let encrypted_content = Encrypted::from_nonce(content, key, nonce)?;
-        Self::write_encrypted(path, encrypted_content)?;
+        let content = Self::maybe_compress(content, compressed)?;
+        Self::write_encrypted(path, &content, key, nonce)?;
         Ok(())
     }
 
-    // Helper function to write content to file. Returns nonce used to encrypt text.
-    fn encrypt_then_write<P>(path: P, content: &[u8], key: &[u8; 32]) -> Result<[u8; 12], Error>
+    // Helper function to write content to file, compressing it first if `compressed` is set.
+    // Returns nonce used to encrypt text and the size of the encrypted content.
+    fn encrypt_then_write<P>(
+        path: P,
+        content: &[u8],
+        key: &KeyMaterial,
+        compressed: bool,
+    ) -> Result<([u8; 12], u64), Error>
     where
         P: AsRef<Path>,
     {
-        let encrypted_content = Encrypted::new(content, key)?;
-        let nonce = *encrypted_content.nonce();
-        Self::write_encrypted(path, encrypted_content)?;
-        Ok(nonce)
+        let content = Self::maybe_compress(content, compressed)?;
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+        let file_size = Self::write_encrypted(path, &content, key, &nonce)?;
+        Ok((nonce, file_size))
+    }
+
+    // Helper function to compress content before it is encrypted. Compressing is safe here, as the
+    // ciphertext of a file is never observed alongside attacker-controlled content.
+    fn maybe_compress(content: &[u8], compressed: bool) -> Result<Zeroizing<Vec<u8>>, Error> {
+        if !compressed {
+            return Ok(Zeroizing::new(content.to_vec()));
+        }
+        match zstd::encode_all(content, ZSTD_LEVEL) {
+            Ok(compressed_content) => Ok(Zeroizing::new(compressed_content)),
+            Err(err) => Err(Error::EncryptionError(err.to_string())),
+        }
     }
 
-    // Helper function to write encrypted bytes.
-    fn write_encrypted<P>(path: P, encrypted_content: Encrypted) -> Result<(), Error>
+    // Helper function to encrypt content with the given nonce and overwrite the file with it.
+    // Content of at least the configured stream threshold is encrypted in chunks. Returns the size
+    // of the encrypted content.
+    fn write_encrypted<P>(
+        path: P,
+        content: &[u8],
+        key: &KeyMaterial,
+        nonce: &[u8; 12],
+    ) -> Result<u64, Error>
     where
         P: AsRef<Path>,
     {
-        let mut file = Self::open_file(&path)?;
-        if let Err(err) = file.write_all(encrypted_content.ciphertext()) {
-            match err.kind() {
-                ErrorKind::NotFound => {
-                    return Err(Error::FileNotFoundError(PathBuf::from(path.as_ref())))
-                }
-                ErrorKind::PermissionDenied => {
-                    return Err(Error::PermissionDeniedError(PathBuf::from(path.as_ref())))
-                }
-                _ => return Err(Error::UnhandledError(err.to_string())),
+        let encrypted_content = if content.len() as u64 >= config::get().stream_threshold_bytes {
+            None
+        } else {
+            Some(Encrypted::from_nonce(content, key, nonce)?)
+        };
+        let file = Self::open_file(&path)?;
+        // Truncate first so no bytes of a longer, older ciphertext are left at the end.
+        file.set_len(0)
+            .map_err(|err| io_error(err, path.as_ref()))?;
+        match encrypted_content {
+            Some(encrypted_content) => (&file)
+                .write_all(encrypted_content.ciphertext())
+                .map_err(|err| io_error(err, path.as_ref()))?,
+            None => Encrypted::encrypt_stream(content, key, nonce, BufWriter::new(&file))?,
+        }
+        file.metadata()
+            .map(|metadata| metadata.len())
+            .map_err(|err| io_error(err, path.as_ref()))
+    }
+
+    // Helper function to decrypt the whole file. A file starting with [STREAM_MAGIC] is normally
+    // a stream, but content encrypted all at once can start with the same bytes by chance, so it
+    // is decrypted all at once if it isn't a valid stream.
+    fn decrypt_file(&self, key: &KeyMaterial) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let mut file = Self::open_file(&self.path)?;
+        let to_error = |err| io_error(err, &self.path);
+
+        let mut magic = vec![];
+        (&mut file)
+            .take(STREAM_MAGIC.len() as u64)
+            .read_to_end(&mut magic)
+            .map_err(to_error)?;
+        file.rewind().map_err(to_error)?;
+        let mut stream_err = None;
+        if magic == STREAM_MAGIC {
+            let mut decrypted_bytes = Zeroizing::new(vec![]);
+            match Encrypted::decrypt_stream(
+                BufReader::new(&mut file),
+                key,
+                &self.content_nonce,
+                &mut *decrypted_bytes,
+            ) {
+                Ok(()) => return Ok(decrypted_bytes),
+                Err(err) => stream_err = Some(err),
             }
+            file.rewind().map_err(to_error)?;
         }
-        Ok(())
+
+        let mut encrypted_bytes: Vec<u8> = vec![];
+        file.read_to_end(&mut encrypted_bytes).map_err(to_error)?;
+        Encrypted::from_bytes(&encrypted_bytes, &self.content_nonce)
+            .decrypt(key)
+            .map_err(|err| stream_err.unwrap_or(err))
     }
 
     // GETTERS
@@ -277,6 +448,77 @@ impl FileData {
     pub fn content_nonce(&self) -> &[u8; 12] {
         &self.content_nonce
     }
+
+    /// Return the size in bytes of the encrypted content of this [FileData].
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// Return the extension of this [FileData] without the leading dot, if its name has one.
+    pub fn extension(&self) -> Option<&str> {
+        self.extension.as_deref()
+    }
+
+    /// Return `true` iff the content of this [FileData] is compressed before it is encrypted.
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
+}
+impl fmt::Display for FileData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FileData {{ name: {:?}, owner: {:?}, path: {:?}, size: {} bytes{} }}",
+            self.name,
+            self.owner_username,
+            self.path,
+            self.file_size,
+            if self.compressed { " (compressed)" } else { "" }
+        )
+    }
+}
+
+/// Return the extension of a file name without the leading dot, if it has a UTF-8 one.
+pub fn extension_of(name: &OsStr) -> Option<String> {
+    Path::new(name)
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_owned)
+}
+
+// A temporary file holding decrypted content, inside a temporary directory only accessible to the
+// current user. Its content is overwritten with zeros before it is deleted.
+struct WipedTempFile {
+    dir: TempDir,
+    name: OsString,
+}
+impl WipedTempFile {
+    // Create a temporary file with the given name and content.
+    fn new(name: &OsStr, content: &[u8]) -> Result<Self, Error> {
+        let dir = match tempfile::Builder::new().prefix("dgruft").tempdir() {
+            Ok(dir) => dir,
+            Err(err) => return Err(Error::UnhandledError(err.to_string())),
+        };
+        let temp_file = Self {
+            dir,
+            name: name.to_owned(),
+        };
+        if let Err(err) = fs::write(temp_file.path(), content) {
+            return Err(Error::UnhandledError(err.to_string()));
+        }
+        Ok(temp_file)
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.path().join(&self.name)
+    }
+}
+impl Drop for WipedTempFile {
+    fn drop(&mut self) {
+        // The directory itself is deleted right after this, when `dir` is dropped.
+        let _ = helpers::overwrite_with_zeros(&self.path());
+        let _ = fs::remove_file(self.path());
+    }
 }
 
 /// [FileData] converted for base-64 storage.
@@ -290,24 +532,45 @@ pub struct Base64FileData {
     pub b64_owner_username: String,
     /// Encrypted content nonce in base-64 format.
     pub b64_content_nonce: String,
+    /// Size of the encrypted content in bytes.
+    pub file_size: i64,
+    /// File extension in plain text, if the file name has one.
+    pub extension: Option<String>,
+    /// Whether the content is compressed before it is encrypted.
+    pub compressed: bool,
 }
 impl Base64FileData {
     /// Output fields as tuple.
-    pub fn as_tuple(&self) -> (&str, &str, &str, &str) {
+    pub fn as_tuple(&self) -> (&str, &str, &str, &str, i64, Option<&str>, bool) {
         (
             &self.b64_path,
             &self.b64_name,
             &self.b64_owner_username,
             &self.b64_content_nonce,
+            self.file_size,
+            self.extension.as_deref(),
+            self.compressed,
         )
     }
 }
 
+// Convert an I/O error on the file at the given path to an [Error].
+fn io_error(err: io::Error, path: &Path) -> Error {
+    match err.kind() {
+        ErrorKind::NotFound => Error::FileNotFoundError(path.to_path_buf()),
+        ErrorKind::PermissionDenied => Error::PermissionDeniedError(path.to_path_buf()),
+        _ => Error::UnhandledError(err.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::backend::account::Account;
-    use pretty_assertions::assert_eq;
+    use crate::backend::{
+        account::Account,
+        encrypted::{self, TAG_LENGTH},
+    };
+    use pretty_assertions::{assert_eq, assert_ne};
     use std::process::Command;
 
     const TEST_USERNAME: &str = "my_account";
@@ -338,7 +601,7 @@ Don't tell anybody!!!!";
         )
         .unwrap();
         let content = my_file.open_decrypted(unlocked.key()).unwrap();
-        assert_eq!(TEST_CONTENT.as_bytes(), content);
+        assert_eq!(TEST_CONTENT.as_bytes(), *content);
         assert_eq!(
             TEST_CONTENT,
             helpers::bytes_to_utf8(&content, "test_content").unwrap()
@@ -384,12 +647,19 @@ Don't tell anybody!!!!";
         )
         .unwrap();
 
+        // Ciphertext is as long as the plaintext plus the authentication tag.
+        let file_size = TEST_CONTENT.len() as u64 + encrypted::TAG_LENGTH as u64;
+        assert_eq!(file_size, my_file.file_size());
+        assert_eq!(file_size, fs::metadata(test_file).unwrap().len());
+
         let my_b64_file = my_file.to_b64().unwrap();
         let my_loaded_file = FileData::from_b64(my_b64_file).unwrap();
+        assert_eq!(file_size, my_loaded_file.file_size());
+        assert_eq!(None, my_loaded_file.extension());
 
         let content = my_loaded_file.open_decrypted(unlocked.key()).unwrap();
         assert_eq!(&OsString::from(test_name), my_loaded_file.name());
-        assert_eq!(TEST_CONTENT.as_bytes(), content);
+        assert_eq!(TEST_CONTENT.as_bytes(), *content);
         assert_eq!(
             TEST_CONTENT,
             helpers::bytes_to_utf8(&content, "test_content").unwrap()
@@ -397,6 +667,228 @@ Don't tell anybody!!!!";
         cleanup_test_file(test_file);
     }
 
+    #[test]
+    fn test_extension() {
+        assert_eq!(Some("md".to_owned()), extension_of(OsStr::new("notes.md")));
+        assert_eq!(
+            Some("gz".to_owned()),
+            extension_of(OsStr::new("archive.tar.gz"))
+        );
+        assert_eq!(None, extension_of(OsStr::new("Makefile")));
+        assert_eq!(None, extension_of(OsStr::new(".bashrc")));
+
+        let test_file = "test_files/testfile7.toml";
+        let my_account = Account::new(TEST_USERNAME, TEST_PASSWORD).unwrap();
+        let my_file = FileData::new(
+            &my_account,
+            TEST_PASSWORD,
+            OsString::from("testfile7.toml"),
+            test_file,
+        )
+        .unwrap();
+        assert_eq!(Some("toml"), my_file.extension());
+        assert_eq!(OsString::from("testfile7.toml"), my_file.temp_file_name());
+
+        let my_loaded_file = FileData::from_b64(my_file.to_b64().unwrap()).unwrap();
+        assert_eq!(Some("toml"), my_loaded_file.extension());
+
+        // A file whose extension doesn't match its name is still edited with its extension.
+        let mut my_b64_file = my_file.to_b64().unwrap();
+        my_b64_file.b64_name = helpers::bytes_to_b64(b"testfile7");
+        let my_renamed_file = FileData::from_b64(my_b64_file).unwrap();
+        assert_eq!(
+            OsString::from("testfile7.toml"),
+            my_renamed_file.temp_file_name()
+        );
+        cleanup_test_file(test_file);
+    }
+
+    #[test]
+    fn test_compressed() {
+        let test_file = "test_files/testfile8.json";
+        let test_name = "testfile8.json";
+        let content = TEST_CONTENT.repeat(100);
+        let key = encrypted::new_key(None);
+        let mut my_file = FileData::new_compressed_with_content_and_key(
+            TEST_USERNAME,
+            &key,
+            OsString::from(test_name),
+            content.as_bytes(),
+            test_file,
+        )
+        .unwrap();
+        assert!(my_file.compressed());
+        assert!(my_file.file_size() < content.len() as u64 / 10);
+        assert_eq!(my_file.file_size(), fs::metadata(test_file).unwrap().len());
+        assert_eq!(
+            content.as_bytes(),
+            &my_file.open_decrypted(&key).unwrap()[..]
+        );
+
+        let my_loaded_file = FileData::from_b64(my_file.to_b64().unwrap()).unwrap();
+        assert!(my_loaded_file.compressed());
+        assert_eq!(
+            content.as_bytes(),
+            &my_loaded_file.open_decrypted(&key).unwrap()[..]
+        );
+
+        // Compression is kept when the file is re-encrypted.
+        my_file.reencrypt(&key).unwrap();
+        assert_eq!(
+            content.as_bytes(),
+            &my_file.open_decrypted(&key).unwrap()[..]
+        );
+        FileData::encrypt_write_with_nonce(
+            test_file,
+            b"short",
+            &key,
+            my_file.content_nonce(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(b"short", &my_file.open_decrypted(&key).unwrap()[..]);
+        cleanup_test_file(test_file);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let test_file = "test_files/testfile9.txt";
+        let key = encrypted::new_key(None);
+        let my_file = FileData::new_with_content_and_key(
+            TEST_USERNAME,
+            &key,
+            OsString::from("testfile9.txt"),
+            TEST_CONTENT.as_bytes(),
+            test_file,
+        )
+        .unwrap();
+
+        let json = serde_json::to_string(&my_file).unwrap();
+        assert!(json.contains(&helpers::bytes_to_b64(my_file.content_nonce())));
+        let loaded: FileData = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::to_string(&loaded).unwrap());
+        assert_eq!(Some("txt"), loaded.extension());
+        assert_eq!(
+            TEST_CONTENT.as_bytes(),
+            &loaded.open_decrypted(&key).unwrap()[..]
+        );
+        cleanup_test_file(test_file);
+    }
+
+    #[test]
+    fn test_display() {
+        let test_file = "test_files/testfile10.md";
+        let key = encrypted::new_key(None);
+        let my_file = FileData::new_with_key(
+            TEST_USERNAME,
+            &key,
+            OsString::from("testfile10.md"),
+            test_file,
+        )
+        .unwrap();
+        assert_eq!(
+            format!(
+                "FileData {{ name: \"testfile10.md\", owner: \"{TEST_USERNAME}\", \
+                path: \"{test_file}\", size: {} bytes }}",
+                encrypted::TAG_LENGTH
+            ),
+            my_file.to_string()
+        );
+        cleanup_test_file(test_file);
+    }
+
+    #[test]
+    fn test_reencrypt() {
+        let test_file = "test_files/testfile5";
+        let test_name = "testfile5";
+        let my_account = Account::new(TEST_USERNAME, TEST_PASSWORD).unwrap();
+        let unlocked = my_account.unlock(TEST_PASSWORD).unwrap();
+        let mut my_file = FileData::new_with_content(
+            &my_account,
+            TEST_PASSWORD,
+            OsString::from(test_name),
+            TEST_CONTENT.as_bytes(),
+            test_file,
+        )
+        .unwrap();
+        let old_nonce = *my_file.content_nonce();
+
+        my_file.reencrypt(unlocked.key()).unwrap();
+        assert_ne!(&old_nonce, my_file.content_nonce());
+        let content = my_file.open_decrypted(unlocked.key()).unwrap();
+        assert_eq!(TEST_CONTENT.as_bytes(), *content);
+        cleanup_test_file(test_file);
+    }
+
+    #[test]
+    fn test_stream_threshold() {
+        let test_file = "test_files/testfile11";
+        let test_name = "testfile11";
+        let key = encrypted::new_key(None);
+        let content: Vec<u8> = (0..config::DEFAULT_STREAM_THRESHOLD_BYTES)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let mut my_file = FileData::new_with_content_and_key(
+            TEST_USERNAME,
+            &key,
+            OsString::from(test_name),
+            &content,
+            test_file,
+        )
+        .unwrap();
+        let encrypted_bytes = fs::read(test_file).unwrap();
+        assert_eq!(STREAM_MAGIC, &encrypted_bytes[..STREAM_MAGIC.len()]);
+        assert_eq!(my_file.file_size(), encrypted_bytes.len() as u64);
+        assert_eq!(content, *my_file.open_decrypted(&key).unwrap());
+
+        my_file.reencrypt(&key).unwrap();
+        assert_eq!(content, *my_file.open_decrypted(&key).unwrap());
+        assert!(my_file.open_decrypted(&encrypted::new_key(None)).is_err());
+
+        // Smaller content is encrypted all at once.
+        FileData::encrypt_write_with_nonce(
+            test_file,
+            &content[1..],
+            &key,
+            my_file.content_nonce(),
+            false,
+        )
+        .unwrap();
+        let encrypted_bytes = fs::read(test_file).unwrap();
+        assert_eq!(content.len() - 1 + TAG_LENGTH, encrypted_bytes.len());
+        assert_eq!(&content[1..], &my_file.open_decrypted(&key).unwrap()[..]);
+        cleanup_test_file(test_file);
+    }
+
+    #[test]
+    fn test_overwrite_shorter() {
+        let test_file = "test_files/testfile6";
+        let test_name = "testfile6";
+        let my_account = Account::new(TEST_USERNAME, TEST_PASSWORD).unwrap();
+        let unlocked = my_account.unlock(TEST_PASSWORD).unwrap();
+        let my_file = FileData::new_with_content(
+            &my_account,
+            TEST_PASSWORD,
+            OsString::from(test_name),
+            TEST_CONTENT.as_bytes(),
+            test_file,
+        )
+        .unwrap();
+
+        FileData::encrypt_write_with_nonce(
+            test_file,
+            b"short",
+            unlocked.key(),
+            my_file.content_nonce(),
+            false,
+        )
+        .unwrap();
+        let content = my_file.open_decrypted(unlocked.key()).unwrap();
+        assert_eq!(b"short", &content[..]);
+        cleanup_test_file(test_file);
+    }
+
     #[test]
     fn test_already_exists() {
         let test_file = "test_files/testfile3";
@@ -438,4 +930,16 @@ Don't tell anybody!!!!";
         my_file.open_decrypted(other_unlocked.key()).unwrap_err();
         cleanup_test_file(test_file);
     }
+
+    #[test]
+    fn test_wiped_temp_file() {
+        let temp_file = WipedTempFile::new(OsStr::new("notes.md"), b"secret notes").unwrap();
+        let path = temp_file.path();
+        assert_eq!(path.file_name().unwrap(), "notes.md");
+        assert_eq!(std::fs::read(&path).unwrap(), b"secret notes");
+
+        drop(temp_file);
+        assert!(!path.exists());
+        assert!(!path.parent().unwrap().exists());
+    }
 }
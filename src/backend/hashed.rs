@@ -4,14 +4,19 @@ use rand_chacha::{
     rand_core::{RngCore, SeedableRng},
     ChaCha20Rng,
 };
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 
 use crate::{error::Error, helpers};
 
 /// 32 bytes hashed and salted using PBKDF2-HMAC-SHA256 and 64-byte salt.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Hashed {
+    #[cfg_attr(feature = "serde", serde(with = "helpers::serde_b64"))]
     hash: [u8; 32],
+    #[cfg_attr(feature = "serde", serde(with = "helpers::serde_b64"))]
     salt: [u8; 64],
 }
 impl Hashed {
@@ -0,0 +1,155 @@
+//! Functionality related to the history of the content of stored passwords.
+use chrono::{DateTime, Utc};
+
+use crate::{
+    backend::{
+        encrypted::{Encrypted, KeyMaterial},
+        password::Password,
+    },
+    error::Error,
+    helpers,
+};
+
+/// The number of old contents kept for each stored password. Older entries are deleted
+/// automatically.
+pub const MAX_PASSWORD_HISTORY: usize = 20;
+
+/// A former content of a stored [Password], still encrypted exactly as it was stored, alongside
+/// the time at which it was replaced.
+#[derive(Debug)]
+pub struct PasswordHistoryEntry {
+    owner_username: String,
+    password_name_ciphertext: Vec<u8>,
+    encrypted_content: Encrypted,
+    changed_at: DateTime<Utc>,
+}
+impl PasswordHistoryEntry {
+    /// Record the current content of the given [Password], which is about to be replaced at
+    /// the given time.
+    pub fn new(password: &Password, changed_at: DateTime<Utc>) -> Self {
+        Self {
+            owner_username: password.owner_username().to_owned(),
+            password_name_ciphertext: password.encrypted_name().ciphertext().to_vec(),
+            encrypted_content: password.encrypted_content().clone(),
+            changed_at,
+        }
+    }
+
+    /// Decrypt the old content of the password.
+    pub fn decrypt(&self, key: &KeyMaterial) -> Result<String, Error> {
+        helpers::bytes_to_utf8(&self.encrypted_content.decrypt(key)?, "history_content")
+    }
+
+    /// Load a [PasswordHistoryEntry] from a [Base64PasswordHistoryEntry]— a set of
+    /// base-64-encoded strings.
+    pub fn from_b64(b64_entry: Base64PasswordHistoryEntry) -> Result<Self, Error> {
+        let owner_username = helpers::bytes_to_utf8(
+            &helpers::b64_to_bytes(&b64_entry.b64_owner_username)?,
+            "owner_username",
+        )?;
+
+        Ok(Self {
+            owner_username,
+            password_name_ciphertext: helpers::b64_to_bytes(
+                &b64_entry.b64_password_name_ciphertext,
+            )?,
+            encrypted_content: Encrypted::from_b64_parts(
+                &b64_entry.b64_content_ciphertext,
+                &b64_entry.b64_content_nonce,
+            )?,
+            changed_at: helpers::timestamp_to_datetime(b64_entry.changed_at)?,
+        })
+    }
+
+    /// Convert this [PasswordHistoryEntry] to a [Base64PasswordHistoryEntry] for storage.
+    pub fn to_b64(&self) -> Base64PasswordHistoryEntry {
+        Base64PasswordHistoryEntry {
+            b64_owner_username: helpers::bytes_to_b64(self.owner_username.as_bytes()),
+            b64_password_name_ciphertext: helpers::bytes_to_b64(&self.password_name_ciphertext),
+            b64_content_ciphertext: self.encrypted_content.ciphertext_as_b64(),
+            b64_content_nonce: self.encrypted_content.nonce_as_b64(),
+            changed_at: self.changed_at.timestamp(),
+        }
+    }
+
+    // GETTERS
+
+    /// Return the username of the account owning the password.
+    pub fn owner_username(&self) -> &str {
+        &self.owner_username
+    }
+
+    /// Return the name ciphertext of the [Password] this [PasswordHistoryEntry] belonged to when
+    /// it was recorded.
+    pub fn password_name_ciphertext(&self) -> &[u8] {
+        &self.password_name_ciphertext
+    }
+
+    /// Return the encrypted old content of the password.
+    pub fn encrypted_content(&self) -> &Encrypted {
+        &self.encrypted_content
+    }
+
+    /// Return the time at which the content was replaced.
+    pub fn changed_at(&self) -> DateTime<Utc> {
+        self.changed_at
+    }
+}
+
+/// A [PasswordHistoryEntry] converted for base-64 storage.
+#[derive(Debug)]
+pub struct Base64PasswordHistoryEntry {
+    /// Owner username in base-64 format.
+    pub b64_owner_username: String,
+    /// Name ciphertext of the associated password in base-64 format.
+    pub b64_password_name_ciphertext: String,
+    /// Old content ciphertext in base-64 format.
+    pub b64_content_ciphertext: String,
+    /// Old content nonce in base-64 format.
+    pub b64_content_nonce: String,
+    /// Time at which the content was replaced as a Unix timestamp.
+    pub changed_at: i64,
+}
+impl Base64PasswordHistoryEntry {
+    /// Output fields as tuple.
+    pub fn as_tuple(&self) -> (&str, &str, &str, &str, i64) {
+        (
+            &self.b64_owner_username,
+            &self.b64_password_name_ciphertext,
+            &self.b64_content_ciphertext,
+            &self.b64_content_nonce,
+            self.changed_at,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{account::Account, password::PasswordChanges};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_to_from_b64() {
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let key = my_account.unlock("my_password").unwrap().key().clone();
+        let mut my_password =
+            Password::new(&my_account, "my_password", "server", "root", "hunter2", "").unwrap();
+
+        let changed_at = helpers::timestamp_to_datetime(1_700_000_000).unwrap();
+        let entry = PasswordHistoryEntry::new(&my_password, changed_at);
+        my_password
+            .apply_changes(&key, &PasswordChanges::new().new_content("hunter3"))
+            .unwrap();
+        assert_eq!("hunter2", entry.decrypt(&key).unwrap());
+
+        let loaded = PasswordHistoryEntry::from_b64(entry.to_b64()).unwrap();
+        assert_eq!("my_account", loaded.owner_username());
+        assert_eq!(
+            my_password.encrypted_name().ciphertext(),
+            loaded.password_name_ciphertext()
+        );
+        assert_eq!(changed_at, loaded.changed_at());
+        assert_eq!("hunter2", loaded.decrypt(&key).unwrap());
+    }
+}
@@ -0,0 +1,208 @@
+//! Parsing of unencrypted KeePass 2.x XML exports.
+use roxmltree::{Document, Node};
+use zeroize::Zeroize;
+
+use crate::error::Error;
+
+/// A single entry of a KeePass database.
+#[derive(Debug, Default, PartialEq)]
+pub struct KeePassEntry {
+    /// Names of the groups containing this entry, outermost first. The root group is left out.
+    pub group_path: Vec<String>,
+    /// The `Title` field.
+    pub title: String,
+    /// The `UserName` field.
+    pub username: String,
+    /// The `Password` field.
+    pub password: String,
+    /// The `URL` field.
+    pub url: String,
+    /// The `Notes` field.
+    pub notes: String,
+}
+impl KeePassEntry {
+    /// Return the group path of this entry joined by slashes, for use as a password category.
+    /// Entries of the root group have an empty category.
+    pub fn category(&self) -> String {
+        self.group_path.join("/")
+    }
+}
+impl Drop for KeePassEntry {
+    fn drop(&mut self) {
+        self.username.zeroize();
+        self.password.zeroize();
+        self.notes.zeroize();
+    }
+}
+
+/// Parse the entries of a KeePass 2.x XML export. Entry history and the contents of the recycle
+/// bin are left out.
+pub fn parse_keepass_xml(xml: &str) -> Result<Vec<KeePassEntry>, Error> {
+    let document = Document::parse(xml)
+        .map_err(|err| Error::UnsupportedFormatError(format!("Invalid KeePass XML: {err}")))?;
+    let keepass_file = document.root_element();
+    if !keepass_file.has_tag_name("KeePassFile") {
+        return Err(Error::UnsupportedFormatError(
+            "Not a KeePass XML export.".to_owned(),
+        ));
+    }
+
+    let recycle_bin_uuid = child_element(keepass_file, "Meta")
+        .and_then(|meta| child_element(meta, "RecycleBinUUID"))
+        .and_then(|uuid| uuid.text());
+    let root_group = child_element(keepass_file, "Root")
+        .and_then(|root| child_element(root, "Group"))
+        .ok_or_else(|| {
+            Error::UnsupportedFormatError("KeePass XML export has no root group.".to_owned())
+        })?;
+
+    let mut entries = vec![];
+    collect_entries(root_group, &mut vec![], recycle_bin_uuid, &mut entries);
+    Ok(entries)
+}
+
+// Add the entries of the given group and all of its subgroups.
+fn collect_entries(
+    group: Node,
+    group_path: &mut Vec<String>,
+    recycle_bin_uuid: Option<&str>,
+    entries: &mut Vec<KeePassEntry>,
+) {
+    for child in group.children().filter(Node::is_element) {
+        match child.tag_name().name() {
+            "Entry" => entries.push(parse_entry(child, group_path)),
+            "Group" => {
+                let uuid = child_element(child, "UUID").and_then(|uuid| uuid.text());
+                if uuid.is_some() && uuid == recycle_bin_uuid {
+                    continue;
+                }
+                let name = child_element(child, "Name")
+                    .and_then(|name| name.text())
+                    .unwrap_or_default();
+                group_path.push(name.to_owned());
+                collect_entries(child, group_path, recycle_bin_uuid, entries);
+                group_path.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_entry(entry: Node, group_path: &[String]) -> KeePassEntry {
+    let mut keepass_entry = KeePassEntry::default();
+    keepass_entry.group_path.extend_from_slice(group_path);
+    for string in entry
+        .children()
+        .filter(|child| child.has_tag_name("String"))
+    {
+        let key = child_element(string, "Key").and_then(|key| key.text());
+        let value = child_element(string, "Value")
+            .and_then(|value| value.text())
+            .unwrap_or_default()
+            .to_owned();
+        match key {
+            Some("Title") => keepass_entry.title = value,
+            Some("UserName") => keepass_entry.username = value,
+            Some("Password") => keepass_entry.password = value,
+            Some("URL") => keepass_entry.url = value,
+            Some("Notes") => keepass_entry.notes = value,
+            _ => {}
+        }
+    }
+    keepass_entry
+}
+
+fn child_element<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const TEST_XML: &str = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<KeePassFile>
+    <Meta>
+        <RecycleBinUUID>YmluYmluYmluYmluYmluYg==</RecycleBinUUID>
+    </Meta>
+    <Root>
+        <Group>
+            <UUID>cm9vdHJvb3Ryb290cm9vdA==</UUID>
+            <Name>Database</Name>
+            <Entry>
+                <String><Key>Title</Key><Value>Router</Value></String>
+                <String><Key>UserName</Key><Value>admin</Value></String>
+                <String><Key>Password</Key><Value Protected="True">hunter2</Value></String>
+            </Entry>
+            <Group>
+                <UUID>ZW1haWxlbWFpbGVtYWlsZQ==</UUID>
+                <Name>Email</Name>
+                <Entry>
+                    <String><Key>Notes</Key><Value>Work &amp; personal</Value></String>
+                    <String><Key>Password</Key><Value Protected="True">new_password</Value></String>
+                    <String><Key>Title</Key><Value>Mail</Value></String>
+                    <String><Key>URL</Key><Value>https://mail.example.com</Value></String>
+                    <String><Key>UserName</Key><Value>me@example.com</Value></String>
+                    <History>
+                        <Entry>
+                            <String><Key>Title</Key><Value>Mail</Value></String>
+                            <String><Key>Password</Key><Value>old_password</Value></String>
+                        </Entry>
+                    </History>
+                </Entry>
+            </Group>
+            <Group>
+                <UUID>YmluYmluYmluYmluYmluYg==</UUID>
+                <Name>Recycle Bin</Name>
+                <Entry>
+                    <String><Key>Title</Key><Value>Deleted</Value></String>
+                </Entry>
+            </Group>
+        </Group>
+    </Root>
+</KeePassFile>"#;
+
+    #[test]
+    fn test_parse_keepass_xml() {
+        let entries = parse_keepass_xml(TEST_XML).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                KeePassEntry {
+                    group_path: vec![],
+                    title: String::from("Router"),
+                    username: String::from("admin"),
+                    password: String::from("hunter2"),
+                    url: String::new(),
+                    notes: String::new(),
+                },
+                KeePassEntry {
+                    group_path: vec![String::from("Email")],
+                    title: String::from("Mail"),
+                    username: String::from("me@example.com"),
+                    password: String::from("new_password"),
+                    url: String::from("https://mail.example.com"),
+                    notes: String::from("Work & personal"),
+                },
+            ]
+        );
+        assert_eq!(entries[0].category(), "");
+        assert_eq!(entries[1].category(), "Email");
+    }
+
+    #[test]
+    fn test_invalid_keepass_xml() {
+        for bad_xml in [
+            "",
+            "<KeePassFile>",
+            "<Other/>",
+            "<KeePassFile><Root/></KeePassFile>",
+        ] {
+            if let Err(Error::UnsupportedFormatError(_)) = parse_keepass_xml(bad_xml) {
+            } else {
+                panic!("Expected UnsupportedFormatError for {bad_xml:?}");
+            }
+        }
+    }
+}
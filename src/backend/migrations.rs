@@ -0,0 +1,173 @@
+//! Migrations between versions of the `dgruft` database schema.
+use std::{ffi::OsStr, fs};
+
+use rusqlite::Connection;
+
+use crate::{
+    backend::{file, sql_schemas::*, sql_statements::*},
+    helpers,
+};
+
+/// The schema version this build of `dgruft` creates and expects.
+///
+/// 1. The schema before versioning was introduced.
+/// 2. Added the `kdf_params` column to `user_credentials`.
+/// 3. Added the `encrypted_url` and `url_nonce` columns to `passwords`.
+/// 4. Added the `category` column to `passwords`.
+/// 5. Added the `expires_at` column to `passwords`.
+/// 6. Added the `created_at` and `updated_at` columns to `passwords`.
+/// 7. Added the `created_at` and `last_login` columns to `user_credentials`.
+/// 8. Added the `file_size` column to `files`.
+/// 9. Added the `extension` column to `files`.
+/// 10. Added the `compressed` column to `files`.
+/// 11. Added the `encrypted_totp_secret` and `totp_secret_nonce` columns to `passwords`.
+pub const SCHEMA_VERSION: u32 = 11;
+
+/// Migrate the database schema from version `from` to version `to`, then record the new version.
+/// Either every migration is applied or none are. Nothing is written if the database is already
+/// at version `to`.
+pub fn run_migrations(conn: &Connection, from: u32, to: u32) -> rusqlite::Result<()> {
+    if from >= to {
+        return Ok(());
+    }
+    let transaction = conn.unchecked_transaction()?;
+    for version in from..to {
+        match version {
+            1 => migrate_v1_to_v2(&transaction)?,
+            2 => migrate_v2_to_v3(&transaction)?,
+            3 => migrate_v3_to_v4(&transaction)?,
+            4 => migrate_v4_to_v5(&transaction)?,
+            5 => migrate_v5_to_v6(&transaction)?,
+            6 => migrate_v6_to_v7(&transaction)?,
+            7 => migrate_v7_to_v8(&transaction)?,
+            8 => migrate_v8_to_v9(&transaction)?,
+            9 => migrate_v9_to_v10(&transaction)?,
+            10 => migrate_v10_to_v11(&transaction)?,
+            _ => unreachable!("no migration from schema version {version}"),
+        }
+    }
+    transaction.execute(SET_SCHEMA_VERSION, [to])?;
+    transaction.commit()
+}
+
+fn migrate_v1_to_v2(conn: &Connection) -> rusqlite::Result<()> {
+    // Development builds added the column before the schema was versioned.
+    if !conn.prepare(USER_CREDENTIALS_HAS_KDF_PARAMS)?.exists([])? {
+        conn.execute(ADD_USER_CREDENTIALS_KDF_PARAMS, ())?;
+    }
+    Ok(())
+}
+
+fn migrate_v2_to_v3(conn: &Connection) -> rusqlite::Result<()> {
+    // The table is created with the columns if it didn't exist before connecting.
+    if !conn.prepare(PASSWORDS_HAS_URL)?.exists([])? {
+        conn.execute(ADD_PASSWORDS_URL, ())?;
+        conn.execute(ADD_PASSWORDS_URL_NONCE, ())?;
+    }
+    Ok(())
+}
+
+fn migrate_v3_to_v4(conn: &Connection) -> rusqlite::Result<()> {
+    // The table is created with the column if it didn't exist before connecting.
+    if !conn.prepare(PASSWORDS_HAS_CATEGORY)?.exists([])? {
+        conn.execute(ADD_PASSWORDS_CATEGORY, ())?;
+    }
+    Ok(())
+}
+
+fn migrate_v4_to_v5(conn: &Connection) -> rusqlite::Result<()> {
+    // The table is created with the column if it didn't exist before connecting.
+    if !conn.prepare(PASSWORDS_HAS_EXPIRES_AT)?.exists([])? {
+        conn.execute(ADD_PASSWORDS_EXPIRES_AT, ())?;
+    }
+    Ok(())
+}
+
+fn migrate_v5_to_v6(conn: &Connection) -> rusqlite::Result<()> {
+    // The table is created with the columns if it didn't exist before connecting.
+    if !conn.prepare(PASSWORDS_HAS_CREATED_AT)?.exists([])? {
+        conn.execute(ADD_PASSWORDS_CREATED_AT, ())?;
+        conn.execute(ADD_PASSWORDS_UPDATED_AT, ())?;
+        // There is no record of when existing passwords were created, so date them to now.
+        conn.execute(BACKFILL_PASSWORDS_TIMESTAMPS, [helpers::unix_timestamp()])?;
+    }
+    Ok(())
+}
+
+fn migrate_v6_to_v7(conn: &Connection) -> rusqlite::Result<()> {
+    // The table is created with the columns if it didn't exist before connecting.
+    if !conn.prepare(USER_CREDENTIALS_HAS_CREATED_AT)?.exists([])? {
+        conn.execute(ADD_USER_CREDENTIALS_CREATED_AT, ())?;
+        conn.execute(ADD_USER_CREDENTIALS_LAST_LOGIN, ())?;
+        // There is no record of when existing accounts were created, so date them to now.
+        conn.execute(
+            BACKFILL_USER_CREDENTIALS_CREATED_AT,
+            [helpers::unix_timestamp()],
+        )?;
+    }
+    Ok(())
+}
+
+fn migrate_v7_to_v8(conn: &Connection) -> rusqlite::Result<()> {
+    // The table is created with the column if it didn't exist before connecting.
+    if conn.prepare(FILES_HAS_FILE_SIZE)?.exists([])? {
+        return Ok(());
+    }
+    conn.execute(ADD_FILES_FILE_SIZE, ())?;
+
+    // Read the size of each encrypted file from disk. Files which can't be found count as empty.
+    let b64_paths = conn
+        .prepare(GET_ALL_FILE_PATHS)?
+        .query_map([], |row| row.get::<usize, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    for b64_path in b64_paths {
+        let file_size = helpers::b64_to_bytes(&b64_path)
+            .ok()
+            .and_then(|path| String::from_utf8(path).ok())
+            .and_then(|path| fs::metadata(path).ok())
+            .map_or(0, |metadata| metadata.len());
+        conn.execute(SET_FILE_SIZE, (file_size as i64, &b64_path))?;
+    }
+    Ok(())
+}
+
+fn migrate_v8_to_v9(conn: &Connection) -> rusqlite::Result<()> {
+    // The table is created with the column if it didn't exist before connecting.
+    if conn.prepare(FILES_HAS_EXTENSION)?.exists([])? {
+        return Ok(());
+    }
+    conn.execute(ADD_FILES_EXTENSION, ())?;
+
+    // Derive the extension of each file from its name, like new files do.
+    let b64_names = conn
+        .prepare(GET_ALL_FILE_NAMES)?
+        .query_map([], |row| {
+            Ok((row.get::<usize, String>(0)?, row.get::<usize, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+    for (b64_path, b64_name) in b64_names {
+        let extension = helpers::b64_to_bytes(&b64_name)
+            .ok()
+            .and_then(|name| String::from_utf8(name).ok())
+            .and_then(|name| file::extension_of(OsStr::new(&name)));
+        conn.execute(SET_FILE_EXTENSION, (extension, &b64_path))?;
+    }
+    Ok(())
+}
+
+fn migrate_v9_to_v10(conn: &Connection) -> rusqlite::Result<()> {
+    // The table is created with the column if it didn't exist before connecting.
+    if !conn.prepare(FILES_HAS_COMPRESSED)?.exists([])? {
+        conn.execute(ADD_FILES_COMPRESSED, ())?;
+    }
+    Ok(())
+}
+
+fn migrate_v10_to_v11(conn: &Connection) -> rusqlite::Result<()> {
+    // The table is created with the columns if it didn't exist before connecting.
+    if !conn.prepare(PASSWORDS_HAS_TOTP_SECRET)?.exists([])? {
+        conn.execute(ADD_PASSWORDS_TOTP_SECRET, ())?;
+        conn.execute(ADD_PASSWORDS_TOTP_SECRET_NONCE, ())?;
+    }
+    Ok(())
+}
@@ -1,21 +1,68 @@
 //! Functionality related to reading and writing encrypted stored passwords.
 //!
 //! These are *stored passwords*, *not* passwords for `dgruft` accounts.
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use hmac::{Hmac, Mac};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use zeroize::Zeroizing;
+
 use crate::helpers;
 use crate::{
-    backend::{account::Account, encrypted::Encrypted},
+    backend::{
+        account::Account,
+        encrypted::{Encrypted, KeyMaterial},
+    },
     error::Error,
 };
 
+/// Passwords which expire within this many days are about to expire.
+pub const EXPIRY_WARNING_DAYS: i64 = 30;
+
+/// Number of seconds for which a TOTP code is valid.
+pub const TOTP_STEP_SECS: u64 = 30;
+
+/// Number of digits of a TOTP code.
+pub const TOTP_DIGITS: u32 = 6;
+
+/// Whether a [Password] has expired. See [Password::expiry_status].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpiryStatus {
+    /// The password has no expiry date, or it is more than [EXPIRY_WARNING_DAYS] days away.
+    Valid,
+    /// The password expires within [EXPIRY_WARNING_DAYS] days.
+    ExpiringSoon,
+    /// The password's expiry date has passed.
+    Expired,
+}
+
 /// A password with an associated owner dgruft account, a username associated with that password, a
-/// name associated with this login info in the dgruft interface, and some personal notes.
+/// name associated with this login info in the dgruft interface, some personal notes, and
+/// optionally the URL of the site it is for and a TOTP secret to generate one-time codes with.
+///
+/// Passwords can be organized into categories, e.g. `banking` or `work`. Category names aren't
+/// secret, so they are stored in plain text and can be searched without decrypting anything. The
+/// same goes for the date after which the password should be changed, and for when the password
+/// was created and last changed.
+///
+/// With the `serde` feature, a [Password] is serialized with all of its fields still encrypted.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Password {
     owner_username: String,
     encrypted_name: Encrypted,
     encrypted_username: Encrypted,
     encrypted_content: Encrypted,
     encrypted_notes: Encrypted,
+    encrypted_url: Option<Encrypted>,
+    encrypted_totp_secret: Option<Encrypted>,
+    category: Option<String>,
+    expires_at: Option<NaiveDate>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
 }
 impl Password {
     /// Create a new [Password].
@@ -28,17 +75,44 @@ impl Password {
         notes: &str,
     ) -> Result<Self, Error> {
         let account_fields = account.unlock(account_password)?;
-        let owner_username = account_fields.username().to_owned();
-        let encrypted_name = Encrypted::new(name.as_bytes(), account_fields.key())?;
-        let encrypted_username = Encrypted::new(username.as_bytes(), account_fields.key())?;
-        let encrypted_content = Encrypted::new(password.as_bytes(), account_fields.key())?;
-        let encrypted_notes = Encrypted::new(notes.as_bytes(), account_fields.key())?;
+        Self::new_with_key(
+            account_fields.username(),
+            account_fields.key(),
+            name,
+            username,
+            password,
+            notes,
+        )
+    }
+
+    /// Create a new [Password] owned by the given account using the account's key. The new
+    /// [Password] has no URL, TOTP secret, category, or expiry date; use
+    /// [Password::apply_changes] to set them.
+    pub fn new_with_key(
+        owner_username: &str,
+        key: &KeyMaterial,
+        name: &str,
+        username: &str,
+        password: &str,
+        notes: &str,
+    ) -> Result<Self, Error> {
+        let encrypted_name = Encrypted::new(name.as_bytes(), key)?;
+        let encrypted_username = Encrypted::new(username.as_bytes(), key)?;
+        let encrypted_content = Encrypted::new(password.as_bytes(), key)?;
+        let encrypted_notes = Encrypted::new(notes.as_bytes(), key)?;
+        let now = Utc::now();
         Ok(Self {
-            owner_username,
+            owner_username: owner_username.to_owned(),
             encrypted_name,
             encrypted_username,
             encrypted_content,
             encrypted_notes,
+            encrypted_url: None,
+            encrypted_totp_secret: None,
+            category: None,
+            expires_at: None,
+            created_at: now,
+            updated_at: now,
         })
     }
 
@@ -48,22 +122,46 @@ impl Password {
             &helpers::b64_to_bytes(&b64_password.b64_owner_username)?,
             "owner_username",
         )?;
-        let encrypted_name = Encrypted::from_b64(
+        let encrypted_name = Encrypted::from_b64_parts(
             &b64_password.b64_name_ciphertext,
             &b64_password.b64_name_nonce,
         )?;
-        let encrypted_username = Encrypted::from_b64(
+        let encrypted_username = Encrypted::from_b64_parts(
             &b64_password.b64_username_ciphertext,
             &b64_password.b64_username_nonce,
         )?;
-        let encrypted_content = Encrypted::from_b64(
+        let encrypted_content = Encrypted::from_b64_parts(
             &b64_password.b64_content_ciphertext,
             &b64_password.b64_content_nonce,
         )?;
-        let encrypted_notes = Encrypted::from_b64(
+        let encrypted_notes = Encrypted::from_b64_parts(
             &b64_password.b64_notes_ciphertext,
             &b64_password.b64_notes_nonce,
         )?;
+        let encrypted_url = match (
+            &b64_password.b64_url_ciphertext,
+            &b64_password.b64_url_nonce,
+        ) {
+            (Some(b64_ciphertext), Some(b64_nonce)) => {
+                Some(Encrypted::from_b64_parts(b64_ciphertext, b64_nonce)?)
+            }
+            _ => None,
+        };
+        let encrypted_totp_secret = match (
+            &b64_password.b64_totp_secret_ciphertext,
+            &b64_password.b64_totp_secret_nonce,
+        ) {
+            (Some(b64_ciphertext), Some(b64_nonce)) => {
+                Some(Encrypted::from_b64_parts(b64_ciphertext, b64_nonce)?)
+            }
+            _ => None,
+        };
+        let expires_at = match &b64_password.expires_at {
+            Some(expires_at) => parse_expiry_date(expires_at)?,
+            None => None,
+        };
+        let created_at = helpers::timestamp_to_datetime(b64_password.created_at)?;
+        let updated_at = helpers::timestamp_to_datetime(b64_password.updated_at)?;
 
         Ok(Self {
             owner_username,
@@ -71,6 +169,12 @@ impl Password {
             encrypted_username,
             encrypted_content,
             encrypted_notes,
+            encrypted_url,
+            encrypted_totp_secret,
+            category: b64_password.category,
+            expires_at,
+            created_at,
+            updated_at,
         })
     }
 
@@ -86,9 +190,78 @@ impl Password {
             b64_username_nonce: self.encrypted_username().nonce_as_b64(),
             b64_content_nonce: self.encrypted_content().nonce_as_b64(),
             b64_notes_nonce: self.encrypted_notes().nonce_as_b64(),
+            b64_url_ciphertext: self.encrypted_url().map(Encrypted::ciphertext_as_b64),
+            b64_url_nonce: self.encrypted_url().map(Encrypted::nonce_as_b64),
+            b64_totp_secret_ciphertext: self
+                .encrypted_totp_secret()
+                .map(Encrypted::ciphertext_as_b64),
+            b64_totp_secret_nonce: self.encrypted_totp_secret().map(Encrypted::nonce_as_b64),
+            category: self.category.clone(),
+            expires_at: self.expires_at.map(|expires_at| expires_at.to_string()),
+            created_at: self.created_at.timestamp(),
+            updated_at: self.updated_at.timestamp(),
         }
     }
 
+    /// Re-encrypt every field of this [Password] with fresh nonces.
+    pub fn reencrypt(&mut self, key: &KeyMaterial) -> Result<(), Error> {
+        self.encrypted_name = self.encrypted_name.reencrypt(key)?;
+        self.encrypted_username = self.encrypted_username.reencrypt(key)?;
+        self.encrypted_content = self.encrypted_content.reencrypt(key)?;
+        self.encrypted_notes = self.encrypted_notes.reencrypt(key)?;
+        if let Some(encrypted_url) = &self.encrypted_url {
+            self.encrypted_url = Some(encrypted_url.reencrypt(key)?);
+        }
+        if let Some(encrypted_totp_secret) = &self.encrypted_totp_secret {
+            self.encrypted_totp_secret = Some(encrypted_totp_secret.reencrypt(key)?);
+        }
+        Ok(())
+    }
+
+    /// Replace the fields of this [Password] which are set in the given [PasswordChanges]. Each
+    /// replaced field is encrypted with a fresh nonce; the other fields are left untouched. An
+    /// empty URL removes the URL, an empty TOTP secret removes the TOTP secret, and an empty
+    /// category removes the category. Unless the changes
+    /// are empty, the [Password] is marked as updated now.
+    pub fn apply_changes(
+        &mut self,
+        key: &KeyMaterial,
+        changes: &PasswordChanges,
+    ) -> Result<(), Error> {
+        if let Some(name) = &changes.name {
+            self.encrypted_name = Encrypted::new(name.as_bytes(), key)?;
+        }
+        if let Some(username) = &changes.username {
+            self.encrypted_username = Encrypted::new(username.as_bytes(), key)?;
+        }
+        if let Some(content) = &changes.content {
+            self.encrypted_content = Encrypted::new(content.as_bytes(), key)?;
+        }
+        if let Some(notes) = &changes.notes {
+            self.encrypted_notes = Encrypted::new(notes.as_bytes(), key)?;
+        }
+        match changes.url.as_deref() {
+            Some("") => self.encrypted_url = None,
+            Some(url) => self.encrypted_url = Some(Encrypted::new(url.as_bytes(), key)?),
+            None => {}
+        }
+        match changes.totp_secret.as_deref() {
+            Some([]) => self.encrypted_totp_secret = None,
+            Some(secret) => self.encrypted_totp_secret = Some(Encrypted::new(secret, key)?),
+            None => {}
+        }
+        if let Some(category) = &changes.category {
+            self.category = normalize_categories(category);
+        }
+        if let Some(expires_at) = changes.expires_at {
+            self.expires_at = expires_at;
+        }
+        if !changes.is_empty() {
+            self.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+
     // GETTERS
 
     /// Return the owner username of this [Password]. This is the `dgruft` username, *not* the
@@ -120,9 +293,92 @@ impl Password {
         &self.encrypted_notes
     }
 
+    /// Return the encrypted URL of this [Password], if it has one. This is the address of the site
+    /// the password is for.
+    pub fn encrypted_url(&self) -> Option<&Encrypted> {
+        self.encrypted_url.as_ref()
+    }
+
+    /// Return the encrypted TOTP secret of this [Password], if it has one. This is the shared
+    /// secret used to generate one-time codes for the site.
+    pub fn encrypted_totp_secret(&self) -> Option<&Encrypted> {
+        self.encrypted_totp_secret.as_ref()
+    }
+
+    /// Return the category of this [Password], if it has one. A password in multiple categories
+    /// has their names separated by commas; see [Password::categories].
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    /// Return the names of the categories this [Password] is in.
+    pub fn categories(&self) -> Vec<&str> {
+        match self.category() {
+            Some(category) => category.split(',').collect(),
+            None => vec![],
+        }
+    }
+
+    /// Return the date after which this [Password] should be changed, if it has one.
+    pub fn expires_at(&self) -> Option<NaiveDate> {
+        self.expires_at
+    }
+
+    /// Return when this [Password] was created.
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// Return when the fields of this [Password] were last changed.
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    /// Return whether this [Password] has expired or is about to expire on the given day.
+    pub fn expiry_status(&self, today: NaiveDate) -> ExpiryStatus {
+        match self.expires_at {
+            Some(expires_at) if expires_at < today => ExpiryStatus::Expired,
+            Some(expires_at) if (expires_at - today).num_days() <= EXPIRY_WARNING_DAYS => {
+                ExpiryStatus::ExpiringSoon
+            }
+            _ => ExpiryStatus::Valid,
+        }
+    }
+
+    /// Decrypt the URL of this [Password].
+    /// Return [`Ok<None>`] if it has no URL.
+    pub fn url(&self, key: &KeyMaterial) -> Result<Option<String>, Error> {
+        match self.encrypted_url() {
+            Some(encrypted_url) => Ok(Some(helpers::bytes_to_utf8(
+                &encrypted_url.decrypt(key)?,
+                "password_url",
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Decrypt the TOTP secret of this [Password].
+    /// Return [`Ok<None>`] if it has no TOTP secret.
+    pub fn totp_secret(&self, key: &KeyMaterial) -> Result<Option<Zeroizing<Vec<u8>>>, Error> {
+        match self.encrypted_totp_secret() {
+            Some(encrypted_totp_secret) => Ok(Some(encrypted_totp_secret.decrypt(key)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Generate the RFC 6238 TOTP code of this [Password] which is valid at the given Unix
+    /// timestamp, using HMAC-SHA1, [TOTP_STEP_SECS]-second steps, and [TOTP_DIGITS] digits.
+    /// Return [`Ok<None>`] if it has no TOTP secret.
+    pub fn generate_totp(&self, key: &KeyMaterial, time: u64) -> Result<Option<String>, Error> {
+        match self.totp_secret(key)? {
+            Some(secret) => Ok(Some(totp_code(&secret, time)?)),
+            None => Ok(None),
+        }
+    }
+
     /// Decrypt all fields of this [Password], including the secure ones. Use with caution and
     /// restraint!
-    pub fn unlock(&self, key: &[u8; 32]) -> Result<DecryptedPasswordFields, Error> {
+    pub fn unlock(&self, key: &KeyMaterial) -> Result<DecryptedPasswordFields, Error> {
         Ok(DecryptedPasswordFields {
             name: helpers::bytes_to_utf8(&self.encrypted_name().decrypt(key)?, "password_name")?,
             username: helpers::bytes_to_utf8(
@@ -134,9 +390,64 @@ impl Password {
                 "password_content",
             )?,
             notes: helpers::bytes_to_utf8(&self.encrypted_notes().decrypt(key)?, "password_notes")?,
+            url: self.url(key)?,
         })
     }
 }
+impl fmt::Display for Password {
+    // Nothing is decrypted; the name is shown as encrypted hex.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Password {{ owner: {:?}, name: {}, nonce: {} }}",
+            self.owner_username,
+            helpers::bytes_to_hex(self.encrypted_name.ciphertext()),
+            helpers::bytes_to_hex(self.encrypted_name.nonce())
+        )
+    }
+}
+
+/// A text field of a [Password].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasswordField {
+    /// The name of the password.
+    Name,
+    /// The username associated with the password.
+    Username,
+    /// The password content.
+    Content,
+    /// The notes associated with the password.
+    Notes,
+    /// The URL of the site the password is for.
+    Url,
+}
+impl PasswordField {
+    /// Return the [PasswordField] with the given name: `name`, `username`, `content` (or its
+    /// alias `password`), `notes`, or `url`.
+    /// Return [None] if there is no field with that name.
+    pub fn from_name(field_name: &str) -> Option<Self> {
+        match field_name {
+            "name" => Some(Self::Name),
+            "username" => Some(Self::Username),
+            "content" | "password" => Some(Self::Content),
+            "notes" => Some(Self::Notes),
+            "url" => Some(Self::Url),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for PasswordField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Name => "name",
+            Self::Username => "username",
+            Self::Content => "password",
+            Self::Notes => "notes",
+            Self::Url => "URL",
+        };
+        write!(f, "{name}")
+    }
+}
 
 /// All the decrypted fields of a [Password]. Use with caution and restraint.
 pub struct DecryptedPasswordFields {
@@ -144,6 +455,7 @@ pub struct DecryptedPasswordFields {
     username: String,
     content: String,
     notes: String,
+    url: Option<String>,
 }
 impl DecryptedPasswordFields {
     /// Return the name of this [DecryptedPasswordFields].
@@ -165,6 +477,129 @@ impl DecryptedPasswordFields {
     pub fn notes(&self) -> &str {
         &self.notes
     }
+
+    /// Return the URL of this [DecryptedPasswordFields], if it has one.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Return the given field of this [DecryptedPasswordFields]. A missing URL is returned as an
+    /// empty string.
+    pub fn get(&self, field: PasswordField) -> &str {
+        match field {
+            PasswordField::Name => self.name(),
+            PasswordField::Username => self.username(),
+            PasswordField::Content => self.content(),
+            PasswordField::Notes => self.notes(),
+            PasswordField::Url => self.url().unwrap_or_default(),
+        }
+    }
+
+    /// Return the field of this [DecryptedPasswordFields] with the given name; see
+    /// [PasswordField::from_name]. A missing URL is returned as an empty string.
+    /// Return [None] if there is no field with that name.
+    pub fn field(&self, field_name: &str) -> Option<&str> {
+        PasswordField::from_name(field_name).map(|field| self.get(field))
+    }
+}
+
+/// New values for some of the fields of a [Password]. Fields left as [None] are not changed.
+#[derive(Debug, Default)]
+pub struct PasswordChanges {
+    /// New name of the password.
+    pub name: Option<String>,
+    /// New username associated with the password.
+    pub username: Option<String>,
+    /// New password content.
+    pub content: Option<String>,
+    /// New notes associated with the password.
+    pub notes: Option<String>,
+    /// New URL associated with the password. An empty URL removes the URL.
+    pub url: Option<String>,
+    /// New TOTP secret of the password. An empty secret removes the TOTP secret.
+    pub totp_secret: Option<Vec<u8>>,
+    /// New category of the password. Multiple categories are separated by commas. An empty
+    /// category removes the category.
+    pub category: Option<String>,
+    /// New expiry date of the password. `Some(None)` removes the expiry date.
+    pub expires_at: Option<Option<NaiveDate>>,
+}
+impl PasswordChanges {
+    /// Create a new, empty [PasswordChanges].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a new name for the password.
+    pub fn new_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Set a new username for the password.
+    pub fn new_username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_owned());
+        self
+    }
+
+    /// Set new password content.
+    pub fn new_content(mut self, content: &str) -> Self {
+        self.content = Some(content.to_owned());
+        self
+    }
+
+    /// Set new notes for the password.
+    pub fn new_notes(mut self, notes: &str) -> Self {
+        self.notes = Some(notes.to_owned());
+        self
+    }
+
+    /// Set a new URL for the password, or remove its URL if the given URL is empty.
+    pub fn new_url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_owned());
+        self
+    }
+
+    /// Set a new TOTP secret for the password, or remove its TOTP secret if the given secret is
+    /// empty.
+    pub fn new_totp_secret(mut self, totp_secret: &[u8]) -> Self {
+        self.totp_secret = Some(totp_secret.to_vec());
+        self
+    }
+
+    /// Set a new category for the password, or remove its category if the given category is
+    /// empty. Multiple categories are separated by commas.
+    pub fn new_category(mut self, category: &str) -> Self {
+        self.category = Some(category.to_owned());
+        self
+    }
+
+    /// Set a new expiry date for the password, or remove its expiry date if [None] is given.
+    pub fn new_expires_at(mut self, expires_at: Option<NaiveDate>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Return true iff these [PasswordChanges] don't change anything.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.username.is_none()
+            && self.content.is_none()
+            && self.notes.is_none()
+            && self.url.is_none()
+            && self.totp_secret.is_none()
+            && self.category.is_none()
+            && self.expires_at.is_none()
+    }
+}
+
+/// A set of [PasswordChanges] to apply to the stored password with the given name.
+#[derive(Debug)]
+pub struct PasswordUpdate {
+    /// Current name of the password to change.
+    pub name: String,
+    /// Changes to apply to the password.
+    pub changes: PasswordChanges,
 }
 
 /// A [Password] converted for base-64 storage.
@@ -188,10 +623,47 @@ pub struct Base64Password {
     pub b64_content_nonce: String,
     /// Password notes nonce in base-64 format.
     pub b64_notes_nonce: String,
+    /// Password URL ciphertext in base-64 format, if the password has a URL.
+    pub b64_url_ciphertext: Option<String>,
+    /// Password URL nonce in base-64 format, if the password has a URL.
+    pub b64_url_nonce: Option<String>,
+    /// Password TOTP secret ciphertext in base-64 format, if the password has a TOTP secret.
+    pub b64_totp_secret_ciphertext: Option<String>,
+    /// Password TOTP secret nonce in base-64 format, if the password has a TOTP secret.
+    pub b64_totp_secret_nonce: Option<String>,
+    /// Password category in plain text, if the password has one.
+    pub category: Option<String>,
+    /// Password expiry date as an ISO 8601 `YYYY-MM-DD` string, if the password has one.
+    pub expires_at: Option<String>,
+    /// Password creation time as a Unix timestamp in seconds.
+    pub created_at: i64,
+    /// Password last change time as a Unix timestamp in seconds.
+    pub updated_at: i64,
 }
 impl Base64Password {
     /// Output fields as tuple.
-    pub fn as_tuple(&self) -> (&str, &str, &str, &str, &str, &str, &str, &str, &str) {
+    #[allow(clippy::type_complexity)]
+    pub fn as_tuple(
+        &self,
+    ) -> (
+        &str,
+        &str,
+        &str,
+        &str,
+        &str,
+        &str,
+        &str,
+        &str,
+        &str,
+        Option<&str>,
+        Option<&str>,
+        Option<&str>,
+        Option<&str>,
+        Option<&str>,
+        Option<&str>,
+        i64,
+        i64,
+    ) {
         (
             &self.b64_owner_username,
             &self.b64_name_ciphertext,
@@ -202,10 +674,77 @@ impl Base64Password {
             &self.b64_username_nonce,
             &self.b64_content_nonce,
             &self.b64_notes_nonce,
+            self.b64_url_ciphertext.as_deref(),
+            self.b64_url_nonce.as_deref(),
+            self.b64_totp_secret_ciphertext.as_deref(),
+            self.b64_totp_secret_nonce.as_deref(),
+            self.category.as_deref(),
+            self.expires_at.as_deref(),
+            self.created_at,
+            self.updated_at,
         )
     }
 }
 
+/// Generate the RFC 6238 TOTP code for the given secret which is valid at the given Unix
+/// timestamp, using HMAC-SHA1, [TOTP_STEP_SECS]-second steps, and [TOTP_DIGITS] digits.
+pub fn totp_code(secret: &[u8], time: u64) -> Result<String, Error> {
+    let counter = time / TOTP_STEP_SECS;
+    let mut mac =
+        Hmac::<Sha1>::new_from_slice(secret).map_err(|e| Error::UnhandledError(e.to_string()))?;
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation: the low nibble of the last byte picks four bytes of the hash.
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    Ok(format!(
+        "{:0width$}",
+        binary % 10_u32.pow(TOTP_DIGITS),
+        width = TOTP_DIGITS as usize
+    ))
+}
+
+/// Return the number of seconds for which the TOTP code valid at the given Unix timestamp stays
+/// valid.
+pub fn totp_seconds_remaining(time: u64) -> u64 {
+    TOTP_STEP_SECS - time % TOTP_STEP_SECS
+}
+
+/// Trim the names of a comma-separated list of categories and leave out empty ones.
+/// Return [None] if no categories are left.
+pub fn normalize_categories(categories: &str) -> Option<String> {
+    let categories: Vec<&str> = categories
+        .split(',')
+        .map(str::trim)
+        .filter(|category| !category.is_empty())
+        .collect();
+    if categories.is_empty() {
+        None
+    } else {
+        Some(categories.join(","))
+    }
+}
+
+/// Parse an ISO 8601 `YYYY-MM-DD` expiry date. An empty string means there is no expiry date.
+pub fn parse_expiry_date(date: &str) -> Result<Option<NaiveDate>, Error> {
+    let date = date.trim();
+    if date.is_empty() {
+        return Ok(None);
+    }
+    match date.parse::<NaiveDate>() {
+        Ok(date) => Ok(Some(date)),
+        Err(_) => Err(Error::UnsupportedFormatError(format!(
+            "Expiry date \"{date}\" is not a YYYY-MM-DD date."
+        ))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -216,6 +755,7 @@ mod tests {
     const TEST_USERNAME: &str = "my_schploggy_account";
     const TEST_CONTENT: &str = "ILoveSchploggy!123";
     const TEST_NOTES: &str = "Security Question: My father's middle name is Bob.";
+    const TEST_URL: &str = "https://schploggy.example.com/login";
 
     #[test]
     fn test_new_password() {
@@ -287,32 +827,343 @@ mod tests {
 
         let my_password_from_b64 = Password::from_b64(my_password_b64).unwrap();
         assert_eq!(
-            my_password_from_b64
+            *my_password_from_b64
                 .encrypted_name()
                 .decrypt(my_key)
                 .unwrap(),
             TEST_NAME.as_bytes()
         );
         assert_eq!(
-            my_password_from_b64
+            *my_password_from_b64
                 .encrypted_username()
                 .decrypt(my_key)
                 .unwrap(),
             TEST_USERNAME.as_bytes()
         );
         assert_eq!(
-            my_password_from_b64
+            *my_password_from_b64
                 .encrypted_content()
                 .decrypt(my_key)
                 .unwrap(),
             TEST_CONTENT.as_bytes()
         );
         assert_eq!(
-            my_password_from_b64
+            *my_password_from_b64
                 .encrypted_notes()
                 .decrypt(my_key)
                 .unwrap(),
             TEST_NOTES.as_bytes()
         );
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let my_fields = my_account.unlock("my_password").unwrap();
+        let mut my_password = Password::new_with_key(
+            my_fields.username(),
+            my_fields.key(),
+            TEST_NAME,
+            TEST_USERNAME,
+            TEST_CONTENT,
+            TEST_NOTES,
+        )
+        .unwrap();
+        my_password
+            .apply_changes(
+                my_fields.key(),
+                &PasswordChanges::new()
+                    .new_url(TEST_URL)
+                    .new_category("work"),
+            )
+            .unwrap();
+
+        let json = serde_json::to_string(&my_password).unwrap();
+        assert!(!json.contains(TEST_CONTENT));
+        let loaded: Password = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::to_string(&loaded).unwrap());
+        assert_eq!(Some("work"), loaded.category());
+        let unlocked = loaded.unlock(my_fields.key()).unwrap();
+        assert_eq!(TEST_CONTENT, unlocked.content());
+        assert_eq!(Some(TEST_URL), unlocked.url());
+    }
+
+    #[test]
+    fn test_display() {
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let my_fields = my_account.unlock("my_password").unwrap();
+        let my_password = Password::new_with_key(
+            my_fields.username(),
+            my_fields.key(),
+            TEST_NAME,
+            TEST_USERNAME,
+            TEST_CONTENT,
+            TEST_NOTES,
+        )
+        .unwrap();
+
+        let displayed = my_password.to_string();
+        assert_eq!(
+            format!(
+                "Password {{ owner: \"my_account\", name: {}, nonce: {} }}",
+                helpers::bytes_to_hex(my_password.encrypted_name().ciphertext()),
+                helpers::bytes_to_hex(my_password.encrypted_name().nonce())
+            ),
+            displayed
+        );
+        for secret in [TEST_NAME, TEST_USERNAME, TEST_CONTENT, TEST_NOTES] {
+            assert!(!displayed.contains(secret));
+        }
+    }
+
+    #[test]
+    fn test_apply_changes() {
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let my_fields = my_account.unlock("my_password").unwrap();
+        let my_key = my_fields.key();
+
+        let mut my_password = Password::new(
+            &my_account,
+            "my_password",
+            TEST_NAME,
+            TEST_USERNAME,
+            TEST_CONTENT,
+            TEST_NOTES,
+        )
+        .unwrap();
+        let old_name_nonce = *my_password.encrypted_name().nonce();
+
+        assert!(PasswordChanges::new().is_empty());
+        let changes = PasswordChanges::new()
+            .new_content("ILoveSchploggyEvenMore!456")
+            .new_notes("");
+        assert!(!changes.is_empty());
+        my_password.apply_changes(my_key, &changes).unwrap();
+
+        assert_eq!(&old_name_nonce, my_password.encrypted_name().nonce());
+        let unlocked = my_password.unlock(my_key).unwrap();
+        assert_eq!(unlocked.name(), TEST_NAME);
+        assert_eq!(unlocked.username(), TEST_USERNAME);
+        assert_eq!(unlocked.content(), "ILoveSchploggyEvenMore!456");
+        assert_eq!(unlocked.notes(), "");
+        assert_eq!(unlocked.url(), None);
+    }
+
+    #[test]
+    fn test_category() {
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let my_fields = my_account.unlock("my_password").unwrap();
+        let my_key = my_fields.key();
+
+        let mut my_password = Password::new(
+            &my_account,
+            "my_password",
+            TEST_NAME,
+            TEST_USERNAME,
+            TEST_CONTENT,
+            TEST_NOTES,
+        )
+        .unwrap();
+        assert_eq!(my_password.category(), None);
+        assert!(my_password.categories().is_empty());
+
+        my_password
+            .apply_changes(
+                my_key,
+                &PasswordChanges::new().new_category(" social, ,games "),
+            )
+            .unwrap();
+        let mut my_password = Password::from_b64(my_password.to_b64()).unwrap();
+        assert_eq!(my_password.category(), Some("social,games"));
+        assert_eq!(my_password.categories(), vec!["social", "games"]);
+
+        my_password
+            .apply_changes(my_key, &PasswordChanges::new().new_category(""))
+            .unwrap();
+        assert!(my_password.to_b64().category.is_none());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let my_fields = my_account.unlock("my_password").unwrap();
+        let my_key = my_fields.key();
+        let today = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+
+        let mut my_password = Password::new(
+            &my_account,
+            "my_password",
+            TEST_NAME,
+            TEST_USERNAME,
+            TEST_CONTENT,
+            TEST_NOTES,
+        )
+        .unwrap();
+        assert_eq!(my_password.expires_at(), None);
+        assert_eq!(my_password.expiry_status(today), ExpiryStatus::Valid);
+
+        let expires_at = parse_expiry_date("2024-05-31").unwrap();
+        my_password
+            .apply_changes(my_key, &PasswordChanges::new().new_expires_at(expires_at))
+            .unwrap();
+        let mut my_password = Password::from_b64(my_password.to_b64()).unwrap();
+        assert_eq!(my_password.expires_at(), expires_at);
+        assert_eq!(my_password.expiry_status(today), ExpiryStatus::ExpiringSoon);
+        assert_eq!(
+            my_password.expiry_status(NaiveDate::from_ymd_opt(2024, 4, 1).unwrap()),
+            ExpiryStatus::Valid
+        );
+        assert_eq!(
+            my_password.expiry_status(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap()),
+            ExpiryStatus::Expired
+        );
+
+        my_password
+            .apply_changes(my_key, &PasswordChanges::new().new_expires_at(None))
+            .unwrap();
+        assert!(my_password.to_b64().expires_at.is_none());
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let my_fields = my_account.unlock("my_password").unwrap();
+        let my_key = my_fields.key();
+
+        let mut b64_password = Password::new(
+            &my_account,
+            "my_password",
+            TEST_NAME,
+            TEST_USERNAME,
+            TEST_CONTENT,
+            TEST_NOTES,
+        )
+        .unwrap()
+        .to_b64();
+        assert_eq!(b64_password.created_at, b64_password.updated_at);
+        b64_password.created_at = 1_700_000_000;
+        b64_password.updated_at = 1_700_000_000;
+        let mut my_password = Password::from_b64(b64_password).unwrap();
+        assert_eq!(my_password.created_at().timestamp(), 1_700_000_000);
+
+        // Empty changes don't count as an update.
+        my_password
+            .apply_changes(my_key, &PasswordChanges::new())
+            .unwrap();
+        assert_eq!(my_password.updated_at().timestamp(), 1_700_000_000);
+
+        my_password
+            .apply_changes(my_key, &PasswordChanges::new().new_notes("new notes"))
+            .unwrap();
+        assert_eq!(my_password.created_at().timestamp(), 1_700_000_000);
+        assert!(my_password.updated_at() > my_password.created_at());
+
+        let mut b64_password = my_password.to_b64();
+        b64_password.created_at = i64::MAX;
+        assert!(Password::from_b64(b64_password).is_err());
+    }
+
+    #[test]
+    fn test_parse_expiry_date() {
+        assert_eq!(
+            parse_expiry_date(" 2024-02-29 ").unwrap(),
+            NaiveDate::from_ymd_opt(2024, 2, 29)
+        );
+        assert_eq!(parse_expiry_date("").unwrap(), None);
+        assert!(parse_expiry_date("2023-02-29").is_err());
+        assert!(parse_expiry_date("29/02/2024").is_err());
+    }
+
+    #[test]
+    fn test_url() {
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let my_fields = my_account.unlock("my_password").unwrap();
+        let my_key = my_fields.key();
+
+        let mut my_password = Password::new(
+            &my_account,
+            "my_password",
+            TEST_NAME,
+            TEST_USERNAME,
+            TEST_CONTENT,
+            TEST_NOTES,
+        )
+        .unwrap();
+        assert!(my_password.encrypted_url().is_none());
+        assert_eq!(my_password.url(my_key).unwrap(), None);
+
+        my_password
+            .apply_changes(my_key, &PasswordChanges::new().new_url(TEST_URL))
+            .unwrap();
+        let mut my_password = Password::from_b64(my_password.to_b64()).unwrap();
+        assert_eq!(my_password.url(my_key).unwrap().unwrap(), TEST_URL);
+        assert_eq!(
+            my_password.unlock(my_key).unwrap().field("url"),
+            Some(TEST_URL)
+        );
+
+        my_password
+            .apply_changes(my_key, &PasswordChanges::new().new_url(""))
+            .unwrap();
+        let my_password_b64 = my_password.to_b64();
+        assert!(my_password_b64.b64_url_ciphertext.is_none());
+        assert!(my_password_b64.b64_url_nonce.is_none());
+        assert_eq!(my_password.unlock(my_key).unwrap().field("url"), Some(""));
+    }
+
+    #[test]
+    fn test_totp() {
+        // The HMAC-SHA1 test vectors of RFC 6238, truncated to six digits.
+        const RFC_SECRET: &[u8] = b"12345678901234567890";
+        let vectors = [
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_111_111_111, "050471"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+            (20_000_000_000, "353130"),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(totp_code(RFC_SECRET, time).unwrap(), code);
+        }
+        assert_eq!(totp_seconds_remaining(59), 1);
+        assert_eq!(totp_seconds_remaining(60), 30);
+
+        let my_account = Account::new("my_account", "my_password").unwrap();
+        let my_fields = my_account.unlock("my_password").unwrap();
+        let my_key = my_fields.key();
+
+        let mut my_password = Password::new(
+            &my_account,
+            "my_password",
+            TEST_NAME,
+            TEST_USERNAME,
+            TEST_CONTENT,
+            TEST_NOTES,
+        )
+        .unwrap();
+        assert!(my_password.encrypted_totp_secret().is_none());
+        assert_eq!(my_password.generate_totp(my_key, 59).unwrap(), None);
+
+        my_password
+            .apply_changes(my_key, &PasswordChanges::new().new_totp_secret(RFC_SECRET))
+            .unwrap();
+        let mut my_password = Password::from_b64(my_password.to_b64()).unwrap();
+        assert_eq!(
+            my_password.totp_secret(my_key).unwrap().unwrap().as_slice(),
+            RFC_SECRET
+        );
+        assert_eq!(
+            my_password.generate_totp(my_key, 59).unwrap().unwrap(),
+            "287082"
+        );
+
+        my_password
+            .apply_changes(my_key, &PasswordChanges::new().new_totp_secret(&[]))
+            .unwrap();
+        let my_password_b64 = my_password.to_b64();
+        assert!(my_password_b64.b64_totp_secret_ciphertext.is_none());
+        assert!(my_password_b64.b64_totp_secret_nonce.is_none());
+    }
 }
@@ -0,0 +1,150 @@
+//! Functionality related to single-use account recovery codes.
+//!
+//! Only the salted hash of each code is stored. The plaintext codes are shown to the user once,
+//! when they are generated.
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+
+use crate::{backend::hashed::Hashed, error::Error, helpers};
+
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// A hashed single-use recovery code belonging to a `dgruft` account.
+#[derive(Debug)]
+pub struct RecoveryCode {
+    owner_username: String,
+    hashed_code: Hashed,
+    last_rotated_at: i64,
+}
+impl RecoveryCode {
+    /// The number of characters in a recovery code.
+    pub const LENGTH: usize = 8;
+    /// The number of recovery codes generated at a time.
+    pub const COUNT: usize = 10;
+
+    /// Generate a new random [RecoveryCode] for the given account, as part of a set of codes
+    /// rotated at the given Unix timestamp. Return the [RecoveryCode] alongside its plaintext code.
+    pub fn generate(owner_username: &str, last_rotated_at: i64) -> (Self, String) {
+        let mut rng = ChaCha20Rng::from_entropy();
+        let mut code = String::with_capacity(Self::LENGTH);
+        while code.len() < Self::LENGTH {
+            // Reject bytes past the largest multiple of the alphabet length to avoid modulo bias.
+            let byte = (rng.next_u32() & 0xFF) as usize;
+            if byte < 256 - (256 % CODE_ALPHABET.len()) {
+                code.push(CODE_ALPHABET[byte % CODE_ALPHABET.len()] as char);
+            }
+        }
+
+        (
+            Self {
+                owner_username: owner_username.to_owned(),
+                hashed_code: Hashed::new(code.as_bytes()),
+                last_rotated_at,
+            },
+            code,
+        )
+    }
+
+    /// Return true iff the given code matches this [RecoveryCode].
+    pub fn check_match(&self, code: &str) -> bool {
+        self.hashed_code.check_match(code.as_bytes())
+    }
+
+    /// Load a [RecoveryCode] from a [Base64RecoveryCode]— a set of base-64-encoded strings.
+    pub fn from_b64(b64_recovery_code: Base64RecoveryCode) -> Result<Self, Error> {
+        let owner_username = helpers::bytes_to_utf8(
+            &helpers::b64_to_bytes(&b64_recovery_code.b64_owner_username)?,
+            "owner_username",
+        )?;
+        let hashed_code = Hashed::from_b64(
+            &b64_recovery_code.b64_code_hash,
+            &b64_recovery_code.b64_code_salt,
+        )?;
+
+        Ok(Self {
+            owner_username,
+            hashed_code,
+            last_rotated_at: b64_recovery_code.last_rotated_at,
+        })
+    }
+
+    /// Convert this [RecoveryCode] to a [Base64RecoveryCode] for storage.
+    pub fn to_b64(&self) -> Base64RecoveryCode {
+        Base64RecoveryCode {
+            b64_owner_username: helpers::bytes_to_b64(self.owner_username().as_bytes()),
+            b64_code_hash: self.hashed_code().hash_as_b64(),
+            b64_code_salt: self.hashed_code().salt_as_b64(),
+            last_rotated_at: self.last_rotated_at(),
+        }
+    }
+
+    // GETTERS
+
+    /// Return the username of the account this [RecoveryCode] belongs to.
+    pub fn owner_username(&self) -> &str {
+        &self.owner_username
+    }
+
+    /// Return the hashed code of this [RecoveryCode].
+    pub fn hashed_code(&self) -> &Hashed {
+        &self.hashed_code
+    }
+
+    /// Return the Unix timestamp at which this [RecoveryCode]'s set of codes was generated.
+    pub fn last_rotated_at(&self) -> i64 {
+        self.last_rotated_at
+    }
+}
+
+/// A [RecoveryCode] converted for base-64 storage.
+#[derive(Debug)]
+pub struct Base64RecoveryCode {
+    /// Owner username in base-64 format.
+    pub b64_owner_username: String,
+    /// Code hash in base-64 format.
+    pub b64_code_hash: String,
+    /// Code salt in base-64 format.
+    pub b64_code_salt: String,
+    /// Unix timestamp at which this code's set of codes was generated.
+    pub last_rotated_at: i64,
+}
+impl Base64RecoveryCode {
+    /// Output fields as tuple.
+    pub fn as_tuple(&self) -> (&str, &str, &str, i64) {
+        (
+            &self.b64_owner_username,
+            &self.b64_code_hash,
+            &self.b64_code_salt,
+            self.last_rotated_at,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn test_generate() {
+        let (recovery_code, code) = RecoveryCode::generate("my_account", 0);
+        assert_eq!(code.len(), RecoveryCode::LENGTH);
+        assert!(code.bytes().all(|byte| CODE_ALPHABET.contains(&byte)));
+        assert!(recovery_code.check_match(&code));
+        assert!(!recovery_code.check_match("wrongcod"));
+
+        let (_, other_code) = RecoveryCode::generate("my_account", 0);
+        assert_ne!(code, other_code);
+    }
+
+    #[test]
+    fn test_to_from_b64() {
+        let (recovery_code, code) = RecoveryCode::generate("马克斯", 1_700_000_000);
+        let loaded = RecoveryCode::from_b64(recovery_code.to_b64()).unwrap();
+        assert_eq!(loaded.owner_username(), "马克斯");
+        assert_eq!(loaded.last_rotated_at(), 1_700_000_000);
+        assert!(loaded.check_match(&code));
+    }
+}
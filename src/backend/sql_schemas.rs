@@ -5,10 +5,32 @@ pub const CREATE_USER_CREDENTIALS: &str = "
         dbl_hashed_password_hash TEXT NOT NULL,
         dbl_hashed_password_salt TEXT NOT NULL,
         encrypted_key_ciphertext TEXT NOT NULL,
-        encrypted_key_nonce TEXT NOT NULL
+        encrypted_key_nonce TEXT NOT NULL,
+        kdf_params TEXT NOT NULL DEFAULT 'pbkdf2',
+        created_at INTEGER NOT NULL,
+        last_login INTEGER
     );
 ";
 
+// Accounts created before Argon2id was introduced use PBKDF2.
+pub const ADD_USER_CREDENTIALS_KDF_PARAMS: &str = "
+    ALTER TABLE user_credentials
+    ADD COLUMN kdf_params TEXT NOT NULL DEFAULT 'pbkdf2'
+";
+
+// Accounts created before timestamps were introduced are dated to the migration; see
+// BACKFILL_USER_CREDENTIALS_CREATED_AT.
+pub const ADD_USER_CREDENTIALS_CREATED_AT: &str = "
+    ALTER TABLE user_credentials
+    ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0
+";
+
+// Accounts which haven't logged in since then have no last login.
+pub const ADD_USER_CREDENTIALS_LAST_LOGIN: &str = "
+    ALTER TABLE user_credentials
+    ADD COLUMN last_login INTEGER
+";
+
 pub const CREATE_PASSWORDS: &str = "
     CREATE TABLE IF NOT EXISTS passwords (
         owner_username TEXT NOT NULL,
@@ -20,6 +42,14 @@ pub const CREATE_PASSWORDS: &str = "
         name_nonce TEXT NOT NULL,
         content_nonce TEXT NOT NULL,
         notes_nonce TEXT NOT NULL,
+        encrypted_url TEXT,
+        url_nonce TEXT,
+        encrypted_totp_secret TEXT,
+        totp_secret_nonce TEXT,
+        category TEXT,
+        expires_at TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
         FOREIGN KEY (owner_username)
             REFERENCES user_credentials(username)
             ON DELETE CASCADE,
@@ -27,14 +57,142 @@ pub const CREATE_PASSWORDS: &str = "
     )
 ";
 
+// Passwords created before URLs were introduced have no URL.
+pub const ADD_PASSWORDS_URL: &str = "
+    ALTER TABLE passwords
+    ADD COLUMN encrypted_url TEXT
+";
+
+pub const ADD_PASSWORDS_URL_NONCE: &str = "
+    ALTER TABLE passwords
+    ADD COLUMN url_nonce TEXT
+";
+
+// Passwords created before categories were introduced have no category.
+pub const ADD_PASSWORDS_CATEGORY: &str = "
+    ALTER TABLE passwords
+    ADD COLUMN category TEXT
+";
+
+// Passwords created before expiry dates were introduced don't expire.
+pub const ADD_PASSWORDS_EXPIRES_AT: &str = "
+    ALTER TABLE passwords
+    ADD COLUMN expires_at TEXT
+";
+
+// Passwords created before timestamps were introduced are dated to the migration; see
+// BACKFILL_PASSWORDS_TIMESTAMPS.
+pub const ADD_PASSWORDS_CREATED_AT: &str = "
+    ALTER TABLE passwords
+    ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0
+";
+
+pub const ADD_PASSWORDS_UPDATED_AT: &str = "
+    ALTER TABLE passwords
+    ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0
+";
+
+// Passwords created before TOTP secrets were introduced have no TOTP secret.
+pub const ADD_PASSWORDS_TOTP_SECRET: &str = "
+    ALTER TABLE passwords
+    ADD COLUMN encrypted_totp_secret TEXT
+";
+
+pub const ADD_PASSWORDS_TOTP_SECRET_NONCE: &str = "
+    ALTER TABLE passwords
+    ADD COLUMN totp_secret_nonce TEXT
+";
+
 pub const CREATE_FILES: &str = "
     CREATE TABLE IF NOT EXISTS files (
         path TEXT NOT NULL PRIMARY KEY,
         name TEXT NOT NULL,
         owner_username TEXT NOT NULL,
         content_nonce TEXT NOT NULL,
+        file_size INTEGER NOT NULL,
+        extension TEXT,
+        compressed INTEGER NOT NULL DEFAULT 0,
         FOREIGN KEY (owner_username)
             REFERENCES user_credentials(username)
             ON DELETE CASCADE
     )
 ";
+
+// The sizes of files stored before sizes were tracked are read from disk by the migration.
+pub const ADD_FILES_FILE_SIZE: &str = "
+    ALTER TABLE files
+    ADD COLUMN file_size INTEGER NOT NULL DEFAULT 0
+";
+
+// The extensions of files stored before extensions were tracked are read from their names by the
+// migration.
+pub const ADD_FILES_EXTENSION: &str = "
+    ALTER TABLE files
+    ADD COLUMN extension TEXT
+";
+
+// Files stored before compression was supported are all uncompressed.
+pub const ADD_FILES_COMPRESSED: &str = "
+    ALTER TABLE files
+    ADD COLUMN compressed INTEGER NOT NULL DEFAULT 0
+";
+
+pub const CREATE_RECOVERY_CODES: &str = "
+    CREATE TABLE IF NOT EXISTS recovery_codes (
+        owner_username TEXT NOT NULL,
+        code_hash TEXT NOT NULL,
+        code_salt TEXT NOT NULL,
+        last_rotated_at INTEGER NOT NULL,
+        FOREIGN KEY (owner_username)
+            REFERENCES user_credentials(username)
+            ON DELETE CASCADE,
+        PRIMARY KEY(owner_username, code_hash)
+    )
+";
+
+// A password can change more than once a second, so rows are identified by their row ID rather
+// than by their password and time.
+pub const CREATE_PASSWORD_HISTORY: &str = "
+    CREATE TABLE IF NOT EXISTS password_history (
+        id INTEGER PRIMARY KEY,
+        owner_username TEXT NOT NULL,
+        password_name TEXT NOT NULL,
+        content_ciphertext TEXT NOT NULL,
+        content_nonce TEXT NOT NULL,
+        changed_at INTEGER NOT NULL,
+        FOREIGN KEY (owner_username, password_name)
+            REFERENCES passwords(owner_username, encrypted_name)
+            ON DELETE CASCADE
+            ON UPDATE CASCADE
+    )
+";
+
+pub const CREATE_PASSWORD_ATTACHMENTS: &str = "
+    CREATE TABLE IF NOT EXISTS password_attachments (
+        owner_username TEXT NOT NULL,
+        password_name TEXT NOT NULL,
+        name TEXT NOT NULL,
+        path TEXT NOT NULL UNIQUE,
+        nonce TEXT NOT NULL,
+        size_bytes INTEGER NOT NULL,
+        FOREIGN KEY (owner_username, password_name)
+            REFERENCES passwords(owner_username, encrypted_name)
+            ON DELETE CASCADE
+            ON UPDATE CASCADE,
+        PRIMARY KEY(owner_username, password_name, name)
+    )
+";
+
+pub const CREATE_VAULT_CONFIG: &str = "
+    CREATE TABLE IF NOT EXISTS vault_config (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    )
+";
+
+pub const CREATE_SCHEMA_VERSION: &str = "
+    CREATE TABLE IF NOT EXISTS schema_version (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        version INTEGER NOT NULL
+    )
+";
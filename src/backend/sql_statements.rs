@@ -5,9 +5,32 @@ pub const INSERT_NEW_ACCOUNT: &str = "
         dbl_hashed_password_hash,
         dbl_hashed_password_salt,
         encrypted_key_ciphertext,
-        encrypted_key_nonce
+        encrypted_key_nonce,
+        kdf_params,
+        created_at,
+        last_login
     )
-    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+";
+
+pub const UPDATE_ACCOUNT: &str = "
+    UPDATE user_credentials
+    SET
+        password_salt = ?2,
+        dbl_hashed_password_hash = ?3,
+        dbl_hashed_password_salt = ?4,
+        encrypted_key_ciphertext = ?5,
+        encrypted_key_nonce = ?6,
+        kdf_params = ?7,
+        created_at = ?8,
+        last_login = ?9
+    WHERE username = ?1
+";
+
+pub const UPDATE_ACCOUNT_LAST_LOGIN: &str = "
+    UPDATE user_credentials
+    SET last_login = ?2
+    WHERE username = ?1
 ";
 
 pub const GET_ACCOUNT: &str = "
@@ -17,9 +40,32 @@ pub const GET_ACCOUNT: &str = "
         dbl_hashed_password_hash,
         dbl_hashed_password_salt,
         encrypted_key_ciphertext,
-        encrypted_key_nonce
+        encrypted_key_nonce,
+        kdf_params,
+        created_at,
+        last_login
+    FROM user_credentials
+    WHERE username = ?1
+";
+
+pub const GET_ALL_ACCOUNTS: &str = "
+    SELECT
+        username,
+        password_salt,
+        dbl_hashed_password_hash,
+        dbl_hashed_password_salt,
+        encrypted_key_ciphertext,
+        encrypted_key_nonce,
+        kdf_params,
+        created_at,
+        last_login
     FROM user_credentials
+";
+
+pub const ACCOUNT_EXISTS: &str = "
+    SELECT 1 FROM user_credentials
     WHERE username = ?1
+    LIMIT 1
 ";
 
 pub const DELETE_ACCOUNT: &str = "
@@ -27,6 +73,11 @@ pub const DELETE_ACCOUNT: &str = "
     WHERE username = ?1
 ";
 
+pub const DELETE_PASSWORD: &str = "
+    DELETE FROM passwords
+    WHERE owner_username = ?1 AND encrypted_name = ?2
+";
+
 pub const INSERT_NEW_PASSWORD: &str = "
     INSERT INTO passwords (
         owner_username,
@@ -37,9 +88,17 @@ pub const INSERT_NEW_PASSWORD: &str = "
         name_nonce,
         username_nonce,
         content_nonce,
-        notes_nonce
+        notes_nonce,
+        encrypted_url,
+        url_nonce,
+        encrypted_totp_secret,
+        totp_secret_nonce,
+        category,
+        expires_at,
+        created_at,
+        updated_at
     )
-    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
 ";
 
 pub const GET_USER_PASSWORDS: &str = "
@@ -52,19 +111,98 @@ pub const GET_USER_PASSWORDS: &str = "
         name_nonce,
         username_nonce,
         content_nonce,
-        notes_nonce
+        notes_nonce,
+        encrypted_url,
+        url_nonce,
+        encrypted_totp_secret,
+        totp_secret_nonce,
+        category,
+        expires_at,
+        created_at,
+        updated_at
     FROM passwords
     WHERE owner_username = ?1
 ";
 
+// Categories are stored as a comma-separated list, so match whole list items.
+pub const GET_USER_PASSWORDS_BY_CATEGORY: &str = "
+    SELECT
+        owner_username,
+        encrypted_name,
+        encrypted_username,
+        encrypted_content,
+        encrypted_notes,
+        name_nonce,
+        username_nonce,
+        content_nonce,
+        notes_nonce,
+        encrypted_url,
+        url_nonce,
+        encrypted_totp_secret,
+        totp_secret_nonce,
+        category,
+        expires_at,
+        created_at,
+        updated_at
+    FROM passwords
+    WHERE owner_username = ?1 AND instr(',' || category || ',', ',' || ?2 || ',') > 0
+";
+
+pub const GET_ALL_PASSWORDS: &str = "
+    SELECT
+        owner_username,
+        encrypted_name,
+        encrypted_username,
+        encrypted_content,
+        encrypted_notes,
+        name_nonce,
+        username_nonce,
+        content_nonce,
+        notes_nonce,
+        encrypted_url,
+        url_nonce,
+        encrypted_totp_secret,
+        totp_secret_nonce,
+        category,
+        expires_at,
+        created_at,
+        updated_at
+    FROM passwords
+";
+
+pub const UPDATE_PASSWORD: &str = "
+    UPDATE passwords
+    SET
+        encrypted_name = ?2,
+        encrypted_username = ?3,
+        encrypted_content = ?4,
+        encrypted_notes = ?5,
+        name_nonce = ?6,
+        username_nonce = ?7,
+        content_nonce = ?8,
+        notes_nonce = ?9,
+        encrypted_url = ?10,
+        url_nonce = ?11,
+        encrypted_totp_secret = ?12,
+        totp_secret_nonce = ?13,
+        category = ?14,
+        expires_at = ?15,
+        created_at = ?16,
+        updated_at = ?17
+    WHERE owner_username = ?1 AND encrypted_name = ?18
+";
+
 pub const INSERT_NEW_FILE: &str = "
     INSERT INTO files (
         path,
         name,
         owner_username,
-        content_nonce
+        content_nonce,
+        file_size,
+        extension,
+        compressed
     )
-    VALUES (?1, ?2, ?3, ?4)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
 ";
 
 pub const GET_FILE: &str = "
@@ -72,11 +210,20 @@ pub const GET_FILE: &str = "
         path,
         name,
         owner_username,
-        content_nonce
+        content_nonce,
+        file_size,
+        extension,
+        compressed
     FROM files
     WHERE path = ?1
 ";
 
+pub const FILE_EXISTS: &str = "
+    SELECT 1 FROM files
+    WHERE path = ?1
+    LIMIT 1
+";
+
 pub const DELETE_FILE: &str = "
     DELETE FROM files
     WHERE path = ?1
@@ -87,13 +234,270 @@ pub const GET_USER_FILES: &str = "
         path,
         name,
         owner_username,
-        content_nonce
+        content_nonce,
+        file_size,
+        extension,
+        compressed
     FROM files
     WHERE owner_username = ?1
 ";
 
+pub const GET_ALL_FILES: &str = "
+    SELECT
+        path,
+        name,
+        owner_username,
+        content_nonce,
+        file_size,
+        extension,
+        compressed
+    FROM files
+";
+
 pub const UPDATE_FILE_CONTENT_NONCE: &str = "
     UPDATE files
     SET content_nonce = ?1
     WHERE path = ?2
 ";
+
+pub const UPDATE_FILE_CONTENT: &str = "
+    UPDATE files
+    SET content_nonce = ?1, file_size = ?2
+    WHERE path = ?3
+";
+
+pub const GET_USER_FILE_SIZE: &str = "
+    SELECT COALESCE(SUM(file_size), 0) FROM files
+    WHERE owner_username = ?1
+";
+
+pub const GET_ALL_FILE_SIZE: &str = "
+    SELECT COALESCE(SUM(file_size), 0) FROM files
+";
+
+pub const INSERT_NEW_RECOVERY_CODE: &str = "
+    INSERT INTO recovery_codes (
+        owner_username,
+        code_hash,
+        code_salt,
+        last_rotated_at
+    )
+    VALUES (?1, ?2, ?3, ?4)
+";
+
+pub const GET_USER_RECOVERY_CODES: &str = "
+    SELECT
+        owner_username,
+        code_hash,
+        code_salt,
+        last_rotated_at
+    FROM recovery_codes
+    WHERE owner_username = ?1
+";
+
+pub const DELETE_RECOVERY_CODE: &str = "
+    DELETE FROM recovery_codes
+    WHERE owner_username = ?1 AND code_hash = ?2
+";
+
+pub const DELETE_USER_RECOVERY_CODES: &str = "
+    DELETE FROM recovery_codes
+    WHERE owner_username = ?1
+";
+
+pub const UPDATE_FILE_PATH: &str = "
+    UPDATE files
+    SET path = ?1
+    WHERE path = ?2
+";
+
+pub const INSERT_NEW_ATTACHMENT: &str = "
+    INSERT INTO password_attachments (
+        owner_username,
+        password_name,
+        name,
+        path,
+        nonce,
+        size_bytes
+    )
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+";
+
+pub const GET_PASSWORD_ATTACHMENTS: &str = "
+    SELECT
+        owner_username,
+        password_name,
+        name,
+        path,
+        nonce,
+        size_bytes
+    FROM password_attachments
+    WHERE owner_username = ?1 AND password_name = ?2
+";
+
+pub const DELETE_ATTACHMENT: &str = "
+    DELETE FROM password_attachments
+    WHERE owner_username = ?1 AND password_name = ?2 AND name = ?3
+";
+
+pub const INSERT_NEW_PASSWORD_HISTORY: &str = "
+    INSERT INTO password_history (
+        owner_username,
+        password_name,
+        content_ciphertext,
+        content_nonce,
+        changed_at
+    )
+    VALUES (?1, ?2, ?3, ?4, ?5)
+";
+
+pub const GET_PASSWORD_HISTORY: &str = "
+    SELECT
+        owner_username,
+        password_name,
+        content_ciphertext,
+        content_nonce,
+        changed_at
+    FROM password_history
+    WHERE owner_username = ?1 AND password_name = ?2
+    ORDER BY changed_at DESC, id DESC
+";
+
+// Keep only the newest ?2 entries of each of the account's passwords.
+pub const PRUNE_PASSWORD_HISTORY: &str = "
+    DELETE FROM password_history
+    WHERE id IN (
+        SELECT id FROM (
+            SELECT
+                id,
+                ROW_NUMBER() OVER (
+                    PARTITION BY password_name
+                    ORDER BY changed_at DESC, id DESC
+                ) AS position
+            FROM password_history
+            WHERE owner_username = ?1
+        )
+        WHERE position > ?2
+    )
+";
+
+pub const GET_CONFIG: &str = "
+    SELECT value FROM vault_config
+    WHERE key = ?1
+";
+
+pub const SET_CONFIG: &str = "
+    INSERT INTO vault_config (key, value)
+    VALUES (?1, ?2)
+    ON CONFLICT(key) DO UPDATE SET value = excluded.value
+";
+
+pub const USER_CREDENTIALS_EXISTS: &str = "
+    SELECT 1 FROM sqlite_master
+    WHERE type = 'table' AND name = 'user_credentials'
+";
+
+pub const SCHEMA_VERSION_EXISTS: &str = "
+    SELECT 1 FROM sqlite_master
+    WHERE type = 'table' AND name = 'schema_version'
+";
+
+pub const GET_SCHEMA_VERSION: &str = "
+    SELECT version FROM schema_version
+    WHERE id = 0
+";
+
+pub const SET_SCHEMA_VERSION: &str = "
+    INSERT OR REPLACE INTO schema_version (id, version)
+    VALUES (0, ?1)
+";
+
+pub const USER_CREDENTIALS_HAS_KDF_PARAMS: &str = "
+    SELECT 1 FROM pragma_table_info('user_credentials')
+    WHERE name = 'kdf_params'
+";
+
+pub const PASSWORDS_HAS_URL: &str = "
+    SELECT 1 FROM pragma_table_info('passwords')
+    WHERE name = 'encrypted_url'
+";
+
+pub const PASSWORDS_HAS_CATEGORY: &str = "
+    SELECT 1 FROM pragma_table_info('passwords')
+    WHERE name = 'category'
+";
+
+pub const PASSWORDS_HAS_EXPIRES_AT: &str = "
+    SELECT 1 FROM pragma_table_info('passwords')
+    WHERE name = 'expires_at'
+";
+
+pub const PASSWORDS_HAS_CREATED_AT: &str = "
+    SELECT 1 FROM pragma_table_info('passwords')
+    WHERE name = 'created_at'
+";
+
+pub const PASSWORDS_HAS_TOTP_SECRET: &str = "
+    SELECT 1 FROM pragma_table_info('passwords')
+    WHERE name = 'encrypted_totp_secret'
+";
+
+pub const BACKFILL_PASSWORDS_TIMESTAMPS: &str = "
+    UPDATE passwords
+    SET created_at = ?1, updated_at = ?1
+";
+
+pub const USER_CREDENTIALS_HAS_CREATED_AT: &str = "
+    SELECT 1 FROM pragma_table_info('user_credentials')
+    WHERE name = 'created_at'
+";
+
+pub const BACKFILL_USER_CREDENTIALS_CREATED_AT: &str = "
+    UPDATE user_credentials
+    SET created_at = ?1
+";
+
+pub const FILES_HAS_FILE_SIZE: &str = "
+    SELECT 1 FROM pragma_table_info('files')
+    WHERE name = 'file_size'
+";
+
+pub const GET_ALL_FILE_PATHS: &str = "
+    SELECT path FROM files
+";
+
+pub const SET_FILE_SIZE: &str = "
+    UPDATE files
+    SET file_size = ?1
+    WHERE path = ?2
+";
+
+pub const FILES_HAS_EXTENSION: &str = "
+    SELECT 1 FROM pragma_table_info('files')
+    WHERE name = 'extension'
+";
+
+pub const GET_ALL_FILE_NAMES: &str = "
+    SELECT path, name FROM files
+";
+
+pub const SET_FILE_EXTENSION: &str = "
+    UPDATE files
+    SET extension = ?1
+    WHERE path = ?2
+";
+
+pub const FILES_HAS_COMPRESSED: &str = "
+    SELECT 1 FROM pragma_table_info('files')
+    WHERE name = 'compressed'
+";
+
+pub const VACUUM: &str = "VACUUM";
+
+pub const VACUUM_INTO: &str = "VACUUM INTO ?1";
+
+pub const INTEGRITY_CHECK: &str = "PRAGMA integrity_check";
+
+pub const FOREIGN_KEY_CHECK: &str = "PRAGMA foreign_key_check";
+
+pub const GET_JOURNAL_MODE: &str = "PRAGMA journal_mode";
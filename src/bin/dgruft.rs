@@ -1,86 +1,932 @@
-use clap::Parser;
-use color_eyre::eyre::{self, eyre};
+use std::{
+    fs::File,
+    io::{self, Write},
+};
+
+use chrono::Local;
+use color_eyre::{
+    config::{HookBuilder, Theme},
+    eyre::{self, eyre},
+};
+use crossterm::style;
+use serde::Serialize;
+use serde_json::{json, Value};
 
 use dgruft::{
-    backend,
-    cli::{Cli, Commands},
+    backend::{
+        self, encrypted,
+        file::FileData,
+        password::{ExpiryStatus, Password, PasswordField},
+        CopiedValue,
+    },
+    cli::{self, output::Printer, Cli, Commands, OutputFormat},
+    config::{self, Config},
+    helpers::{self, password},
 };
 
-fn match_args(args: Cli) -> eyre::Result<()> {
-    let password = rpassword::prompt_password(format!("Password for {}: ", args.username))?;
-    match args.command {
+/// The output of every command when `--json` is given.
+#[derive(Serialize)]
+struct JsonOutput {
+    ok: bool,
+    data: Value,
+    error: Option<String>,
+}
+
+// Run the given command. Text output is printed as it happens; JSON output is returned as the
+// data of the [JsonOutput].
+fn match_args(args: Cli) -> eyre::Result<Value> {
+    let format = args.output_format();
+    let printer = args.printer();
+    let given_account = args.given_account();
+    config::init(
+        Config::load(args.config.as_deref())?
+            .with_env_vars()?
+            .with_paths(args.db_path.clone(), args.data_dir.clone()),
+    )?;
+    if let Commands::GeneratePassword {
+        length,
+        no_symbols,
+        no_digits,
+        no_uppercase,
+    } = args.command
+    {
+        let generated = password::generate_password(length, !no_symbols, !no_digits, !no_uppercase);
+        return emit(format, json!({ "password": &generated }), || {
+            println!("{generated}")
+        });
+    }
+    if let Commands::Completions { shell } = args.command {
+        // Complete the names of existing accounts, if there are any.
+        let accounts = backend::with_shared_lock(backend::list_accounts).unwrap_or_default();
+        let mut script = vec![];
+        clap_complete::generate(
+            shell,
+            &mut cli::completion_command(&accounts),
+            env!("CARGO_BIN_NAME"),
+            &mut script,
+        );
+        let script = String::from_utf8(script)?;
+        return emit(format, json!({ "script": &script }), || print!("{script}"));
+    }
+    if let Commands::ListAccounts { long } = args.command {
+        if long {
+            let accounts = backend::with_shared_lock(backend::list_account_summaries)?;
+            return emit(format, json!({ "accounts": &accounts }), || {
+                for account in &accounts {
+                    println!("{}", printer.account(&account.username));
+                    println!(
+                        "    created:    {}",
+                        helpers::format_datetime(account.created_at)
+                    );
+                    println!(
+                        "    last login: {}",
+                        account
+                            .last_login
+                            .map(helpers::format_datetime)
+                            .unwrap_or_else(|| "never".to_owned())
+                    );
+                }
+                println!("{} account(s).", accounts.len());
+            });
+        }
+        let accounts = backend::with_shared_lock(backend::list_accounts)?;
+        return emit(format, json!({ "accounts": &accounts }), || {
+            for account in &accounts {
+                println!("{}", printer.account(account));
+            }
+            println!("{} account(s).", accounts.len());
+        });
+    }
+    if let Commands::Backup { path } = args.command {
+        backend::with_shared_lock(|| backend::backup(&path))?;
+        return emit(format, json!({ "path": &path }), || {
+            println!("Database backed up to {path:?}.")
+        });
+    }
+    if let Commands::Import { path } = args.command {
+        let export_key =
+            encrypted::KeyMaterial::from_b64(&rpassword::prompt_password("Export key: ")?)?;
+        let password = rpassword::prompt_password("Account password: ")?;
+        let report =
+            backend::with_locked_file(|| backend::import_json(&path, &export_key, &password))?;
+        return emit(format, serde_json::to_value(&report)?, || {
+            println!(
+                "Imported {} account(s), {} password(s), and {} file(s). Skipped {} conflict(s).",
+                report.accounts_added,
+                report.credentials_added,
+                report.files_added,
+                report.conflicts_skipped
+            )
+        });
+    }
+    if let Commands::ImportKeepass { path } = args.command {
+        let account = resolve_account(given_account)?;
+        let password =
+            rpassword::prompt_password(format!("Password for {}: ", printer.account(&account)))?;
+        let report =
+            backend::with_locked_file(|| backend::import_keepass(&path, &account, &password))?;
+        return emit(format, serde_json::to_value(&report)?, || {
+            println!(
+                "Imported {} password(s). Skipped {} conflict(s).",
+                report.credentials_added, report.conflicts_skipped
+            )
+        });
+    }
+    if let Commands::ExportCsv {
+        mask_passwords,
+        output,
+    } = args.command
+    {
+        if format == OutputFormat::Json && output.is_none() {
+            return Err(eyre!("--output is required to export CSV with --json."));
+        }
+        let account = resolve_account(given_account)?;
+        let password =
+            rpassword::prompt_password(format!("Password for {}: ", printer.account(&account)))?;
+        eprintln!(
+            "{}",
+            printer.warning(
+                "WARNING: CSV output is NOT encrypted. Anyone who can read it can read your passwords."
+            )
+        );
+        backend::with_shared_lock(|| {
+            backend::export_credentials_csv(&account, &password, output.as_deref(), mask_passwords)
+        })?;
+        return Ok(json!({ "path": output }));
+    }
+    if let Commands::ImportCsv { path } = args.command {
+        let account = resolve_account(given_account)?;
+        let password =
+            rpassword::prompt_password(format!("Password for {}: ", printer.account(&account)))?;
+        let report = backend::with_locked_file(|| {
+            backend::import_credentials_csv(&account, &password, File::open(&path)?)
+        })?;
+        return emit(format, serde_json::to_value(&report)?, || {
+            println!(
+                "Imported {} password(s). Skipped {} conflict(s).",
+                report.credentials_added, report.conflicts_skipped
+            )
+        });
+    }
+    if let Commands::Check = args.command {
+        let problems = backend::with_shared_lock(backend::check)?;
+        if format == OutputFormat::Text {
+            for problem in &problems {
+                println!("{}", printer.error(problem));
+            }
+            println!("{} problem(s) found.", problems.len());
+        }
+        if !problems.is_empty() {
+            let mut message = String::from("Database check failed.");
+            if format == OutputFormat::Json {
+                for problem in &problems {
+                    message.push('\n');
+                    message.push_str(problem);
+                }
+            }
+            return Err(eyre!(message));
+        }
+        return Ok(json!({ "problems": problems }));
+    }
+    if let Commands::Status = args.command {
+        let status = backend::with_shared_lock(backend::status)?;
+        return emit(format, serde_json::to_value(&status)?, || {
+            println!("Database: {:?}", status.db_path);
+            println!("Size: {} bytes", status.db_size_bytes);
+            match status.modified_at {
+                Some(modified_at) => println!("Last modified: {modified_at} (Unix time)"),
+                None => println!("Last modified: unknown"),
+            }
+            println!("Schema version: {}", status.schema_version);
+            println!("WAL mode: {}", if status.wal_mode { "on" } else { "off" });
+            println!("Accounts: {}", status.accounts);
+            println!("Passwords: {}", status.credentials);
+            println!(
+                "Files: {} ({} bytes)",
+                status.files, status.files_size_bytes
+            );
+            if status.integrity_problems.is_empty() {
+                println!("Integrity: ok");
+            } else {
+                println!(
+                    "{}",
+                    printer.error(format!(
+                        "Integrity: {} problem(s) found. Run `check` for details.",
+                        status.integrity_problems.len()
+                    ))
+                );
+            }
+        });
+    }
+    if let Commands::Optimize { into } = args.command {
+        backend::with_locked_file(|| backend::optimize(into.as_deref()))?;
+        return emit(format, json!({ "into": &into }), || {
+            if let Some(into) = &into {
+                println!("Database backed up to {into:?}.");
+            }
+            println!("Database optimized.");
+        });
+    }
+
+    // Commands which prompt on stdin or open an editor can't print JSON. Account passwords are
+    // read from the terminal, so they don't get in the way.
+    if format == OutputFormat::Json && prompts_on_stdin(&args.command) {
+        return Err(eyre!("--json is not supported for this command."));
+    }
+
+    if let Commands::Init {
+        allow_weak_password,
+        kdf,
+    } = args.command
+    {
+        let config = config::get();
+        if config.db_path.exists() {
+            eprintln!(
+                "{}",
+                printer.warning(format!(
+                    "A vault already exists at {:?}. Nothing was changed.",
+                    config.db_path
+                ))
+            );
+            return Ok(Value::Null);
+        }
+        let username = resolve_account(given_account)?;
+        let password =
+            rpassword::prompt_password(format!("Password for {}: ", printer.account(&username)))?;
+        backend::with_locked_file(|| {
+            backend::init_vault(
+                username,
+                password,
+                allow_weak_password,
+                kdf.unwrap_or(config.kdf),
+            )
+        })?;
+        return emit(
+            format,
+            json!({ "db_path": &config.db_path, "data_dir": &config.data_dir }),
+            || {
+                println!("Vault created.");
+                println!("Database: {:?}", config.db_path);
+                println!("Data directory: {:?}", config.data_dir);
+            },
+        );
+    }
+
+    let username = resolve_account(given_account)?;
+    let password =
+        rpassword::prompt_password(format!("Password for {}: ", printer.account(&username)))?;
+    let read_only = match &args.command {
+        Commands::Files { list, .. } => *list,
+        Commands::Passwords {
+            list, export_env, ..
+        } => *list || !export_env.is_empty(),
+        Commands::Totp { set, .. } => !set,
+        Commands::Export { .. }
+        | Commands::PasswordShow { .. }
+        | Commands::PasswordHistory { .. }
+        | Commands::CopyPassword { .. }
+        | Commands::CopyUsername { .. }
+        | Commands::AuditPasswords
+        | Commands::AuditBreaches => true,
+        Commands::Account { .. }
+        | Commands::ChangePassword { .. }
+        | Commands::Init { .. }
+        | Commands::Backup { .. }
+        | Commands::Check
+        | Commands::Status
+        | Commands::Completions { .. }
+        | Commands::ExportCsv { .. }
+        | Commands::Import { .. }
+        | Commands::ImportCsv { .. }
+        | Commands::ImportKeepass { .. }
+        | Commands::ListAccounts { .. }
+        | Commands::Optimize { .. }
+        | Commands::GeneratePassword { .. } => false,
+    };
+    if read_only {
+        backend::with_shared_lock(|| run_command(args.command, username, password, format, printer))
+    } else {
+        backend::with_locked_file(|| run_command(args.command, username, password, format, printer))
+    }
+}
+
+// Print a command's result in text mode, and return its JSON data.
+fn emit(format: OutputFormat, data: Value, print_text: impl FnOnce()) -> eyre::Result<Value> {
+    if format == OutputFormat::Text {
+        print_text();
+    }
+    Ok(data)
+}
+
+// Return whether the given command prompts on stdin or opens an editor.
+fn prompts_on_stdin(command: &Commands) -> bool {
+    match command {
+        Commands::Account { .. } => true,
+        Commands::Files {
+            new, open, delete, ..
+        } => *new || *open || *delete,
+        Commands::Passwords {
+            new, open, delete, ..
+        } => *new || *open || *delete,
+        _ => false,
+    }
+}
+
+fn run_command(
+    command: Commands,
+    username: String,
+    password: String,
+    format: OutputFormat,
+    printer: Printer,
+) -> eyre::Result<Value> {
+    match command {
         Commands::Account {
             new,
             delete,
             force_delete,
+            change_password,
+            allow_weak_password,
+            kdf,
         } => {
             if new {
-                backend::new_account(args.username, password)?;
+                backend::new_account(
+                    username,
+                    password,
+                    allow_weak_password,
+                    kdf.unwrap_or(config::get().kdf),
+                )?;
             } else if delete {
-                backend::delete_account(args.username, password, false)?;
+                backend::delete_account(username, password, false)?;
             } else if force_delete {
-                backend::delete_account(args.username, password, true)?;
+                backend::delete_account(username, password, true)?;
+            } else if change_password {
+                change_account_password(
+                    &username,
+                    &password,
+                    allow_weak_password,
+                    format,
+                    printer,
+                )?;
             } else {
                 return Err(eyre!(
-                    "Impossible option combination: new, delete, force_delete all false."
+                    "Impossible option combination: new, delete, force_delete, change_password all false."
                 ));
             }
         }
+        Commands::ChangePassword {
+            allow_weak_password,
+        } => {
+            return change_account_password(
+                &username,
+                &password,
+                allow_weak_password,
+                format,
+                printer,
+            );
+        }
         Commands::Files {
             new,
             open,
             list,
+            long,
             delete,
             force_delete,
+            reencrypt,
+            export,
+            overwrite,
+            store,
+            compress,
             filename,
         } => {
             if new {
-                backend::new_file(args.username, password, filename.unwrap())?;
+                backend::new_file(username, password, filename.unwrap())?;
             } else if open {
-                backend::open_file(args.username, password, filename.unwrap())?;
+                backend::open_file(username, password, filename.unwrap())?;
             } else if list {
-                backend::list_files(args.username, password)?;
-            } else if delete {
-                backend::delete_file(args.username, password, filename.unwrap(), false)?;
-            } else if force_delete {
-                backend::delete_file(args.username, password, filename.unwrap(), true)?;
+                let files = backend::list_files(username, password)?;
+                return emit(format, files_json(&files), || print_files(&files, long));
+            } else if delete || force_delete {
+                let filename = filename.unwrap();
+                if backend::delete_file(username, password, filename.clone(), force_delete)? {
+                    return emit(
+                        format,
+                        json!({ "deleted": filename.to_string_lossy() }),
+                        || println!("File {filename:?} deleted successfully."),
+                    );
+                }
+            } else if reencrypt {
+                let filename = filename.unwrap();
+                backend::reencrypt_file(username, password, filename.clone())?;
+                return emit(
+                    format,
+                    json!({ "reencrypted": filename.to_string_lossy() }),
+                    || println!("File {filename:?} re-encrypted."),
+                );
+            } else if let Some(dest_dir) = export {
+                let report =
+                    backend::bulk_export_files(&username, &password, &dest_dir, overwrite)?;
+                for (path, err) in &report.failed {
+                    eprintln!(
+                        "{}",
+                        printer.error(format!("Failed to export {path:?}: {err}"))
+                    );
+                }
+                let data = json!({
+                    "exported": report.exported,
+                    "skipped": report.skipped,
+                    "failed": report.failed.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+                });
+                return emit(format, data, || {
+                    println!(
+                        "{} file(s) exported, {} skipped, {} failed.",
+                        report.exported,
+                        report.skipped,
+                        report.failed.len()
+                    )
+                });
+            } else if let Some(source_path) = store {
+                if compress {
+                    backend::store_file_compressed(&username, &password, &source_path)?;
+                } else {
+                    backend::store_file(&username, &password, &source_path)?;
+                }
+                let name = source_path.file_name().unwrap_or_default().to_owned();
+                return emit(format, json!({ "stored": name.to_string_lossy() }), || {
+                    println!("File {name:?} stored successfully.")
+                });
             } else {
                 return Err(eyre!(
-                    "Impossible option combination: new, open, list, delete, force_delete all false."
+                    "Impossible option combination: new, open, list, delete, force_delete, reencrypt, export, store all false."
                 ));
             }
         }
         Commands::Passwords {
             new,
             open,
+            allow_weak_password,
+            reveal,
+            url,
+            category,
+            expires,
+            show_expired,
+            show_expiring_soon,
+            long,
             list,
             delete,
             force_delete,
+            export_env,
+            import_env,
             passwordname,
         } => {
             if new {
-                backend::new_password(args.username, password, passwordname.unwrap())?;
+                backend::new_password(
+                    username,
+                    password,
+                    passwordname.unwrap(),
+                    allow_weak_password,
+                    url,
+                    category,
+                    expires,
+                )?;
             } else if open {
-                backend::open_password(args.username, password, passwordname.unwrap())?;
+                backend::open_password(
+                    username,
+                    password,
+                    passwordname.unwrap(),
+                    reveal,
+                    url,
+                    category,
+                    expires,
+                )?;
             } else if list {
-                backend::list_passwords(args.username, password)?;
-            } else if delete {
-                backend::delete_password(args.username, password, passwordname.unwrap(), false)?;
-            } else if force_delete {
-                backend::delete_password(args.username, password, passwordname.unwrap(), true)?;
+                let passwords = backend::list_passwords(
+                    username,
+                    password,
+                    category,
+                    show_expired,
+                    show_expiring_soon,
+                )?;
+                return emit(format, passwords_json(&passwords), || {
+                    print_passwords(&passwords, long, printer)
+                });
+            } else if delete || force_delete {
+                let passwordname = passwordname.unwrap();
+                if backend::delete_password(username, password, passwordname.clone(), force_delete)?
+                {
+                    return emit(
+                        format,
+                        json!({ "deleted": passwordname.to_string_lossy() }),
+                        || println!("Password {passwordname:?} deleted successfully."),
+                    );
+                }
+            } else if !export_env.is_empty() {
+                let mapping = parse_mapping(&export_env)?;
+                let script = backend::export_password_to_env(
+                    &username,
+                    &password,
+                    &passwordname.unwrap().to_string_lossy(),
+                    &mapping,
+                )?;
+                return emit(format, json!({ "script": &script }), || print!("{script}"));
+            } else if !import_env.is_empty() {
+                let mapping = parse_mapping(&import_env)?;
+                let passwordname = passwordname.unwrap();
+                backend::create_password_from_env(
+                    &username,
+                    &password,
+                    &passwordname.to_string_lossy(),
+                    &mapping,
+                )?;
+                return emit(
+                    format,
+                    json!({ "created": passwordname.to_string_lossy() }),
+                    || {
+                        println!(
+                            "Password {} created successfully.",
+                            printer.credential(format!("{passwordname:?}"))
+                        )
+                    },
+                );
             } else {
                 return Err(eyre!(
-                    "Impossible option combination: new, open, list, delete, force_delete all false."
+                    "Impossible option combination: new, open, list, delete, force_delete, exportenv, importenv all false."
                 ));
             }
         }
+        Commands::PasswordShow {
+            reveal,
+            field,
+            passwordname,
+        } => {
+            if let Some(field_name) = field {
+                let value =
+                    backend::show_password_field(&username, &password, &passwordname, &field_name)?;
+                return emit(
+                    format,
+                    json!({ "field": &field_name, "value": &value }),
+                    || println!("{value}"),
+                );
+            }
+            let details = backend::show_password(&username, &password, &passwordname, reveal)?;
+            return emit(format, serde_json::to_value(&details)?, || {
+                print!("{details}")
+            });
+        }
+        Commands::PasswordHistory {
+            reveal,
+            passwordname,
+        } => {
+            let history = backend::get_password_history(&username, &password, &passwordname)?;
+            let data: Vec<Value> = history
+                .iter()
+                .map(|(changed_at, content)| {
+                    json!({
+                        "changed_at": changed_at.timestamp(),
+                        "password": reveal.then_some(content),
+                    })
+                })
+                .collect();
+            return emit(format, data.into(), || {
+                for (changed_at, content) in &history {
+                    let content = if reveal { content.as_str() } else { "********" };
+                    println!("{}\t{content}", helpers::format_datetime(*changed_at));
+                }
+                println!(
+                    "{} former password(s) of {}.",
+                    history.len(),
+                    printer.credential(format!("{passwordname:?}"))
+                );
+            });
+        }
+        Commands::Totp { set, passwordname } => {
+            if set {
+                let b32_secret = rpassword::prompt_password("TOTP secret: ")?;
+                let has_secret =
+                    backend::set_totp_secret(&username, &password, &passwordname, &b32_secret)?;
+                return emit(format, json!({ "totp_secret_set": has_secret }), || {
+                    if has_secret {
+                        println!("TOTP secret of {passwordname:?} updated.");
+                    } else {
+                        println!("TOTP secret of {passwordname:?} removed.");
+                    }
+                });
+            } else {
+                let (code, seconds_remaining) =
+                    backend::generate_totp(&username, &password, &passwordname)?;
+                return emit(
+                    format,
+                    json!({ "code": &code, "seconds_remaining": seconds_remaining }),
+                    || println!("{code} (valid for {seconds_remaining}s)"),
+                );
+            }
+        }
+        Commands::CopyPassword { passwordname } => {
+            return copy_field(
+                &username,
+                &password,
+                &passwordname,
+                PasswordField::Content,
+                format,
+            );
+        }
+        Commands::CopyUsername { passwordname } => {
+            return copy_field(
+                &username,
+                &password,
+                &passwordname,
+                PasswordField::Username,
+                format,
+            );
+        }
+        Commands::AuditPasswords => {
+            let groups = backend::find_duplicate_passwords(&username, &password)?;
+            return emit(format, json!(groups), || {
+                for group in &groups {
+                    let names: Vec<String> = group
+                        .iter()
+                        .map(|name| printer.credential(format!("{name:?}")))
+                        .collect();
+                    println!("{}", names.join(", "));
+                }
+                println!("{} group(s) of passwords share content.", groups.len());
+            });
+        }
+        Commands::AuditBreaches => {
+            let results = backend::audit_breaches(&username, &password)?;
+            let data: Vec<Value> = results
+                .iter()
+                .map(|(name, count)| json!({ "name": name, "breaches": count }))
+                .collect();
+            return emit(format, data.into(), || {
+                for (name, count) in &results {
+                    let status = match count {
+                        Some(0) => "not found".to_owned(),
+                        Some(count) => format!("found in {count} breach(es)"),
+                        None => "not checked".to_owned(),
+                    };
+                    println!("{}\t{status}", printer.credential(format!("{name:?}")));
+                }
+                let breached = results
+                    .iter()
+                    .filter(|(_, count)| count.is_some_and(|count| count > 0))
+                    .count();
+                println!("{breached} breached password(s).");
+            });
+        }
+        Commands::Export { path } => {
+            let export_key = backend::export_json(&username, &password, &path)?;
+            eprintln!(
+                "Export key (needed to import): {}",
+                helpers::bytes_to_b64(export_key.as_bytes())
+            );
+            return emit(format, json!({ "path": &path }), || {
+                println!("Account exported to {path:?}.")
+            });
+        }
+        Commands::Init { .. }
+        | Commands::Backup { .. }
+        | Commands::Check
+        | Commands::Status
+        | Commands::Completions { .. }
+        | Commands::ExportCsv { .. }
+        | Commands::Import { .. }
+        | Commands::ImportCsv { .. }
+        | Commands::ImportKeepass { .. }
+        | Commands::ListAccounts { .. }
+        | Commands::Optimize { .. }
+        | Commands::GeneratePassword { .. } => {
+            return Err(eyre!("This command does not need an account."));
+        }
     };
-    Ok(())
+    Ok(Value::Null)
+}
+
+// Copy a field of a password to the clipboard, then wait until the clipboard has been cleared.
+// If the clipboard can't be accessed, the value is shown instead.
+fn copy_field(
+    username: &str,
+    password: &str,
+    passwordname: &str,
+    field: PasswordField,
+    format: OutputFormat,
+) -> eyre::Result<Value> {
+    let clear_thread = match backend::copy_to_clipboard(username, password, passwordname, field)? {
+        CopiedValue::Clipboard(clear_thread) => clear_thread,
+        CopiedValue::Unavailable(value) => {
+            return emit(format, json!({ "value": value.as_str() }), || {
+                println!("{}", value.as_str())
+            });
+        }
+    };
+    let clear_secs = config::get().clipboard_clear_secs;
+    let data = emit(format, json!({ "copied": field.to_string() }), || {
+        if clear_secs == 0 {
+            println!("Copied the {field} of {passwordname:?} to the clipboard.");
+        } else {
+            println!(
+                "Copied the {field} of {passwordname:?} to the clipboard. It is cleared in {clear_secs} seconds."
+            );
+        }
+    });
+    if let Some(clear_thread) = clear_thread {
+        clear_thread
+            .join()
+            .map_err(|_| eyre!("Could not clear the clipboard."))?;
+    }
+    data
+}
+
+// Prompt for a new account password twice, then change the account's password to it.
+fn change_account_password(
+    username: &str,
+    password: &str,
+    allow_weak_password: bool,
+    format: OutputFormat,
+    printer: Printer,
+) -> eyre::Result<Value> {
+    let new_password =
+        rpassword::prompt_password(format!("New Password for {}: ", printer.account(username)))?;
+    let confirm_password = rpassword::prompt_password(format!(
+        "Confirm New Password for {}: ",
+        printer.account(username)
+    ))?;
+    if confirm_password != new_password {
+        return Err(eyre!("New passwords do not match."));
+    }
+    let report =
+        backend::change_account_password(username, password, &new_password, allow_weak_password)?;
+    emit(format, serde_json::to_value(&report)?, || {
+        println!(
+            "Password for account \"{}\" changed successfully.",
+            printer.account(username)
+        );
+        println!(
+            "{} password(s) and {} file(s) unchanged, since the account key stays the same.",
+            report.credentials_unchanged, report.files_unchanged
+        );
+    })
+}
+
+// Return the given account, or else the configured default account, or else prompt for one.
+fn resolve_account(account: Option<String>) -> eyre::Result<String> {
+    if let Some(account) = account.or_else(|| config::get().default_account.clone()) {
+        return Ok(account);
+    }
+    // Prompt on stderr so the prompt doesn't end up in JSON output.
+    eprint!("Account: ");
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let account = input.trim();
+    if account.is_empty() {
+        return Err(eyre!("An account is required for this command."));
+    }
+    Ok(account.to_owned())
+}
+
+// Parse a list of `FIELD=VAR` arguments.
+fn parse_mapping(args: &[String]) -> eyre::Result<Vec<(&str, &str)>> {
+    args.iter()
+        .map(|arg| {
+            arg.split_once('=')
+                .ok_or_else(|| eyre!("Expected FIELD=VAR, got \"{arg}\"."))
+        })
+        .collect()
+}
+
+// Describe the given files for JSON output.
+fn files_json(files: &[FileData]) -> Value {
+    let files: Vec<Value> = files
+        .iter()
+        .map(|file| {
+            json!({
+                "name": file.name().to_string_lossy(),
+                "path": file.path(),
+                "size": file.file_size(),
+                "compressed": file.compressed(),
+                "extension": file.extension(),
+            })
+        })
+        .collect();
+    json!({ "files": files })
+}
+
+// Print the names and paths of the given files, followed by the number of files. Paths inside the
+// data directory are shown relative to it. If `long` is set, the full stored path, the content
+// nonce, the encrypted size, and the extension of each file are shown as well, and whether it is
+// compressed.
+fn print_files(files: &[FileData], long: bool) {
+    let data_dir = &config::get().data_dir;
+    for file in files {
+        let shown_path = file.path().strip_prefix(data_dir).unwrap_or(file.path());
+        println!(
+            "{}\t{}",
+            file.name().to_string_lossy(),
+            shown_path.display()
+        );
+        if long {
+            println!("    path:  {}", file.path().display());
+            println!("    nonce: {}", helpers::bytes_to_hex(file.content_nonce()));
+            if file.compressed() {
+                println!("    size:  {} bytes (compressed)", file.file_size());
+            } else {
+                println!("    size:  {} bytes", file.file_size());
+            }
+            println!("    type:  {}", file.extension().unwrap_or("none"));
+        }
+    }
+    println!("{} file(s).", files.len());
+}
+
+// Describe the given passwords for JSON output.
+fn passwords_json(passwords: &[(String, Password)]) -> Value {
+    let passwords: Vec<Value> = passwords
+        .iter()
+        .map(|(name, stored_password)| {
+            json!({
+                "name": name,
+                "category": stored_password.category(),
+                "expires_at": stored_password.expires_at().map(|expires_at| expires_at.to_string()),
+                "created_at": stored_password.created_at().timestamp(),
+                "updated_at": stored_password.updated_at().timestamp(),
+            })
+        })
+        .collect();
+    json!({ "passwords": passwords })
+}
+
+// Print the names and categories of the given passwords, followed by the number of passwords.
+// Passwords which expire soon are marked with ⚠, and expired passwords are marked with ✗. If
+// `long` is set, when each password was created and last changed is shown as well.
+fn print_passwords(passwords: &[(String, Password)], long: bool, printer: Printer) {
+    let today = Local::now().date_naive();
+    for (password_name, stored_password) in passwords {
+        let mut line = password_name.clone();
+        if let Some(password_category) = stored_password.category() {
+            line.push_str(&format!("\t{password_category}"));
+        }
+        match (
+            stored_password.expiry_status(today),
+            stored_password.expires_at(),
+        ) {
+            (ExpiryStatus::Expired, Some(expires_at)) => {
+                println!(
+                    "{line}\t{}",
+                    printer.error(format!("✗ expired {expires_at}"))
+                )
+            }
+            (ExpiryStatus::ExpiringSoon, Some(expires_at)) => {
+                println!(
+                    "{line}\t{}",
+                    printer.warning(format!("⚠ expires {expires_at}"))
+                )
+            }
+            _ => println!("{line}"),
+        }
+        if long {
+            println!(
+                "    created: {}",
+                helpers::format_datetime(stored_password.created_at())
+            );
+            println!(
+                "    updated: {}",
+                helpers::format_datetime(stored_password.updated_at())
+            );
+        }
+    }
+    println!("{} password(s).", passwords.len());
 }
 
 fn main() -> eyre::Result<()> {
-    color_eyre::install()?;
-    let args = Cli::parse();
-    match_args(args)?;
+    let args = Cli::parse_args();
+    if args.printer().color() {
+        color_eyre::install()?;
+    } else {
+        style::force_color_output(false);
+        HookBuilder::default().theme(Theme::new()).install()?;
+    }
+    match args.output_format() {
+        OutputFormat::Text => {
+            match_args(args)?;
+        }
+        OutputFormat::Json => {
+            let output = match match_args(args) {
+                Ok(data) => JsonOutput {
+                    ok: true,
+                    data,
+                    error: None,
+                },
+                Err(err) => JsonOutput {
+                    ok: false,
+                    data: Value::Null,
+                    error: Some(format!("{err:#}")),
+                },
+            };
+            println!("{}", serde_json::to_string(&output)?);
+            if !output.ok {
+                std::process::exit(1);
+            }
+        }
+    }
     Ok(())
 }
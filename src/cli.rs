@@ -1,31 +1,156 @@
 //! Functionality related to the command line.
-use std::ffi::OsString;
+use std::{ffi::OsString, path::PathBuf};
 
-use clap::{ArgGroup, Parser, Subcommand};
+use clap::{
+    builder::PossibleValuesParser, error::ErrorKind, ArgGroup, Command, CommandFactory, Parser,
+    Subcommand,
+};
+use clap_complete::Shell;
 
-use crate::helpers;
+use crate::{backend::account::KdfAlgorithm, helpers};
+
+pub mod output;
+
+use output::Printer;
+
+const CONFIGURATION_HELP: &str = "\
+Configuration:
+  Each setting is taken from the first of these places where it is set:
+    1. Command-line flags: --db-path, --data-dir, --account
+    2. Environment variables: DGRUFT_DB_PATH, DGRUFT_DATA_DIR, DGRUFT_DEFAULT_ACCOUNT
+    3. The configuration file: db_path, data_dir, default_account
+    4. The platform defaults
+
+  The configuration file is read from the platform's configuration directory, which
+  DGRUFT_CONFIG overrides.
+
+  DGRUFT_DATA, the former name of DGRUFT_DATA_DIR, is still read if DGRUFT_DATA_DIR isn't set.
+
+  If a data directory is set but no database path is set anywhere, the database is stored
+  inside the data directory.";
 
 /// The command-line interface.
 #[derive(Parser, Debug)]
 #[command(author, version = helpers::version(), about = "Encrypted storage for passwords and data.")]
+#[command(after_help = CONFIGURATION_HELP)]
 pub struct Cli {
-    /// Account username.
-    pub username: String,
+    /// Account username. Same as `--account`, which can't be given as well.
+    #[clap(conflicts_with = "account")]
+    pub username: Option<String>,
+    /// The account to use. Defaults to `default_account` from the configuration file, or is
+    /// prompted for. Used by every command except `backup`, `check`, `completions`, `import`,
+    /// `list-accounts`, `optimize`, `status`, and `generate-password`.
+    #[clap(short, long, global = true, value_name = "NAME")]
+    pub account: Option<String>,
+    /// Print the result as a JSON object `{"ok": bool, "data": ..., "error": null | string}`. Not
+    /// supported by `account`, nor by commands which prompt for input or open an editor.
+    #[clap(long, global = true)]
+    pub json: bool,
+    /// Read the configuration from this file instead of the default configuration file.
+    #[clap(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Use the SQLite database at this path, overriding the configuration file.
+    #[clap(long = "db-path", global = true, value_name = "PATH")]
+    pub db_path: Option<PathBuf>,
+    /// Store account files in this directory, overriding the configuration file. Unless a
+    /// database path is set with `--db-path` or elsewhere, the database is stored inside it too.
+    #[clap(long = "data-dir", global = true, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+    /// Don't color the output. Also disabled by setting the `NO_COLOR` environment variable.
+    #[clap(long = "nocolor", alias = "no-color", global = true)]
+    pub no_color: bool,
     /// All the possible commands the user can give CLI `dgruft`.
     #[command(subcommand)]
     pub command: Commands,
 }
 
+impl Cli {
+    /// Parse the command-line arguments of this process, exiting with a usage error if they are
+    /// invalid.
+    pub fn parse_args() -> Self {
+        Self::try_parse_args_from(std::env::args_os()).unwrap_or_else(|err| err.exit())
+    }
+
+    /// Parse the given command-line arguments.
+    pub fn try_parse_args_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let cli = Self::try_parse_from(args)?;
+        // clap doesn't check global arguments given after the subcommand for conflicts.
+        if cli.username.is_some() && cli.account.is_some() {
+            return Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                "the argument '--account <NAME>' cannot be used with '[USERNAME]'",
+            ));
+        }
+        Ok(cli)
+    }
+
+    /// Return the [OutputFormat] requested by the user.
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Text
+        }
+    }
+
+    /// Return the account given with `--account` or as the username argument, if any.
+    pub fn given_account(&self) -> Option<String> {
+        self.account.clone().or_else(|| self.username.clone())
+    }
+
+    /// Return a [Printer] coloring output as requested by the user. JSON output is never colored.
+    pub fn printer(&self) -> Printer {
+        Printer::from_env(self.no_color || self.json)
+    }
+}
+
+/// Return the [Command] describing `dgruft`, for generating shell completions. The given account
+/// names are completed wherever an account is expected.
+pub fn completion_command(accounts: &[String]) -> Command {
+    let command = Cli::command();
+    if accounts.is_empty() {
+        return command;
+    }
+    let account_parser = PossibleValuesParser::new(accounts.iter().cloned());
+    command
+        .mut_arg("username", |arg| arg.value_parser(account_parser.clone()))
+        .mut_arg("account", |arg| arg.value_parser(account_parser))
+}
+
+/// How `dgruft` prints the results of commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    Text,
+    /// A single JSON object, for scripts.
+    Json,
+}
+
 /// All the possible commands the user can give CLI `dgruft`.
 #[derive(Debug, Subcommand)]
 pub enum Commands {
+    /// Create the data directory and database of a new vault, and its first account.
+    Init {
+        /// Accept a weak account password.
+        #[clap(long = "allowweakpassword")]
+        allow_weak_password: bool,
+        /// The key derivation function used for the account's password. See `account --kdf`.
+        #[clap(long, value_name = "KDF")]
+        kdf: Option<KdfAlgorithm>,
+    },
+
     /// Manage accounts.
     #[command(arg_required_else_help = true)]
     #[clap(group(
-            ArgGroup::new("account")
+            ArgGroup::new("account_action")
                 .required(true)           
-                .args(&["new", "delete", "force_delete"])
+                .args(&["new", "delete", "force_delete", "change_password"])
     ))]
+    #[clap(group(ArgGroup::new("sets_password").args(&["new", "change_password"])))]
     Account {
         /// Add the account.
         #[clap(short, long)]
@@ -36,6 +161,24 @@ pub enum Commands {
         /// Delete the account without confirmation.
         #[clap(short = 'D', long = "deleteforce")]
         force_delete: bool,
+        /// Change the account password.
+        #[clap(short = 'c', long = "changepassword")]
+        change_password: bool,
+        /// Accept a weak account password when creating the account or changing its password.
+        #[clap(long = "allowweakpassword", requires = "sets_password")]
+        allow_weak_password: bool,
+        /// The key derivation function used for the new account's password: `argon2id`, `scrypt`,
+        /// `pbkdf2-sha256`, or explicit parameters like `scrypt$n=131072,r=8,p=1`. Defaults to
+        /// `kdf` from the configuration file, or `argon2id`.
+        #[clap(long, value_name = "KDF", requires = "new")]
+        kdf: Option<KdfAlgorithm>,
+    },
+
+    /// Change the account password. Same as `account --changepassword`.
+    ChangePassword {
+        /// Accept a weak new password.
+        #[clap(long = "allowweakpassword")]
+        allow_weak_password: bool,
     },
 
     /// Manage files.
@@ -45,24 +188,42 @@ pub enum Commands {
     #[clap(group(
             ArgGroup::new("file")
                 .required(true)           
-                .args(&["new", "open", "list", "delete", "force_delete"])
+                .args(&["new", "open", "list", "delete", "force_delete", "reencrypt", "export", "store"])
     ))]
     Files {
         /// Create the file.
-        #[clap(short, long, requires="filename")]
+        #[clap(short, long, requires = "filename")]
         new: bool,
         /// Open the file.
-        #[clap(short, long, requires="filename")]
+        #[clap(short, long, requires = "filename")]
         open: bool,
         /// List all files owned by this account.
         #[clap(short, long)]
         list: bool,
+        /// Also show the full path, nonce, size, and extension of each file when listing.
+        #[clap(long, requires = "list")]
+        long: bool,
         /// Delete the file.
-        #[clap(short = 'd', long = "delete", requires="filename")]
+        #[clap(short = 'd', long = "delete", requires = "filename")]
         delete: bool,
         /// Delete the file without confirmation.
-        #[clap(short = 'D', long = "forcedelete", requires="filename")]
+        #[clap(short = 'D', long = "forcedelete", requires = "filename")]
         force_delete: bool,
+        /// Re-encrypt the file with a fresh nonce.
+        #[clap(short = 'r', long, requires = "filename")]
+        reencrypt: bool,
+        /// Decrypt all files owned by this account into the given directory.
+        #[clap(short = 'x', long, value_name = "DIR")]
+        export: Option<PathBuf>,
+        /// Overwrite existing files when exporting.
+        #[clap(long, requires = "export")]
+        overwrite: bool,
+        /// Encrypt a copy of the given plaintext file and store it under the same name.
+        #[clap(short, long, value_name = "PATH")]
+        store: Option<PathBuf>,
+        /// Compress the file before encrypting it when storing. Best for text-heavy files.
+        #[clap(long, requires = "store")]
+        compress: bool,
         /// The name of the file.
         filename: Option<OsString>,
     },
@@ -74,25 +235,298 @@ pub enum Commands {
     #[clap(group(
             ArgGroup::new("password")
                 .required(true)           
-                .args(&["new", "open", "list", "delete", "force_delete"])
+                .args(&["new", "open", "list", "delete", "force_delete", "export_env", "import_env"])
     ))]
     Passwords {
         /// Create the password.
-        #[clap(short, long, requires="passwordname")]
+        #[clap(short, long, requires = "passwordname")]
         new: bool,
         /// Open the password.
-        #[clap(short, long, requires="passwordname")]
+        #[clap(short, long, requires = "passwordname")]
         open: bool,
+        /// Accept weak password content when creating the password.
+        #[clap(long = "allowweakpassword", requires = "new")]
+        allow_weak_password: bool,
+        /// Show the password content in plain text when opening the password.
+        #[clap(long, requires = "open")]
+        reveal: bool,
+        /// The URL of the site the password is for, when creating or opening the password. An
+        /// empty URL removes the password's URL. Prompted for if not given.
+        #[clap(long, value_name = "URL", requires = "passwordname")]
+        url: Option<String>,
+        /// The category of the password when creating or opening it, e.g. `banking`, or the
+        /// category of the passwords to list. Separate multiple categories with commas. An empty
+        /// category removes the password's category.
+        #[clap(long, value_name = "CATEGORY")]
+        category: Option<String>,
+        /// The date after which the password should be changed, as `YYYY-MM-DD`, when creating or
+        /// opening the password. An empty date removes the password's expiry date.
+        #[clap(long, value_name = "DATE", requires = "passwordname")]
+        expires: Option<String>,
+        /// Only list passwords which have expired.
+        #[clap(long = "showexpired", alias = "show-expired", requires = "list")]
+        show_expired: bool,
+        /// Only list passwords which expire within 30 days.
+        #[clap(
+            long = "showexpiringsoon",
+            alias = "show-expiring-soon",
+            requires = "list"
+        )]
+        show_expiring_soon: bool,
+        /// Also show when each password was created and last changed when listing.
+        #[clap(long, requires = "list")]
+        long: bool,
         /// List all passwords owned by this account.
         #[clap(short, long)]
         list: bool,
         /// Delete the password.
-        #[clap(short = 'd', long = "delete", requires="passwordname")]
+        #[clap(short = 'd', long = "delete", requires = "passwordname")]
         delete: bool,
         /// Delete the password without confirmation.
-        #[clap(short = 'D', long = "forcedelete", requires="passwordname")]
+        #[clap(short = 'D', long = "forcedelete", requires = "passwordname")]
         force_delete: bool,
+        /// Print shell statements exporting the given fields of the password as environment
+        /// variables, e.g. `username=DB_USER password=DB_PASS`.
+        #[clap(
+            long = "exportenv",
+            value_name = "FIELD=VAR",
+            num_args = 1..,
+            requires = "passwordname"
+        )]
+        export_env: Vec<String>,
+        /// Create the password from the values of the given environment variables, e.g.
+        /// `username=DB_USER password=DB_PASS`.
+        #[clap(
+            long = "importenv",
+            value_name = "FIELD=VAR",
+            num_args = 1..,
+            requires = "passwordname"
+        )]
+        import_env: Vec<String>,
         /// The name of the password.
         passwordname: Option<OsString>,
     },
+
+    /// Show all fields of a password without changing it. The password content is masked unless
+    /// `--reveal` is given.
+    #[command(alias = "credential-show")]
+    PasswordShow {
+        /// Show the password content in plain text.
+        #[clap(long)]
+        reveal: bool,
+        /// Only print the given field, unmasked, e.g. for use in scripts: `name`, `username`,
+        /// `password`, `notes`, or `url`.
+        #[clap(long, value_name = "FIELD", conflicts_with = "reveal")]
+        field: Option<String>,
+        /// The name of the password.
+        passwordname: String,
+    },
+
+    /// Show the former contents of a password, newest first. Only the last 20 are kept.
+    #[command(alias = "credential-history")]
+    PasswordHistory {
+        /// Show the former contents in plain text.
+        #[clap(long)]
+        reveal: bool,
+        /// The name of the password.
+        passwordname: String,
+    },
+
+    /// Show the current TOTP code of a password and how long it stays valid, or set the
+    /// password's TOTP secret.
+    Totp {
+        /// Prompt for a base-32 secret and set it as the TOTP secret of the password instead.
+        /// An empty secret removes the password's TOTP secret.
+        #[clap(long)]
+        set: bool,
+        /// The name of the password.
+        passwordname: String,
+    },
+
+    /// Copy the content of a password to the clipboard without showing it. The clipboard is
+    /// cleared again after 30 seconds, unless configured otherwise.
+    CopyPassword {
+        /// The name of the password.
+        passwordname: String,
+    },
+
+    /// Copy the username of a password to the clipboard without showing it. The clipboard is
+    /// cleared again after 30 seconds, unless configured otherwise.
+    CopyUsername {
+        /// The name of the password.
+        passwordname: String,
+    },
+
+    /// List groups of passwords which share the same content.
+    AuditPasswords,
+
+    /// Check all passwords against the "Have I Been Pwned" database of breached passwords. Only
+    /// the first five characters of the SHA-1 hash of each password are sent.
+    AuditBreaches,
+
+    /// Export the account with all of its passwords and files to an encrypted file. The key needed
+    /// to import it again is printed to stderr.
+    Export {
+        /// Path of the export. Must not already exist.
+        path: PathBuf,
+    },
+
+    /// Import accounts, passwords, and files from a file written by `export`. Prompts for the
+    /// export key and the password of the exported account.
+    Import {
+        /// Path of the export.
+        path: PathBuf,
+    },
+
+    /// Import the entries of an unencrypted KeePass 2.x XML export as passwords.
+    ImportKeepass {
+        /// Path of the KeePass XML export.
+        path: PathBuf,
+    },
+
+    /// Write all passwords of an account as unencrypted CSV.
+    ExportCsv {
+        /// Replace password content with asterisks.
+        #[clap(long = "maskpasswords", alias = "mask-passwords")]
+        mask_passwords: bool,
+        /// Write to this file instead of stdout. Must not already exist.
+        #[clap(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Create passwords from the rows of a CSV file with the columns
+    /// `name,username,password,notes,url,category,expires_at`.
+    ImportCsv {
+        /// Path of the CSV file.
+        path: PathBuf,
+    },
+
+    /// List the usernames of all accounts.
+    ListAccounts {
+        /// Also show when each account was created and last logged in.
+        #[clap(long)]
+        long: bool,
+    },
+
+    /// Write a snapshot of the database to the given path.
+    Backup {
+        /// Path of the snapshot. Any database already at this path is overwritten.
+        path: PathBuf,
+    },
+
+    /// Check the database for corruption and inconsistencies.
+    Check,
+
+    /// Show the location, size, and contents of the database, and whether it is healthy.
+    Status,
+
+    /// Reclaim unused space in the database.
+    Optimize {
+        /// Also write a compacted copy of the database to this path.
+        #[clap(long, value_name = "PATH")]
+        into: Option<PathBuf>,
+    },
+
+    /// Print a shell completion script.
+    Completions {
+        /// The shell to complete commands for.
+        shell: Shell,
+    },
+
+    /// Print a random password.
+    #[command(alias = "g")]
+    GeneratePassword {
+        /// The length of the password.
+        #[clap(short, long, default_value_t = 20)]
+        length: usize,
+        /// Leave symbols out of the password.
+        #[clap(long)]
+        no_symbols: bool,
+        /// Leave digits out of the password.
+        #[clap(long)]
+        no_digits: bool,
+        /// Leave uppercase letters out of the password.
+        #[clap(long)]
+        no_uppercase: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_completion_command() {
+        let accounts = vec![String::from("alice"), String::from("bob")];
+        let command = completion_command(&accounts);
+
+        let possible_account_names = |arg: &clap::Arg| -> Vec<String> {
+            arg.get_possible_values()
+                .iter()
+                .map(|value| value.get_name().to_owned())
+                .collect()
+        };
+        let username_arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == "username")
+            .unwrap();
+        assert_eq!(possible_account_names(username_arg), accounts);
+        let account_arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == "account")
+            .unwrap();
+        assert_eq!(possible_account_names(account_arg), accounts);
+
+        let no_accounts_command = completion_command(&[]);
+        let username_arg = no_accounts_command
+            .get_arguments()
+            .find(|arg| arg.get_id() == "username")
+            .unwrap();
+        assert!(possible_account_names(username_arg).is_empty());
+    }
+
+    #[test]
+    fn test_allow_weak_password() {
+        let parses = |args: &[&str]| Cli::try_parse_args_from(args).is_ok();
+        assert!(parses(&[
+            "dgruft",
+            "account",
+            "--new",
+            "--allowweakpassword"
+        ]));
+        assert!(parses(&["dgruft", "account", "-c", "--allowweakpassword"]));
+        assert!(!parses(&[
+            "dgruft",
+            "account",
+            "--delete",
+            "--allowweakpassword"
+        ]));
+        assert!(!parses(&["dgruft", "account", "-D", "--allowweakpassword"]));
+    }
+
+    #[test]
+    fn test_given_account() {
+        let given_account = |args: &[&str]| Cli::try_parse_args_from(args).unwrap().given_account();
+        assert_eq!(given_account(&["dgruft", "list-accounts"]), None);
+        assert_eq!(
+            given_account(&["dgruft", "alice", "files", "--list"]),
+            Some(String::from("alice"))
+        );
+        assert_eq!(
+            given_account(&["dgruft", "files", "--list", "-a", "bob"]),
+            Some(String::from("bob"))
+        );
+        for args in [
+            &["dgruft", "--account", "bob", "alice", "files", "--list"][..],
+            &["dgruft", "alice", "files", "--list", "-a", "bob"][..],
+        ] {
+            assert!(Cli::try_parse_args_from(args).is_err(), "{args:?}");
+        }
+    }
 }
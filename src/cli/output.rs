@@ -0,0 +1,96 @@
+//! Consistent formatting of command-line output.
+use std::{env, fmt::Display};
+
+use crossterm::style::Stylize;
+
+/// Formats the parts of command-line output which are highlighted: account names in cyan,
+/// password names in green, errors in red, and warnings in yellow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Printer {
+    color: bool,
+}
+impl Printer {
+    /// Create a new [Printer] which colors its output if `color` is set.
+    pub fn new(color: bool) -> Self {
+        Self { color }
+    }
+
+    /// Create a new [Printer] which colors its output unless `no_color` is set or the `NO_COLOR`
+    /// environment variable is set to a non-empty value.
+    pub fn from_env(no_color: bool) -> Self {
+        let no_color_env = env::var("NO_COLOR").is_ok_and(|value| !value.is_empty());
+        Self::new(!no_color && !no_color_env)
+    }
+
+    /// Return whether this [Printer] colors its output.
+    pub fn color(&self) -> bool {
+        self.color
+    }
+
+    /// Format an account name.
+    pub fn account(&self, name: impl Display) -> String {
+        if self.color {
+            name.to_string().cyan().to_string()
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Format the name of a stored password.
+    pub fn credential(&self, name: impl Display) -> String {
+        if self.color {
+            name.to_string().green().to_string()
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Format an error message.
+    pub fn error(&self, message: impl Display) -> String {
+        if self.color {
+            message.to_string().red().to_string()
+        } else {
+            message.to_string()
+        }
+    }
+
+    /// Format a warning.
+    pub fn warning(&self, message: impl Display) -> String {
+        if self.color {
+            message.to_string().yellow().bold().to_string()
+        } else {
+            message.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_no_color() {
+        let printer = Printer::new(false);
+        assert_eq!(printer.account("my_account"), "my_account");
+        assert_eq!(printer.credential("my_password"), "my_password");
+        assert_eq!(printer.error("oh no"), "oh no");
+        assert_eq!(printer.warning("careful"), "careful");
+    }
+
+    #[test]
+    fn test_color() {
+        crossterm::style::force_color_output(true);
+        let printer = Printer::new(true);
+        for formatted in [
+            printer.account("my_account"),
+            printer.credential("my_password"),
+            printer.error("oh no"),
+            printer.warning("careful"),
+        ] {
+            assert!(formatted.starts_with('\x1b'));
+        }
+        assert!(printer.account("my_account").contains("my_account"));
+        assert_ne!(printer.account("x"), printer.credential("x"));
+    }
+}
@@ -0,0 +1,460 @@
+//! Loading of the `dgruft` TOML configuration file.
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use color_eyre::eyre::{self, eyre};
+use directories::{BaseDirs, ProjectDirs};
+use serde::Deserialize;
+
+use crate::{backend::account::KdfAlgorithm, helpers};
+
+/// Name of the configuration file inside the configuration directory.
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Name of the database file inside the data directory, unless configured otherwise.
+pub const DATABASE_NAME: &str = "dgruft.db";
+
+/// Name of the `dgruft` directory inside the platform's data and configuration directories.
+pub const DIRECTORY_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Number of seconds after which copied values are cleared from the clipboard, unless configured
+/// otherwise.
+pub const DEFAULT_CLIPBOARD_CLEAR_SECS: u64 = 30;
+
+/// Size in bytes from which files are encrypted in chunks, unless configured otherwise.
+pub const DEFAULT_STREAM_THRESHOLD_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Environment variable overriding the database path of the configuration file.
+pub const DB_PATH_ENV_VAR: &str = "DGRUFT_DB_PATH";
+
+/// Environment variable overriding the data directory of the configuration file.
+pub const DATA_DIR_ENV_VAR: &str = "DGRUFT_DATA_DIR";
+
+/// Former name of [DATA_DIR_ENV_VAR], still read if [DATA_DIR_ENV_VAR] isn't set.
+pub const LEGACY_DATA_DIR_ENV_VAR: &str = "DGRUFT_DATA";
+
+/// Environment variable overriding the default account of the configuration file.
+pub const DEFAULT_ACCOUNT_ENV_VAR: &str = "DGRUFT_DEFAULT_ACCOUNT";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The default locations of `dgruft` data and configuration, following the conventions of the
+/// current platform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paths {
+    /// Directory in which account files are stored.
+    pub data_dir: PathBuf,
+    /// Path of the SQLite database.
+    pub db_path: PathBuf,
+    /// Directory in which the configuration file is stored.
+    pub config_dir: PathBuf,
+}
+impl Paths {
+    /// Resolve the default paths for the current platform.
+    ///
+    /// The data directory is `$XDG_DATA_HOME/dgruft` on Linux,
+    /// `~/Library/Application Support/dgruft` on macOS and `%APPDATA%\dgruft` on Windows. The
+    /// configuration directory is found the same way using the platform's configuration directory,
+    /// and can be overridden with the `DGRUFT_CONFIG` environment variable. The data directory is
+    /// overridden with [DATA_DIR_ENV_VAR] by [Config::with_env_vars] instead.
+    ///
+    /// Earlier versions of `dgruft` used the platform's local project directories instead, which
+    /// differ on macOS and Windows. If such a directory exists and the new one doesn't, it is
+    /// used instead so that existing vaults are still found.
+    ///
+    /// Fails if a directory is not overridden and the user's home directory cannot be found.
+    pub fn resolve() -> eyre::Result<Self> {
+        let base_dirs = BaseDirs::new();
+        let base_dir = |get_dir: fn(&BaseDirs) -> &Path| {
+            base_dirs
+                .as_ref()
+                .map(|base_dirs| get_dir(base_dirs).join(DIRECTORY_NAME))
+                .ok_or_else(|| eyre!("Could not find the home directory."))
+        };
+        let legacy_dirs = ProjectDirs::from("ca", "maxgmr", DIRECTORY_NAME);
+        let data_dir = existing_dir(
+            base_dir(BaseDirs::data_dir)?,
+            legacy_dirs.as_ref().map(ProjectDirs::data_local_dir),
+        );
+        let config_dir = match helpers::config_folder_env_var_name() {
+            Some(config_dir) => config_dir,
+            None => existing_dir(
+                base_dir(BaseDirs::config_dir)?,
+                legacy_dirs.as_ref().map(ProjectDirs::config_local_dir),
+            ),
+        };
+        Ok(Self {
+            db_path: data_dir.join(DATABASE_NAME),
+            data_dir,
+            config_dir,
+        })
+    }
+
+    /// Resolve the default paths with [Paths::resolve]. If they can't be resolved, fall back to
+    /// the `.data` and `.config` directories inside the current working directory.
+    pub fn resolve_or_fallback() -> Self {
+        Self::resolve().unwrap_or_else(|_| {
+            let data_dir = PathBuf::from(".").join(".data");
+            Self {
+                db_path: data_dir.join(DATABASE_NAME),
+                data_dir,
+                config_dir: PathBuf::from(".").join(".config"),
+            }
+        })
+    }
+
+    /// Return the path of the configuration file used when none is given.
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir.join(CONFIG_FILE_NAME)
+    }
+}
+
+// Return the legacy directory if only it exists, otherwise the given directory.
+fn existing_dir(dir: PathBuf, legacy_dir: Option<&Path>) -> PathBuf {
+    match legacy_dir {
+        Some(legacy_dir) if !dir.exists() && legacy_dir.exists() => legacy_dir.to_path_buf(),
+        _ => dir,
+    }
+}
+
+/// The resolved `dgruft` configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Path of the SQLite database.
+    pub db_path: PathBuf,
+    /// Directory in which account files are stored.
+    pub data_dir: PathBuf,
+    /// Account used by commands when no username is given.
+    pub default_account: Option<String>,
+    /// Key derivation function of new accounts, unless chosen with `--kdf`.
+    pub kdf: KdfAlgorithm,
+    /// Number of seconds after which copied values are cleared from the clipboard. 0 means they
+    /// are never cleared.
+    pub clipboard_clear_secs: u64,
+    /// Size in bytes from which files are encrypted in chunks rather than all at once.
+    pub stream_threshold_bytes: u64,
+    // Whether `db_path` was set explicitly rather than derived from a data directory.
+    db_path_is_set: bool,
+}
+impl Config {
+    /// Create the default configuration, using the given default paths.
+    pub fn new(paths: &Paths) -> Self {
+        Self {
+            db_path: paths.db_path.clone(),
+            data_dir: paths.data_dir.clone(),
+            default_account: None,
+            kdf: KdfAlgorithm::default(),
+            clipboard_clear_secs: DEFAULT_CLIPBOARD_CLEAR_SECS,
+            stream_threshold_bytes: DEFAULT_STREAM_THRESHOLD_BYTES,
+            db_path_is_set: false,
+        }
+    }
+
+    /// Load the configuration file at the given path, or at [Paths::config_path] if no path is
+    /// given. Settings missing from the file keep their default values.
+    ///
+    /// A missing file at the default path is not an error, but a missing file at a given path is.
+    pub fn load(path: Option<&Path>) -> eyre::Result<Self> {
+        let paths = Paths::resolve_or_fallback();
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => (paths.config_path(), false),
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::from_toml(&contents, &paths)
+                .map_err(|err| eyre!("Invalid configuration file {path:?}: {err}")),
+            Err(err) if err.kind() == ErrorKind::NotFound && !required => Ok(Self::new(&paths)),
+            Err(err) => Err(eyre!("Could not read configuration file {path:?}: {err}")),
+        }
+    }
+
+    /// Override settings with the values of the [DB_PATH_ENV_VAR], [DATA_DIR_ENV_VAR] (or else
+    /// [LEGACY_DATA_DIR_ENV_VAR]), and [DEFAULT_ACCOUNT_ENV_VAR] environment variables, if they are
+    /// set.
+    pub fn with_env_vars(self) -> eyre::Result<Self> {
+        self.with_vars(|name| env::var_os(name))
+    }
+
+    // Override settings with the variables returned by `var`.
+    fn with_vars(mut self, var: impl Fn(&str) -> Option<OsString>) -> eyre::Result<Self> {
+        if let Some(default_account) = var(DEFAULT_ACCOUNT_ENV_VAR) {
+            self.default_account = Some(default_account.into_string().map_err(|_| {
+                eyre!("The environment variable {DEFAULT_ACCOUNT_ENV_VAR} is not valid UTF-8.")
+            })?);
+        }
+        Ok(self.with_paths(
+            var(DB_PATH_ENV_VAR).map(PathBuf::from),
+            var(DATA_DIR_ENV_VAR)
+                .or_else(|| var(LEGACY_DATA_DIR_ENV_VAR))
+                .map(PathBuf::from),
+        ))
+    }
+
+    /// Override the database path and data directory, e.g. with paths given on the command line.
+    /// If `data_dir` is given but no database path has been set anywhere, the database is stored
+    /// inside it.
+    pub fn with_paths(mut self, db_path: Option<PathBuf>, data_dir: Option<PathBuf>) -> Self {
+        if let Some(data_dir) = data_dir {
+            if !self.db_path_is_set {
+                self.db_path = data_dir.join(DATABASE_NAME);
+            }
+            self.data_dir = data_dir;
+        }
+        if let Some(db_path) = db_path {
+            self.db_path = db_path;
+            self.db_path_is_set = true;
+        }
+        self
+    }
+
+    /// Parse a configuration from the contents of a TOML file. Settings missing from the file keep
+    /// the default values from the given paths. If only `data_dir` is set, the database is stored
+    /// inside it.
+    pub fn from_toml(contents: &str, paths: &Paths) -> eyre::Result<Self> {
+        let file: ConfigFile = toml::from_str(contents)?;
+        let db_path = match (&file.db_path, &file.data_dir) {
+            (Some(db_path), _) => db_path.clone(),
+            (None, Some(data_dir)) => data_dir.join(DATABASE_NAME),
+            (None, None) => paths.db_path.clone(),
+        };
+        Ok(Self {
+            db_path,
+            db_path_is_set: file.db_path.is_some(),
+            data_dir: file.data_dir.unwrap_or_else(|| paths.data_dir.clone()),
+            default_account: file.default_account,
+            kdf: match file.kdf {
+                Some(kdf) => KdfAlgorithm::parse(&kdf)?,
+                None => KdfAlgorithm::default(),
+            },
+            clipboard_clear_secs: file
+                .clipboard_clear_secs
+                .unwrap_or(DEFAULT_CLIPBOARD_CLEAR_SECS),
+            stream_threshold_bytes: file
+                .stream_threshold_bytes
+                .unwrap_or(DEFAULT_STREAM_THRESHOLD_BYTES),
+        })
+    }
+}
+
+/// Set the configuration used by the rest of the program. Fails if the configuration has already
+/// been set or used.
+pub fn init(config: Config) -> eyre::Result<()> {
+    CONFIG
+        .set(config)
+        .map_err(|_| eyre!("The configuration has already been set."))
+}
+
+/// Return the configuration used by the rest of the program. If [init] has not been called, the
+/// default configuration is used, with the paths of [Paths::resolve_or_fallback].
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| Config::new(&Paths::resolve_or_fallback()))
+}
+
+// The configuration file as written, before defaults are filled in.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    db_path: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    default_account: Option<String>,
+    kdf: Option<String>,
+    clipboard_clear_secs: Option<u64>,
+    stream_threshold_bytes: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn test_paths() -> Paths {
+        Paths {
+            data_dir: PathBuf::from("/default"),
+            db_path: PathBuf::from("/default").join(DATABASE_NAME),
+            config_dir: PathBuf::from("/default_config"),
+        }
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            db_path = "/vaults/work.db"
+            data_dir = "/vaults/work"
+            default_account = "me"
+            kdf = "scrypt"
+            clipboard_clear_secs = 10
+            stream_threshold_bytes = 1024
+            "#,
+            &test_paths(),
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                db_path: PathBuf::from("/vaults/work.db"),
+                data_dir: PathBuf::from("/vaults/work"),
+                default_account: Some(String::from("me")),
+                kdf: KdfAlgorithm::default_scrypt(),
+                clipboard_clear_secs: 10,
+                stream_threshold_bytes: 1024,
+                db_path_is_set: true,
+            }
+        );
+
+        let config = Config::from_toml(r#"data_dir = "/vaults/home""#, &test_paths()).unwrap();
+        assert_eq!(
+            config.db_path,
+            PathBuf::from("/vaults/home").join(DATABASE_NAME)
+        );
+        assert_eq!(config.default_account, None);
+        assert_eq!(config.kdf, KdfAlgorithm::default());
+        assert_eq!(config.clipboard_clear_secs, DEFAULT_CLIPBOARD_CLEAR_SECS);
+        assert_eq!(
+            config.stream_threshold_bytes,
+            DEFAULT_STREAM_THRESHOLD_BYTES
+        );
+
+        assert_eq!(
+            Config::from_toml("", &test_paths()).unwrap(),
+            Config::new(&test_paths())
+        );
+    }
+
+    #[test]
+    fn test_existing_dir() {
+        let dir = PathBuf::from("test_files/missing_data_dir");
+        let legacy_dir = Path::new("test_files");
+        assert_eq!(existing_dir(dir.clone(), Some(legacy_dir)), legacy_dir);
+        assert_eq!(existing_dir(dir.clone(), None), dir);
+        assert_eq!(
+            existing_dir(
+                dir.clone(),
+                Some(Path::new("test_files/missing_legacy_dir"))
+            ),
+            dir
+        );
+        assert_eq!(
+            existing_dir(PathBuf::from("src"), Some(legacy_dir)),
+            PathBuf::from("src")
+        );
+    }
+
+    #[test]
+    fn test_with_paths() {
+        let config = Config::new(&test_paths());
+        assert_eq!(config.clone().with_paths(None, None), config);
+
+        let portable = config
+            .clone()
+            .with_paths(None, Some(PathBuf::from("/media/usb")));
+        assert_eq!(portable.data_dir, PathBuf::from("/media/usb"));
+        assert_eq!(
+            portable.db_path,
+            PathBuf::from("/media/usb").join(DATABASE_NAME)
+        );
+
+        let portable = config.with_paths(
+            Some(PathBuf::from("/media/usb/vault.db")),
+            Some(PathBuf::from("/media/usb")),
+        );
+        assert_eq!(portable.db_path, PathBuf::from("/media/usb/vault.db"));
+        assert_eq!(portable.data_dir, PathBuf::from("/media/usb"));
+
+        // A database path set in the configuration file is kept when only the data directory is
+        // overridden.
+        let config = Config::from_toml(r#"db_path = "/vaults/work.db""#, &test_paths())
+            .unwrap()
+            .with_paths(None, Some(PathBuf::from("/media/usb")));
+        assert_eq!(config.db_path, PathBuf::from("/vaults/work.db"));
+        assert_eq!(config.data_dir, PathBuf::from("/media/usb"));
+    }
+
+    #[test]
+    fn test_with_vars() {
+        let config = Config::new(&test_paths());
+        assert_eq!(config.clone().with_vars(|_| None).unwrap(), config);
+
+        let config = config
+            .with_vars(|name| match name {
+                DATA_DIR_ENV_VAR => Some(OsString::from("/ci/data")),
+                DEFAULT_ACCOUNT_ENV_VAR => Some(OsString::from("ci")),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(config.data_dir, PathBuf::from("/ci/data"));
+        assert_eq!(
+            config.db_path,
+            PathBuf::from("/ci/data").join(DATABASE_NAME)
+        );
+        assert_eq!(config.default_account, Some(String::from("ci")));
+
+        // The former name of the data directory variable is read unless the current one is set.
+        let legacy_config = Config::new(&test_paths())
+            .with_vars(|name| match name {
+                LEGACY_DATA_DIR_ENV_VAR => Some(OsString::from("/legacy/data")),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(legacy_config.data_dir, PathBuf::from("/legacy/data"));
+        let both_config = Config::new(&test_paths())
+            .with_vars(|name| match name {
+                DATA_DIR_ENV_VAR => Some(OsString::from("/ci/data")),
+                LEGACY_DATA_DIR_ENV_VAR => Some(OsString::from("/legacy/data")),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(both_config.data_dir, PathBuf::from("/ci/data"));
+
+        // Command-line paths take precedence over environment variables.
+        let config = config.with_paths(Some(PathBuf::from("/cli.db")), None);
+        assert_eq!(config.db_path, PathBuf::from("/cli.db"));
+        assert_eq!(config.data_dir, PathBuf::from("/ci/data"));
+
+        // A database path set by an environment variable is kept when only the data directory is
+        // given on the command line.
+        let config = Config::new(&test_paths())
+            .with_vars(|name| match name {
+                DB_PATH_ENV_VAR => Some(OsString::from("/ci/vault.db")),
+                _ => None,
+            })
+            .unwrap()
+            .with_paths(None, Some(PathBuf::from("/cli/data")));
+        assert_eq!(config.db_path, PathBuf::from("/ci/vault.db"));
+        assert_eq!(config.data_dir, PathBuf::from("/cli/data"));
+    }
+
+    #[test]
+    fn test_invalid_toml() {
+        for bad_toml in [
+            "db_path = 3",
+            r#"kdf = "rot13""#,
+            r#"unknown_setting = true"#,
+            "not toml",
+        ] {
+            assert!(
+                Config::from_toml(bad_toml, &test_paths()).is_err(),
+                "{bad_toml:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_paths() {
+        let paths = Paths::resolve().unwrap();
+        assert_eq!(paths.db_path, paths.data_dir.join(DATABASE_NAME));
+        assert_eq!(paths.config_path(), paths.config_dir.join(CONFIG_FILE_NAME));
+    }
+
+    #[test]
+    fn test_load_missing() {
+        let missing_path = Path::new("test_files/missing_config.toml");
+        assert!(Config::load(Some(missing_path)).is_err());
+    }
+}
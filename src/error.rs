@@ -18,6 +18,12 @@ pub enum Error {
     Utf8FromBytesError(String),
     /// Could not find an account with that username in database.
     AccountNotFoundError(String),
+    /// Could not find a stored password with that name in the database.
+    PasswordNotFoundError(String),
+    /// Tried to create a stored password with a name that the account already uses.
+    PasswordAlreadyExistsError(String),
+    /// Could not find a password attachment with that name in the database.
+    AttachmentNotFoundError(String),
     /// Problem encrypting something.
     EncryptionError(String),
     /// Problem decrypting something.
@@ -30,6 +36,10 @@ pub enum Error {
     FileNotFoundError(PathBuf),
     /// Tried to use non-UTF-8 file path.
     NonUtf8FilePathError(String),
+    /// Problem deriving a key from a password.
+    KeyDerivationError(String),
+    /// Stored data is in a format this version of dgruft does not understand.
+    UnsupportedFormatError(String),
     /// Generic error thrown when there is no [Error] enum value. Should only be used for errors
     /// that should never occur.
     UnhandledError(String),
@@ -63,6 +73,21 @@ impl fmt::Display for Error {
                     "AccountNotFoundError: Account \"{username}\" does not exist in the database."
                 )
             }
+            Error::PasswordNotFoundError(name) => {
+                format!(
+                    "PasswordNotFoundError: Password \"{name}\" does not exist in the database."
+                )
+            }
+            Error::PasswordAlreadyExistsError(name) => {
+                format!(
+                    "PasswordAlreadyExistsError: Cannot create password \"{name}\"— password already exists."
+                )
+            }
+            Error::AttachmentNotFoundError(name) => {
+                format!(
+                    "AttachmentNotFoundError: Attachment \"{name}\" does not exist in the database."
+                )
+            }
             Error::EncryptionError(error_as_string) => {
                 format!("EncryptionError: {}", error_as_string)
             }
@@ -90,6 +115,12 @@ impl fmt::Display for Error {
                     var_name
                 )
             }
+            Error::KeyDerivationError(error_as_string) => {
+                format!("KeyDerivationError: {}", error_as_string)
+            }
+            Error::UnsupportedFormatError(error_as_string) => {
+                format!("UnsupportedFormatError: {}", error_as_string)
+            }
             Error::UnhandledError(error_as_string) => {
                 format!("UnhandledError: {}", error_as_string)
             }
@@ -1,14 +1,22 @@
 //! Small, general helper functions.
 use std::{
     env,
+    fs::OpenOptions,
+    io::{self, Write},
     path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use base64ct::{Base64, Encoding};
-use directories::ProjectDirs;
+use base64ct::{Base64, Base64UrlUnpadded, Encoding};
+use chrono::{DateTime, Utc};
+use data_encoding::BASE32_NOPAD;
 use regex::Regex;
 
-use crate::error::Error;
+use crate::{config::Paths, error::Error};
+
+pub mod password;
+#[cfg(feature = "serde")]
+pub mod serde_b64;
 
 const VERSION_MESSAGE: &str = concat!(
     env!("CARGO_PKG_VERSION"),
@@ -22,36 +30,29 @@ const VERSION_MESSAGE: &str = concat!(
 /// Get the version of the program as a string.
 pub fn version() -> String {
     let author = clap::crate_authors!();
+    let (data_dir, config_dir) = match Paths::resolve() {
+        Ok(paths) => (
+            paths.data_dir.display().to_string(),
+            paths.config_dir.display().to_string(),
+        ),
+        Err(_) => (String::from("unknown"), String::from("unknown")),
+    };
     format!(
         "\
 {VERSION_MESSAGE}
 
 Author: {author}
 
-Data Directory: {}
-Config Directory: {}",
-        get_data_dir().display(),
-        get_config_dir().display(),
+Data Directory: {data_dir}
+Config Directory: {config_dir}"
     )
 }
 
-/// Get the directory of this project,
-pub fn project_directory() -> Option<ProjectDirs> {
-    ProjectDirs::from("ca", "maxgmr", env!("CARGO_PKG_NAME"))
-}
-
 /// Get the crate name in all caps as a string.
 pub fn project_name() -> String {
     env!("CARGO_CRATE_NAME").to_uppercase().to_string()
 }
 
-/// Get the environment variable name that can be set to change the data folder location.
-pub fn data_folder_env_var_name() -> Option<PathBuf> {
-    env::var(format!("{}_DATA", project_name().clone()))
-        .ok()
-        .map(PathBuf::from)
-}
-
 /// Get the environment variable name that can be set to change the config folder location.
 pub fn config_folder_env_var_name() -> Option<PathBuf> {
     env::var(format!("{}_CONFIG", project_name().clone()))
@@ -64,26 +65,24 @@ pub fn log_file_name() -> String {
     format!("{}.log", env!("CARGO_PKG_NAME"))
 }
 
-/// Get the directory where program data is stored.
-pub fn get_data_dir() -> PathBuf {
-    if let Some(path_buf) = data_folder_env_var_name().clone() {
-        path_buf
-    } else if let Some(proj_dirs) = project_directory() {
-        proj_dirs.data_local_dir().to_path_buf()
-    } else {
-        PathBuf::from(".").join(".data")
+/// Get the current time as a Unix timestamp in seconds.
+pub fn unix_timestamp() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(_) => 0,
     }
 }
 
-/// Get the directory where configuration files are stored.
-pub fn get_config_dir() -> PathBuf {
-    if let Some(path_buf) = config_folder_env_var_name().clone() {
-        path_buf
-    } else if let Some(proj_dirs) = project_directory() {
-        proj_dirs.config_local_dir().to_path_buf()
-    } else {
-        PathBuf::from(".").join(".config")
-    }
+/// Convert a Unix timestamp in seconds to a [DateTime].
+pub fn timestamp_to_datetime(timestamp: i64) -> Result<DateTime<Utc>, Error> {
+    DateTime::from_timestamp(timestamp, 0).ok_or_else(|| {
+        Error::UnsupportedFormatError(format!("Timestamp {timestamp} is out of range."))
+    })
+}
+
+/// Format a [DateTime] for display, e.g. `2024-05-01 13:37:00 UTC`.
+pub fn format_datetime(datetime: DateTime<Utc>) -> String {
+    datetime.format("%Y-%m-%d %H:%M:%S UTC").to_string()
 }
 
 /// Convert Path to String.
@@ -116,6 +115,40 @@ pub fn b64_to_bytes(str: &str) -> Result<Vec<u8>, Error> {
     }
 }
 
+/// Convert bytes to an unpadded URL-safe base 64 string.
+pub fn bytes_to_b64_url(bytes: &[u8]) -> String {
+    Base64UrlUnpadded::encode_string(bytes)
+}
+
+/// Convert an unpadded URL-safe base 64 string to bytes.
+pub fn b64_url_to_bytes(str: &str) -> Result<Vec<u8>, Error> {
+    match Base64UrlUnpadded::decode_vec(str) {
+        Ok(bytes) => Ok(bytes),
+        Err(base64ct::Error::InvalidEncoding) => Err(Error::InvalidB64Error(str.to_owned())),
+        Err(e) => Err(Error::UnhandledError(e.to_string())),
+    }
+}
+
+/// Convert bytes to an unpadded base 32 string, as used for TOTP secrets.
+pub fn bytes_to_base32(bytes: &[u8]) -> String {
+    BASE32_NOPAD.encode(bytes)
+}
+
+/// Convert a base 32 string to bytes. Case, whitespace, and padding are ignored, since TOTP
+/// secrets are often shown in lowercase groups of four.
+///
+/// The input isn't included in the error, since it is usually a secret.
+pub fn base32_to_bytes(str: &str) -> Result<Vec<u8>, Error> {
+    let normalized: String = str
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    BASE32_NOPAD
+        .decode(normalized.as_bytes())
+        .map_err(|_| Error::UnsupportedFormatError(String::from("Invalid base-32 string.")))
+}
+
 /// Convert base 64 string to fixed length byte array.
 pub fn b64_to_fixed<T, const LEN: usize>(src: T, debug_name: &str) -> Result<[u8; LEN], Error>
 where
@@ -158,6 +191,38 @@ where
     Ok(output)
 }
 
+/// Convert bytes to a lowercase hexadecimal string.
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Overwrite the whole content of the file at the given path with zeros and flush it to disk.
+/// This makes the old content harder to recover after the file is deleted.
+pub fn overwrite_with_zeros(path: &Path) -> io::Result<()> {
+    const CHUNK_SIZE: usize = 8192;
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let zeros = [0u8; CHUNK_SIZE];
+    while remaining > 0 {
+        let chunk_len = remaining.min(CHUNK_SIZE as u64) as usize;
+        file.write_all(&zeros[..chunk_len])?;
+        remaining -= chunk_len as u64;
+    }
+    file.sync_all()
+}
+
+/// Return true iff the given string is a valid POSIX shell environment variable name.
+pub fn is_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(first) if first == '_' || first.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+/// Quote a string so that a POSIX shell reads it back literally.
+pub fn shell_quote(string: &str) -> String {
+    format!("'{}'", string.replace('\'', "'\\''"))
+}
+
 /// Convert bytes to UTF-8 string.
 pub fn bytes_to_utf8(bytes: &[u8], debug_name: &str) -> Result<String, Error> {
     match std::str::from_utf8(bytes) {
@@ -205,4 +270,38 @@ mod tests {
             panic!("Wrong error type");
         }
     }
+
+    #[test]
+    fn test_base32() {
+        assert_eq!(bytes_to_base32(b"Hello!"), "JBSWY3DPEE");
+        assert_eq!(base32_to_bytes("JBSWY3DPEE").unwrap(), b"Hello!");
+        assert_eq!(base32_to_bytes("jbsw y3dp ee======").unwrap(), b"Hello!");
+        assert!(base32_to_bytes("JBSWY3DPE1").is_err());
+    }
+
+    #[test]
+    fn test_is_env_var_name() {
+        assert!(is_env_var_name("DB_USER"));
+        assert!(is_env_var_name("_private2"));
+        assert!(!is_env_var_name(""));
+        assert!(!is_env_var_name("2FA_CODE"));
+        assert!(!is_env_var_name("DB-USER"));
+        assert!(!is_env_var_name("X;rm"));
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("hunter2"), "'hunter2'");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("it's $HOME"), "'it'\\''s $HOME'");
+    }
+
+    #[test]
+    fn test_overwrite_with_zeros() {
+        let path = Path::new("test_files/testzeros");
+        std::fs::write(path, vec![0xAB; 10_000]).unwrap();
+        overwrite_with_zeros(path).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), vec![0u8; 10_000]);
+        std::fs::remove_file(path).unwrap();
+    }
 }
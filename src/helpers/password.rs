@@ -0,0 +1,154 @@
+//! Strength estimation and random generation of passwords.
+use std::fmt;
+
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// A rough estimate of how hard a password is to guess.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PasswordStrength {
+    /// Less than 40 bits of entropy.
+    Weak,
+    /// At least 40 bits of entropy.
+    Fair,
+    /// At least 60 bits of entropy.
+    Strong,
+    /// At least 80 bits of entropy.
+    VeryStrong,
+}
+impl fmt::Display for PasswordStrength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            PasswordStrength::Weak => "Weak",
+            PasswordStrength::Fair => "Fair",
+            PasswordStrength::Strong => "Strong",
+            PasswordStrength::VeryStrong => "Very Strong",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Estimate the entropy of a password in bits, assuming each character is picked at random from
+/// all the character classes the password uses.
+pub fn entropy_bits(password: &str) -> f64 {
+    let has = |predicate: fn(&char) -> bool| password.chars().any(|c| predicate(&c));
+    let mut cardinality = 0;
+    if has(char::is_ascii_lowercase) {
+        cardinality += 26;
+    }
+    if has(char::is_ascii_uppercase) {
+        cardinality += 26;
+    }
+    if has(char::is_ascii_digit) {
+        cardinality += 10;
+    }
+    if has(|c| c.is_ascii_punctuation() || *c == ' ') {
+        cardinality += 33;
+    }
+    if has(|c| !c.is_ascii()) {
+        cardinality += 100;
+    }
+
+    if cardinality == 0 {
+        return 0.0;
+    }
+    password.chars().count() as f64 * (cardinality as f64).log2()
+}
+
+/// Score the strength of a password based on its [entropy_bits].
+pub fn password_strength(password: &str) -> PasswordStrength {
+    match entropy_bits(password) {
+        bits if bits < 40.0 => PasswordStrength::Weak,
+        bits if bits < 60.0 => PasswordStrength::Fair,
+        bits if bits < 80.0 => PasswordStrength::Strong,
+        _ => PasswordStrength::VeryStrong,
+    }
+}
+
+/// Generate a random password of the given length. Lowercase letters are always used; symbols,
+/// digits, and uppercase letters are added to the alphabet if enabled.
+pub fn generate_password(length: usize, symbols: bool, digits: bool, uppercase: bool) -> String {
+    let mut alphabet = String::from(LOWERCASE);
+    if symbols {
+        alphabet.push_str(SYMBOLS);
+    }
+    if digits {
+        alphabet.push_str(DIGITS);
+    }
+    if uppercase {
+        alphabet.push_str(UPPERCASE);
+    }
+    let alphabet = alphabet.as_bytes();
+
+    // Reject values past the largest multiple of the alphabet length to avoid modulo bias.
+    let mut rng = ChaCha20Rng::from_entropy();
+    let limit = u32::MAX - u32::MAX % alphabet.len() as u32;
+    (0..length)
+        .map(|_| loop {
+            let value = rng.next_u32();
+            if value < limit {
+                break alphabet[(value % alphabet.len() as u32) as usize] as char;
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_entropy_bits() {
+        assert_eq!(entropy_bits(""), 0.0);
+        assert_eq!(entropy_bits("aaaa"), 4.0 * 26f64.log2());
+        assert_eq!(entropy_bits("aA1!"), 4.0 * 95f64.log2());
+    }
+
+    #[test]
+    fn test_password_strength() {
+        assert_eq!(password_strength(""), PasswordStrength::Weak);
+        assert_eq!(password_strength("password"), PasswordStrength::Weak);
+        assert_eq!(password_strength("hunter2hunt"), PasswordStrength::Fair);
+        assert_eq!(
+            password_strength("hunter2hunter2"),
+            PasswordStrength::Strong
+        );
+        assert_eq!(
+            password_strength("Tr0ub4dor&3xyz"),
+            PasswordStrength::VeryStrong
+        );
+        assert_eq!(
+            password_strength("correct horse battery staple"),
+            PasswordStrength::VeryStrong
+        );
+    }
+
+    #[test]
+    fn test_generate_password() {
+        assert_eq!(generate_password(0, true, true, true), "");
+
+        let all = generate_password(200, true, true, true);
+        assert_eq!(all.len(), 200);
+        assert!(all.chars().all(|c| c.is_ascii_graphic()));
+
+        let lowercase = generate_password(200, false, false, false);
+        assert_eq!(lowercase.len(), 200);
+        assert!(lowercase.chars().all(|c| c.is_ascii_lowercase()));
+
+        let no_symbols = generate_password(200, false, true, true);
+        assert!(no_symbols.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        assert_ne!(
+            generate_password(32, true, true, true),
+            generate_password(32, true, true, true)
+        );
+    }
+}
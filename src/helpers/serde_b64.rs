@@ -0,0 +1,30 @@
+//! Serialize byte fields as base-64 strings. Use with `#[serde(with = "helpers::serde_b64")]`.
+//!
+//! Works with any byte container which can be built from a [Vec] of bytes, e.g. `Vec<u8>` or
+//! fixed-length arrays such as `[u8; 12]`.
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+use crate::helpers;
+
+/// Serialize the given bytes as a base-64 string.
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]>,
+    S: Serializer,
+{
+    serializer.serialize_str(&helpers::bytes_to_b64(bytes.as_ref()))
+}
+
+/// Deserialize bytes from a base-64 string. Fixed-length arrays must be given a string of
+/// exactly the right length.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: TryFrom<Vec<u8>>,
+    D: Deserializer<'de>,
+{
+    let b64 = String::deserialize(deserializer)?;
+    let bytes = helpers::b64_to_bytes(&b64).map_err(de::Error::custom)?;
+    let len = bytes.len();
+    T::try_from(bytes)
+        .map_err(|_| de::Error::invalid_length(len, &"a base-64 string of the right length"))
+}
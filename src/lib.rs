@@ -2,12 +2,19 @@
 //!
 //! This is a personal project; using `dgruft` for storage of confidential information is *not
 //! recommended*.
+//!
+//! The `serde` feature adds `Serialize` and `Deserialize` impls to the stored types, e.g.
+//! [backend::encrypted::Encrypted] and [backend::account::Account]. It only gates these impls:
+//! `serde` and `serde_json` are always dependencies, since configuration, exports, and JSON output
+//! use them.
 #![warn(missing_docs)]
 
 /// Backend code for `dgruft`.
 pub mod backend;
 /// Command line argument parsing.
 pub mod cli;
+/// Configuration file loading.
+pub mod config;
 /// `dgruft`-specific errors.
 pub mod error;
 #[cfg(feature = "frontend")]
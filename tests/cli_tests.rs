@@ -0,0 +1,74 @@
+use std::{
+    fs,
+    process::{Command, Output, Stdio},
+};
+
+use serde_json::Value;
+
+const TEST_CONFIG_PATH: &str = "dbs/cli-test-config.toml";
+const TEST_DB_PATH: &str = "dbs/cli-test.db";
+
+// Run the `dgruft` binary with an empty configuration and nothing on stdin.
+fn run_dgruft(args: &[&str]) -> Output {
+    fs::write(TEST_CONFIG_PATH, "").unwrap();
+    Command::new(env!("CARGO_BIN_EXE_dgruft"))
+        .args(["--config", TEST_CONFIG_PATH, "--db-path", TEST_DB_PATH])
+        .args(args)
+        .env_remove("DGRUFT_DEFAULT_ACCOUNT")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+// Parse the JSON envelope printed by the binary. It must be the only thing on stdout.
+fn json_output(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn json_envelope_tests() {
+    let output = run_dgruft(&["--json", "generate-password", "--length", "12"]);
+    assert!(output.status.success());
+    let json = json_output(&output);
+    assert_eq!(Value::Bool(true), json["ok"]);
+    assert_eq!(
+        12,
+        json["data"]["password"].as_str().unwrap().chars().count()
+    );
+    assert!(json["error"].is_null());
+
+    // Commands which prompt on stdin or open an editor are rejected before anything is prompted.
+    for args in [
+        vec!["--json", "-a", "bob", "passwords", "--new", "bank"],
+        vec!["--json", "-a", "bob", "files", "--open", "notes.txt"],
+        vec!["--json", "-a", "bob", "account", "--new"],
+    ] {
+        let output = run_dgruft(&args);
+        assert_eq!(Some(1), output.status.code());
+        let json = json_output(&output);
+        assert_eq!(Value::Bool(false), json["ok"]);
+        assert!(json["data"].is_null());
+        assert_eq!(
+            "--json is not supported for this command.",
+            json["error"].as_str().unwrap()
+        );
+    }
+
+    // Other account commands get as far as asking for the account.
+    for args in [
+        vec!["--json", "password-show", "bank"],
+        vec!["--json", "totp", "bank"],
+        vec!["--json", "audit-passwords"],
+        vec!["--json", "passwords", "--list"],
+        vec!["--json", "files", "--forcedelete", "notes.txt"],
+    ] {
+        let output = run_dgruft(&args);
+        assert_eq!(Some(1), output.status.code());
+        let json = json_output(&output);
+        assert_eq!(Value::Bool(false), json["ok"]);
+        assert_eq!(
+            "An account is required for this command.",
+            json["error"].as_str().unwrap()
+        );
+    }
+}
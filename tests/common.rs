@@ -17,4 +17,3 @@ pub fn reset_test_db() {
         .status()
         .expect("failed");
 }
-
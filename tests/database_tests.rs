@@ -1,15 +1,29 @@
-use std::ffi::OsString;
+use std::{ffi::OsString, fs::File, path::PathBuf};
 
 mod common;
 
 use account::Account;
 use dgruft::backend::*;
+use dgruft::config;
 use dgruft::error::Error;
 use dgruft::helpers;
 use file::FileData;
 
 // Run with `cargo test --test '*' -- --test-threads=1`
 
+// Point the backend at the test database. Only the first call has an effect.
+fn init_test_config() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        config::init(config::Config::new(&config::Paths {
+            data_dir: common::get_test_dir(),
+            db_path: PathBuf::from(common::TEST_DB_PATH),
+            config_dir: common::get_test_dir(),
+        }))
+        .unwrap()
+    });
+}
+
 #[test]
 #[ignore]
 fn edit_tests() {
@@ -44,9 +58,10 @@ fn edit_tests() {
 
     file.edit(sec_fields.key()).unwrap();
 
-    // Update file data to match new nonce. Undo changes if nonce change fails.
-    db.update_file_content_nonce(
+    // Update file data to match new nonce and size. Undo changes if the update fails.
+    db.update_file_content(
         file.content_nonce(),
+        file.file_size(),
         &helpers::path_to_string(file.path()).unwrap(),
     )
     .unwrap();
@@ -136,25 +151,21 @@ fn file_tests() {
     // Load files from database
     println!("{}", file_path_1.to_str().unwrap());
     let file_1 = FileData::from_b64(
-        db.get_b64_file_data(&helpers::bytes_to_b64(
-            file_path_1.to_str().unwrap().as_bytes(),
-        ))
-        .unwrap()
-        .unwrap(),
+        db.get_b64_file_data(file_path_1.to_str().unwrap())
+            .unwrap()
+            .unwrap(),
     )
     .unwrap();
     let file_2 = FileData::from_b64(
-        db.get_b64_file_data(&helpers::bytes_to_b64(
-            file_path_2.to_str().unwrap().as_bytes(),
-        ))
-        .unwrap()
-        .unwrap(),
+        db.get_b64_file_data(file_path_2.to_str().unwrap())
+            .unwrap()
+            .unwrap(),
     )
     .unwrap();
 
     // Ensure files content O.K.
     let content_1 = file_1.open_decrypted(sec_fields.key()).unwrap();
-    assert_eq!(content_1, b"");
+    assert_eq!(*content_1, b"");
     let content_2 = file_2.open_decrypted(sec_fields.key()).unwrap();
     assert_eq!(
         helpers::bytes_to_utf8(&content_2, "content_2").unwrap(),
@@ -164,15 +175,11 @@ fn file_tests() {
     // Ensure deletion works as intended.
     db.delete_account(username).unwrap().unwrap();
     assert!(db
-        .get_b64_file_data(&helpers::bytes_to_b64(
-            file_path_1.to_str().unwrap().as_bytes(),
-        ))
+        .get_b64_file_data(file_path_1.to_str().unwrap())
         .unwrap()
         .is_none());
     assert!(db
-        .get_b64_file_data(&helpers::bytes_to_b64(
-            file_path_2.to_str().unwrap().as_bytes(),
-        ))
+        .get_b64_file_data(file_path_2.to_str().unwrap())
         .unwrap()
         .is_none());
     assert!(db.get_b64_files(username).unwrap().is_none());
@@ -322,14 +329,14 @@ fn password_tests() {
     fn assert_encrypted_eq(
         unencrypted_str: &str,
         encrypted: &encrypted::Encrypted,
-        key: &[u8; 32],
+        key: &encrypted::KeyMaterial,
     ) {
-        assert_eq!(unencrypted_str.as_bytes(), encrypted.decrypt(key).unwrap());
+        assert_eq!(unencrypted_str.as_bytes(), *encrypted.decrypt(key).unwrap());
     }
 
     fn get_with_name<'a>(
         desired_name: &str,
-        key: &[u8; 32],
+        key: &encrypted::KeyMaterial,
         passwords: &'a [password::Password],
     ) -> &'a password::Password {
         passwords
@@ -378,3 +385,1911 @@ fn password_tests() {
 
     assert!(db.get_b64_passwords(username_1).unwrap().is_none());
 }
+
+#[test]
+fn get_all_tests() {
+    common::reset_test_db();
+    let _ = std::fs::remove_file("test_files/get_all_file_1");
+    let _ = std::fs::remove_file("test_files/get_all_file_2");
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    assert!(db.get_all_b64_accounts().unwrap().is_empty());
+    assert!(db.get_all_b64_passwords().unwrap().is_empty());
+    assert!(db.get_all_b64_files().unwrap().is_empty());
+
+    let accounts = [("get_all_1", "password_1"), ("get_all_2", "password_2")];
+    for (i, (username, password)) in accounts.iter().enumerate() {
+        let account = Account::new(username, password).unwrap();
+        db.add_new_account(account.to_b64()).unwrap();
+        let sec_fields = account.unlock(password).unwrap();
+
+        let pass = password::Password::new(&account, password, "bank", "me", "1234", "").unwrap();
+        db.add_new_password(pass.to_b64()).unwrap();
+
+        let file_name = OsString::from(format!("get_all_file_{}", i + 1));
+        let mut file_path = common::get_test_dir();
+        file_path.push(&file_name);
+        let file =
+            FileData::new_with_key(username, sec_fields.key(), file_name, &file_path).unwrap();
+        db.add_new_file_data(file.to_b64().unwrap()).unwrap();
+    }
+
+    let all_accounts: Vec<Account> = db
+        .get_all_b64_accounts()
+        .unwrap()
+        .into_iter()
+        .map(|b64_account| Account::from_b64(b64_account).unwrap())
+        .collect();
+    let all_passwords = db.get_all_b64_passwords().unwrap();
+    let all_files = db.get_all_b64_files().unwrap();
+    assert_eq!(all_accounts.len(), 2);
+    assert_eq!(all_passwords.len(), 2);
+    assert_eq!(all_files.len(), 2);
+    for (username, password) in accounts {
+        let loaded = all_accounts
+            .iter()
+            .find(|account| account.username() == username)
+            .unwrap();
+        assert!(loaded.check_password_match(password));
+
+        let b64_username = helpers::bytes_to_b64(username.as_bytes());
+        assert!(all_passwords
+            .iter()
+            .any(|pass| pass.b64_owner_username == b64_username));
+        assert!(all_files
+            .iter()
+            .any(|file| file.b64_owner_username == b64_username));
+    }
+
+    // Deleting an account should remove its rows from the full listings.
+    db.delete_account(accounts[0].0).unwrap().unwrap();
+    assert_eq!(db.get_all_b64_accounts().unwrap().len(), 1);
+    assert_eq!(db.get_all_b64_passwords().unwrap().len(), 1);
+    assert_eq!(db.get_all_b64_files().unwrap().len(), 1);
+}
+
+#[test]
+fn owner_filter_tests() {
+    common::reset_test_db();
+    let _ = std::fs::remove_file("test_files/owner_filter_file_1");
+    let _ = std::fs::remove_file("test_files/owner_filter_file_2");
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let accounts = [("owner_1", "password_1"), ("owner_2", "password_2")];
+    for (i, (username, password)) in accounts.iter().enumerate() {
+        let account = Account::new(username, password).unwrap();
+        db.add_new_account(account.to_b64()).unwrap();
+        let sec_fields = account.unlock(password).unwrap();
+
+        for name in ["bank", "email"] {
+            let pass = password::Password::new(&account, password, name, "me", "1234", "").unwrap();
+            db.add_new_password(pass.to_b64()).unwrap();
+        }
+
+        let file_name = OsString::from(format!("owner_filter_file_{}", i + 1));
+        let mut file_path = common::get_test_dir();
+        file_path.push(&file_name);
+        let file =
+            FileData::new_with_key(username, sec_fields.key(), file_name, &file_path).unwrap();
+        db.add_new_file_data(file.to_b64().unwrap()).unwrap();
+    }
+
+    // Only rows owned by the given account are returned.
+    for (username, _) in accounts {
+        let b64_username = helpers::bytes_to_b64(username.as_bytes());
+
+        let passwords = db.get_b64_passwords(username).unwrap().unwrap();
+        assert_eq!(passwords.len(), 2);
+        assert!(passwords
+            .iter()
+            .all(|pass| pass.b64_owner_username == b64_username));
+
+        let files = db.get_b64_files(username).unwrap().unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files
+            .iter()
+            .all(|file| file.b64_owner_username == b64_username));
+    }
+
+    // Per-owner counts only count rows owned by the given account.
+    use database::Table;
+    assert_eq!(db.count_rows(Table::Passwords).unwrap(), 4);
+    assert_eq!(
+        db.count_rows_by_owner(Table::Passwords, "owner_1").unwrap(),
+        Some(2)
+    );
+    assert_eq!(
+        db.count_rows_by_owner(Table::Files, "owner_1").unwrap(),
+        Some(1)
+    );
+    assert_eq!(
+        db.count_rows_by_owner(Table::UserCredentials, "owner_1")
+            .unwrap(),
+        Some(1)
+    );
+    assert_eq!(
+        db.count_rows_by_owner(Table::VaultConfig, "owner_1")
+            .unwrap(),
+        None
+    );
+
+    let b64_name = db.get_b64_passwords("owner_1").unwrap().unwrap()[0]
+        .b64_name_ciphertext
+        .clone();
+    db.delete_password("owner_1", &b64_name).unwrap().unwrap();
+    assert_eq!(db.count_rows(Table::Passwords).unwrap(), 3);
+    assert_eq!(
+        db.count_rows_by_owner(Table::Passwords, "owner_1").unwrap(),
+        Some(1)
+    );
+    assert_eq!(
+        db.count_rows_by_owner(Table::Passwords, "owner_2").unwrap(),
+        Some(2)
+    );
+}
+
+#[test]
+fn category_filter_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "category_account";
+    let account_password = "category_password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let sec_fields = account.unlock(account_password).unwrap();
+
+    for (name, category) in [
+        ("bank", "banking"),
+        ("email", "social,work"),
+        ("forum", "social"),
+        ("game", ""),
+    ] {
+        let mut pass =
+            password::Password::new(&account, account_password, name, "me", "1234", "").unwrap();
+        pass.apply_changes(
+            sec_fields.key(),
+            &password::PasswordChanges::new().new_category(category),
+        )
+        .unwrap();
+        db.add_new_password(pass.to_b64()).unwrap();
+    }
+
+    let names_in = |category: &str| -> Vec<String> {
+        let mut names: Vec<String> = db
+            .get_b64_passwords_by_category(username, category)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|b64_password| {
+                let pass = password::Password::from_b64(b64_password).unwrap();
+                helpers::bytes_to_utf8(
+                    &pass.encrypted_name().decrypt(sec_fields.key()).unwrap(),
+                    "name",
+                )
+                .unwrap()
+            })
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(names_in("banking"), vec!["bank"]);
+    assert_eq!(names_in("social"), vec!["email", "forum"]);
+    assert_eq!(names_in(" work "), vec!["email"]);
+    assert!(names_in("soc").is_empty());
+    assert!(names_in("missing").is_empty());
+    assert!(db
+        .get_b64_passwords_by_category("missing_account", "social")
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn update_account_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "update_account_account";
+    let account = Account::new(username, "old_password").unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+
+    let new_account = Account::new(username, "new_password").unwrap();
+    assert_ne!(account.password_salt(), new_account.password_salt());
+    db.update_account(new_account.to_b64()).unwrap();
+    let loaded = Account::from_b64(db.get_b64_account(username).unwrap().unwrap()).unwrap();
+    assert_eq!(new_account.password_salt(), loaded.password_salt());
+    assert_eq!(
+        new_account.derive_key("new_password").unwrap(),
+        loaded.derive_key("new_password").unwrap()
+    );
+    assert!(loaded.check_password_match("new_password"));
+    assert!(!loaded.check_password_match("old_password"));
+
+    assert!(db.account_exists(username).unwrap());
+    assert!(!db.account_exists("missing_account").unwrap());
+
+    // Updating an account that doesn't exist should fail rather than insert it.
+    let missing_account = Account::new("missing_account", "password").unwrap();
+    let err = db.update_account(missing_account.to_b64()).unwrap_err();
+    if let rusqlite::Error::QueryReturnedNoRows = err {
+    } else {
+        panic!("Wrong error type");
+    }
+    assert!(db.get_b64_account("missing_account").unwrap().is_none());
+}
+
+#[test]
+fn last_login_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "last_login_account";
+    let account = Account::new(username, "last_login_password").unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let loaded = Account::from_b64(db.get_b64_account(username).unwrap().unwrap()).unwrap();
+    assert_eq!(
+        account.created_at().timestamp(),
+        loaded.created_at().timestamp()
+    );
+    assert!(loaded.last_login().is_none());
+
+    db.update_last_login(username, 1_700_000_000)
+        .unwrap()
+        .unwrap();
+    let loaded = Account::from_b64(db.get_b64_account(username).unwrap().unwrap()).unwrap();
+    assert_eq!(
+        Some(1_700_000_000),
+        loaded.last_login().map(|last_login| last_login.timestamp())
+    );
+    assert!(db
+        .update_last_login("missing_account", 1_700_000_000)
+        .unwrap()
+        .is_none());
+
+    // Updating the account's credentials keeps its timestamps.
+    db.update_account(loaded.to_b64()).unwrap();
+    let b64_account = db.get_b64_account(username).unwrap().unwrap();
+    assert_eq!(b64_account.created_at, account.created_at().timestamp());
+    assert_eq!(b64_account.last_login, Some(1_700_000_000));
+}
+
+#[test]
+fn account_timestamp_migration_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    let account = Account::new("my_account", "my_password").unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    drop(db);
+
+    // Turn the database back into a version 6 database, from before accounts had timestamps.
+    let connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    connection
+        .execute_batch(
+            "ALTER TABLE user_credentials DROP COLUMN last_login;
+            ALTER TABLE user_credentials DROP COLUMN created_at;
+            UPDATE schema_version SET version = 6;",
+        )
+        .unwrap();
+    drop(connection);
+
+    let before_migration = helpers::unix_timestamp();
+    let db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    let loaded = Account::from_b64(db.get_b64_account("my_account").unwrap().unwrap()).unwrap();
+    // Existing accounts are dated to the migration and haven't logged in since.
+    assert!(loaded.created_at().timestamp() >= before_migration);
+    assert!(loaded.last_login().is_none());
+    assert!(loaded.check_password_match("my_password"));
+}
+
+#[test]
+fn legacy_kdf_migration_tests() {
+    common::reset_test_db();
+
+    // Create an account the way dgruft did before Argon2id, in the old schema.
+    let key = encrypted::new_key(None);
+    let hashed_password = hashed::Hashed::new(b"legacy_password");
+    let dbl_hashed_password = hashed::Hashed::new(hashed_password.hash());
+    let encrypted_key = encrypted::Encrypted::new(
+        key.as_bytes(),
+        &encrypted::KeyMaterial::new(*hashed_password.hash()),
+    )
+    .unwrap();
+    let connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    connection
+        .execute(
+            "CREATE TABLE user_credentials (
+                username TEXT PRIMARY KEY,
+                password_salt TEXT NOT NULL,
+                dbl_hashed_password_hash TEXT NOT NULL,
+                dbl_hashed_password_salt TEXT NOT NULL,
+                encrypted_key_ciphertext TEXT NOT NULL,
+                encrypted_key_nonce TEXT NOT NULL
+            )",
+            (),
+        )
+        .unwrap();
+    connection
+        .execute(
+            "INSERT INTO user_credentials VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                helpers::bytes_to_b64(b"legacy_account"),
+                hashed_password.salt_as_b64(),
+                dbl_hashed_password.hash_as_b64(),
+                dbl_hashed_password.salt_as_b64(),
+                encrypted_key.ciphertext_as_b64(),
+                encrypted_key.nonce_as_b64(),
+            ),
+        )
+        .unwrap();
+    drop(connection);
+
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    let b64_account = db.get_b64_account("legacy_account").unwrap().unwrap();
+    assert_eq!(b64_account.kdf_params, account::LEGACY_KDF_PARAMS);
+    let mut legacy_account = Account::from_b64(b64_account).unwrap();
+    assert!(legacy_account.kdf().is_none());
+    assert_eq!(
+        &key,
+        legacy_account.unlock("legacy_password").unwrap().key()
+    );
+
+    // Changing the password stores the account with Argon2id parameters.
+    legacy_account
+        .change_password("legacy_password", "new_password")
+        .unwrap();
+    db.update_account(legacy_account.to_b64()).unwrap();
+    drop(db);
+    let db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    let loaded = Account::from_b64(db.get_b64_account("legacy_account").unwrap().unwrap()).unwrap();
+    assert_eq!(Some(&account::KdfAlgorithm::default()), loaded.kdf());
+    assert_eq!(&key, loaded.unlock("new_password").unwrap().key());
+}
+
+#[test]
+fn password_url_migration_tests() {
+    common::reset_test_db();
+    let username = "my_account";
+    let account_password = "my_password";
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let pass =
+        password::Password::new(&account, account_password, "bank", "me", "1234", "").unwrap();
+    db.add_new_password(pass.to_b64()).unwrap();
+    drop(db);
+
+    // Turn the database back into a version 2 database, from before passwords had URLs.
+    let connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    connection
+        .execute_batch(
+            "ALTER TABLE passwords DROP COLUMN url_nonce;
+            ALTER TABLE passwords DROP COLUMN encrypted_url;
+            UPDATE schema_version SET version = 2;",
+        )
+        .unwrap();
+    drop(connection);
+
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    let sec_fields = account.unlock(account_password).unwrap();
+    let mut loaded = password::Password::from_b64(
+        db.get_b64_passwords(username)
+            .unwrap()
+            .unwrap()
+            .pop()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(loaded.url(sec_fields.key()).unwrap(), None);
+
+    let old_b64_name_ciphertext = loaded.encrypted_name().ciphertext_as_b64();
+    loaded
+        .apply_changes(
+            sec_fields.key(),
+            &password::PasswordChanges::new().new_url("https://bank.example.com"),
+        )
+        .unwrap();
+    db.update_passwords(vec![(old_b64_name_ciphertext, loaded.to_b64())])
+        .unwrap();
+    let loaded = password::Password::from_b64(
+        db.get_b64_passwords(username)
+            .unwrap()
+            .unwrap()
+            .pop()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        loaded.url(sec_fields.key()).unwrap().unwrap(),
+        "https://bank.example.com"
+    );
+}
+
+#[test]
+fn password_expiry_migration_tests() {
+    common::reset_test_db();
+    let username = "my_account";
+    let account_password = "my_password";
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let pass =
+        password::Password::new(&account, account_password, "bank", "me", "1234", "").unwrap();
+    db.add_new_password(pass.to_b64()).unwrap();
+    drop(db);
+
+    // Turn the database back into a version 4 database, from before passwords could expire.
+    let connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    connection
+        .execute_batch(
+            "ALTER TABLE passwords DROP COLUMN expires_at;
+            UPDATE schema_version SET version = 4;",
+        )
+        .unwrap();
+    drop(connection);
+
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    let sec_fields = account.unlock(account_password).unwrap();
+    let mut loaded = password::Password::from_b64(
+        db.get_b64_passwords(username)
+            .unwrap()
+            .unwrap()
+            .pop()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(loaded.expires_at(), None);
+
+    let expires_at = password::parse_expiry_date("2030-01-31").unwrap();
+    let old_b64_name_ciphertext = loaded.encrypted_name().ciphertext_as_b64();
+    loaded
+        .apply_changes(
+            sec_fields.key(),
+            &password::PasswordChanges::new().new_expires_at(expires_at),
+        )
+        .unwrap();
+    db.update_passwords(vec![(old_b64_name_ciphertext, loaded.to_b64())])
+        .unwrap();
+    let b64_password = db
+        .get_b64_passwords(username)
+        .unwrap()
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert_eq!(b64_password.expires_at.as_deref(), Some("2030-01-31"));
+    assert_eq!(
+        password::Password::from_b64(b64_password)
+            .unwrap()
+            .expires_at(),
+        expires_at
+    );
+}
+
+#[test]
+fn password_timestamp_migration_tests() {
+    common::reset_test_db();
+    let username = "my_account";
+    let account_password = "my_password";
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let pass =
+        password::Password::new(&account, account_password, "bank", "me", "1234", "").unwrap();
+    db.add_new_password(pass.to_b64()).unwrap();
+    drop(db);
+
+    // Turn the database back into a version 5 database, from before passwords had timestamps.
+    let connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    connection
+        .execute_batch(
+            "ALTER TABLE passwords DROP COLUMN updated_at;
+            ALTER TABLE passwords DROP COLUMN created_at;
+            UPDATE schema_version SET version = 5;",
+        )
+        .unwrap();
+    drop(connection);
+
+    let before_migration = helpers::unix_timestamp();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    let sec_fields = account.unlock(account_password).unwrap();
+    let mut loaded = password::Password::from_b64(
+        db.get_b64_passwords(username)
+            .unwrap()
+            .unwrap()
+            .pop()
+            .unwrap(),
+    )
+    .unwrap();
+    // Existing passwords are dated to the migration.
+    assert!(loaded.created_at().timestamp() >= before_migration);
+    assert_eq!(loaded.created_at(), loaded.updated_at());
+
+    let old_b64_name_ciphertext = loaded.encrypted_name().ciphertext_as_b64();
+    loaded
+        .apply_changes(
+            sec_fields.key(),
+            &password::PasswordChanges::new().new_notes("new notes"),
+        )
+        .unwrap();
+    db.update_passwords(vec![(old_b64_name_ciphertext, loaded.to_b64())])
+        .unwrap();
+    let reloaded = password::Password::from_b64(
+        db.get_b64_passwords(username)
+            .unwrap()
+            .unwrap()
+            .pop()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(reloaded.created_at(), loaded.created_at());
+    assert_eq!(
+        reloaded.updated_at().timestamp(),
+        loaded.updated_at().timestamp()
+    );
+}
+
+#[test]
+fn file_size_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "file_size_account";
+    let password = "file_size_password";
+    let account = Account::new(username, password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let sec_fields = account.unlock(password).unwrap();
+    assert_eq!(db.total_file_size(username).unwrap(), Some(0));
+    assert_eq!(db.total_file_size("missing_account").unwrap(), None);
+
+    let mut total = 0;
+    for (name, content) in [("size_file_1", "a"), ("size_file_2", "bcdefg")] {
+        let mut path = common::get_test_dir();
+        path.push(name);
+        let _ = std::fs::remove_file(&path);
+        let file = FileData::new_with_content_and_key(
+            username,
+            sec_fields.key(),
+            OsString::from(name),
+            content.as_bytes(),
+            &path,
+        )
+        .unwrap();
+        assert_eq!(file.file_size(), std::fs::metadata(&path).unwrap().len());
+        total += file.file_size();
+        db.add_new_file_data(file.to_b64().unwrap()).unwrap();
+    }
+    assert_eq!(db.total_file_size(username).unwrap(), Some(total));
+    assert_eq!(db.total_file_size_all().unwrap(), total);
+
+    let mut path = common::get_test_dir();
+    path.push("size_file_1");
+    let path_string = helpers::path_to_string(&path).unwrap();
+    db.update_file_content(&[0; 12], 1000, &path_string)
+        .unwrap();
+    let loaded = FileData::from_b64(db.get_b64_file_data(&path_string).unwrap().unwrap()).unwrap();
+    assert_eq!(loaded.file_size(), 1000);
+    assert_eq!(loaded.content_nonce(), &[0; 12]);
+    assert!(db
+        .update_file_content(&[0; 12], 1000, "missing_path")
+        .is_err());
+}
+
+#[test]
+fn file_size_migration_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "my_account";
+    let password = "my_password";
+    let account = Account::new(username, password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let sec_fields = account.unlock(password).unwrap();
+
+    let mut path = common::get_test_dir();
+    path.push("size_migration_file");
+    let _ = std::fs::remove_file(&path);
+    let file = FileData::new_with_content_and_key(
+        username,
+        sec_fields.key(),
+        OsString::from("size_migration_file"),
+        b"some content",
+        &path,
+    )
+    .unwrap();
+    db.add_new_file_data(file.to_b64().unwrap()).unwrap();
+    // A file which is missing from disk.
+    let mut missing_b64_file = file.to_b64().unwrap();
+    missing_b64_file.b64_path = helpers::bytes_to_b64(b"test_files/missing_file");
+    db.add_new_file_data(missing_b64_file).unwrap();
+    drop(db);
+
+    // Turn the database back into a version 7 database, from before file sizes were tracked.
+    let connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    connection
+        .execute_batch(
+            "ALTER TABLE files DROP COLUMN file_size;
+            UPDATE schema_version SET version = 7;",
+        )
+        .unwrap();
+    drop(connection);
+
+    let db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    let path_string = helpers::path_to_string(&path).unwrap();
+    let loaded = FileData::from_b64(db.get_b64_file_data(&path_string).unwrap().unwrap()).unwrap();
+    assert_eq!(loaded.file_size(), file.file_size());
+    let missing = FileData::from_b64(
+        db.get_b64_file_data("test_files/missing_file")
+            .unwrap()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(missing.file_size(), 0);
+    assert_eq!(
+        db.total_file_size(username).unwrap(),
+        Some(file.file_size())
+    );
+}
+
+#[test]
+fn file_extension_migration_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "my_account";
+    let password = "my_password";
+    let account = Account::new(username, password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let sec_fields = account.unlock(password).unwrap();
+
+    let mut paths = vec![];
+    for name in ["extension_file.txt", "extension_file"] {
+        let mut path = common::get_test_dir();
+        path.push(name);
+        let _ = std::fs::remove_file(&path);
+        let file = FileData::new_with_key(username, sec_fields.key(), OsString::from(name), &path)
+            .unwrap();
+        db.add_new_file_data(file.to_b64().unwrap()).unwrap();
+        paths.push(helpers::path_to_string(&path).unwrap());
+    }
+    drop(db);
+
+    // Turn the database back into a version 8 database, from before extensions were tracked.
+    let connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    connection
+        .execute_batch(
+            "ALTER TABLE files DROP COLUMN extension;
+            UPDATE schema_version SET version = 8;",
+        )
+        .unwrap();
+    drop(connection);
+
+    let db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    let extensions: Vec<Option<String>> = paths
+        .iter()
+        .map(|path| {
+            FileData::from_b64(db.get_b64_file_data(path).unwrap().unwrap())
+                .unwrap()
+                .extension()
+                .map(str::to_owned)
+        })
+        .collect();
+    assert_eq!(extensions, vec![Some("txt".to_owned()), None]);
+}
+
+#[test]
+fn file_compression_migration_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "my_account";
+    let password = "my_password";
+    let account = Account::new(username, password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let sec_fields = account.unlock(password).unwrap();
+
+    let mut path = common::get_test_dir();
+    path.push("compression_file.json");
+    let _ = std::fs::remove_file(&path);
+    let content = br#"{"key": "value"}"#;
+    let file = FileData::new_with_content_and_key(
+        username,
+        sec_fields.key(),
+        OsString::from("compression_file.json"),
+        content,
+        &path,
+    )
+    .unwrap();
+    db.add_new_file_data(file.to_b64().unwrap()).unwrap();
+    let path_string = helpers::path_to_string(&path).unwrap();
+    drop(db);
+
+    // Turn the database back into a version 9 database, from before files could be compressed.
+    let connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    connection
+        .execute_batch(
+            "ALTER TABLE files DROP COLUMN compressed;
+            UPDATE schema_version SET version = 9;",
+        )
+        .unwrap();
+    drop(connection);
+
+    let db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    let loaded_file =
+        FileData::from_b64(db.get_b64_file_data(&path_string).unwrap().unwrap()).unwrap();
+    assert!(!loaded_file.compressed());
+    assert_eq!(
+        content,
+        &loaded_file.open_decrypted(sec_fields.key()).unwrap()[..]
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn password_totp_migration_tests() {
+    common::reset_test_db();
+    let username = "my_account";
+    let account_password = "my_password";
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let pass =
+        password::Password::new(&account, account_password, "bank", "me", "1234", "").unwrap();
+    db.add_new_password(pass.to_b64()).unwrap();
+    drop(db);
+
+    // Turn the database back into a version 10 database, from before passwords had TOTP secrets.
+    let connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    connection
+        .execute_batch(
+            "ALTER TABLE passwords DROP COLUMN totp_secret_nonce;
+            ALTER TABLE passwords DROP COLUMN encrypted_totp_secret;
+            UPDATE schema_version SET version = 10;",
+        )
+        .unwrap();
+    drop(connection);
+
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    let sec_fields = account.unlock(account_password).unwrap();
+    let mut loaded = password::Password::from_b64(
+        db.get_b64_passwords(username)
+            .unwrap()
+            .unwrap()
+            .pop()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(loaded.generate_totp(sec_fields.key(), 59).unwrap(), None);
+
+    let old_b64_name_ciphertext = loaded.encrypted_name().ciphertext_as_b64();
+    loaded
+        .apply_changes(
+            sec_fields.key(),
+            &password::PasswordChanges::new().new_totp_secret(b"12345678901234567890"),
+        )
+        .unwrap();
+    db.update_passwords(vec![(old_b64_name_ciphertext, loaded.to_b64())])
+        .unwrap();
+    let loaded = password::Password::from_b64(
+        db.get_b64_passwords(username)
+            .unwrap()
+            .unwrap()
+            .pop()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        loaded.generate_totp(sec_fields.key(), 59).unwrap().unwrap(),
+        "287082"
+    );
+}
+
+#[test]
+fn recovery_code_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "recovery_account";
+    let account = Account::new(username, "recovery_password").unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+
+    assert!(db.get_b64_recovery_codes("bleurgh").unwrap().is_none());
+    assert!(db
+        .get_b64_recovery_codes(username)
+        .unwrap()
+        .unwrap()
+        .is_empty());
+
+    let (recovery_code_1, code_1) = recovery::RecoveryCode::generate(username, 1);
+    let (recovery_code_2, code_2) = recovery::RecoveryCode::generate(username, 1);
+    db.replace_recovery_codes(
+        username,
+        vec![recovery_code_1.to_b64(), recovery_code_2.to_b64()],
+    )
+    .unwrap();
+
+    let loaded: Vec<recovery::RecoveryCode> = db
+        .get_b64_recovery_codes(username)
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .map(|b64_code| recovery::RecoveryCode::from_b64(b64_code).unwrap())
+        .collect();
+    assert_eq!(loaded.len(), 2);
+    assert!(loaded.iter().any(|code| code.check_match(&code_1)));
+    assert!(loaded.iter().any(|code| code.check_match(&code_2)));
+
+    // Codes are single-use.
+    let b64_hash_1 = recovery_code_1.hashed_code().hash_as_b64();
+    db.delete_recovery_code(username, &b64_hash_1)
+        .unwrap()
+        .unwrap();
+    assert!(db
+        .delete_recovery_code(username, &b64_hash_1)
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        db.get_b64_recovery_codes(username).unwrap().unwrap().len(),
+        1
+    );
+
+    // Replacing codes invalidates all of the old ones.
+    let (recovery_code_3, code_3) = recovery::RecoveryCode::generate(username, 2);
+    db.replace_recovery_codes(username, vec![recovery_code_3.to_b64()])
+        .unwrap();
+    let loaded = db.get_b64_recovery_codes(username).unwrap().unwrap();
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded[0].last_rotated_at, 2);
+    let loaded = recovery::RecoveryCode::from_b64(loaded.into_iter().next().unwrap()).unwrap();
+    assert!(loaded.check_match(&code_3));
+    assert!(!loaded.check_match(&code_2));
+
+    // Codes are removed alongside their account.
+    db.delete_account(username).unwrap().unwrap();
+    assert!(db.get_b64_recovery_codes(username).unwrap().is_none());
+}
+
+#[test]
+fn file_path_tests() {
+    common::reset_test_db();
+    let _ = std::fs::remove_file("test_files/file_path_file");
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "file_path_account";
+    let password = "file_path_password";
+    let account = Account::new(username, password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let sec_fields = account.unlock(password).unwrap();
+
+    let file_name = OsString::from("file_path_file");
+    let mut file_path = common::get_test_dir();
+    file_path.push(&file_name);
+    let file = FileData::new_with_key(username, sec_fields.key(), file_name, &file_path).unwrap();
+    db.add_new_file_data(file.to_b64().unwrap()).unwrap();
+
+    let old_path = helpers::path_to_string(&file_path).unwrap();
+    let new_path = "/mnt/external/file_path_file";
+    db.update_file_path(new_path, &old_path).unwrap();
+    assert!(db.get_b64_file_data(&old_path).unwrap().is_none());
+    let moved = FileData::from_b64(db.get_b64_file_data(new_path).unwrap().unwrap()).unwrap();
+    assert_eq!(moved.path(), std::path::Path::new(new_path));
+    assert_eq!(moved.content_nonce(), file.content_nonce());
+
+    assert!(db.file_data_exists(new_path).unwrap());
+    assert!(!db.file_data_exists(&old_path).unwrap());
+
+    // Updating a path that doesn't exist should fail.
+    db.update_file_path(new_path, &old_path).unwrap_err();
+
+    // A batch of nonce updates containing a missing path changes nothing.
+    let new_nonce = [7u8; 12];
+    db.update_file_content_nonces(vec![
+        (new_nonce, new_path.to_owned()),
+        (new_nonce, old_path.clone()),
+    ])
+    .unwrap_err();
+    let unchanged = FileData::from_b64(db.get_b64_file_data(new_path).unwrap().unwrap()).unwrap();
+    assert_eq!(unchanged.content_nonce(), file.content_nonce());
+
+    db.update_file_content_nonces(vec![(new_nonce, new_path.to_owned())])
+        .unwrap();
+    let changed = FileData::from_b64(db.get_b64_file_data(new_path).unwrap().unwrap()).unwrap();
+    assert_eq!(changed.content_nonce(), &new_nonce);
+
+    // A failed deletion callback undoes the deletion.
+    let err = db
+        .delete_file_data_with(new_path, || Err(rusqlite::Error::InvalidQuery))
+        .unwrap_err();
+    if let rusqlite::Error::InvalidQuery = err {
+    } else {
+        panic!("Wrong error type");
+    }
+    assert!(db.get_b64_file_data(new_path).unwrap().is_some());
+
+    let mut called = false;
+    db.delete_file_data_with(new_path, || -> rusqlite::Result<()> {
+        called = true;
+        Ok(())
+    })
+    .unwrap()
+    .unwrap();
+    assert!(called);
+    assert!(db.get_b64_file_data(new_path).unwrap().is_none());
+    assert!(db
+        .delete_file_data_with(new_path, || -> rusqlite::Result<()> { panic!() })
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn rollback_insert_fail() {
+    common::reset_test_db();
+    let _ = std::fs::remove_file("test_files/rollback_insert_file");
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "rollback_insert_account";
+    let password = "rollback_insert_password";
+    let account = Account::new(username, password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let sec_fields = account.unlock(password).unwrap();
+
+    let file_name = OsString::from("rollback_insert_file");
+    let mut file_path = common::get_test_dir();
+    file_path.push(&file_name);
+    let file = FileData::new_with_key(username, sec_fields.key(), file_name, &file_path).unwrap();
+    let path_string = helpers::path_to_string(&file_path).unwrap();
+
+    // A failed callback undoes the insertion.
+    db.add_new_file_data_with(file.to_b64().unwrap(), || {
+        Err(rusqlite::Error::InvalidQuery)
+    })
+    .unwrap_err();
+    assert!(!db.file_data_exists(&path_string).unwrap());
+
+    db.add_new_file_data_with(file.to_b64().unwrap(), || -> rusqlite::Result<()> {
+        Ok(())
+    })
+    .unwrap();
+    assert!(db.file_data_exists(&path_string).unwrap());
+
+    // Inserting a duplicate fails before the callback is run.
+    db.add_new_file_data_with(file.to_b64().unwrap(), || -> rusqlite::Result<()> {
+        panic!()
+    })
+    .unwrap_err();
+}
+
+#[test]
+fn update_passwords_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "update_account";
+    let account_password = "update_password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let key = account.unlock(account_password).unwrap().key().clone();
+
+    let mut pass_1 =
+        password::Password::new(&account, account_password, "bank", "me", "1234", "").unwrap();
+    let mut pass_2 =
+        password::Password::new(&account, account_password, "email", "me@me.me", "abcd", "")
+            .unwrap();
+    db.add_new_password(pass_1.to_b64()).unwrap();
+    db.add_new_password(pass_2.to_b64()).unwrap();
+
+    let old_name_1 = pass_1.encrypted_name().ciphertext_as_b64();
+    let old_name_2 = pass_2.encrypted_name().ciphertext_as_b64();
+    pass_1.reencrypt(&key).unwrap();
+    pass_2.reencrypt(&key).unwrap();
+
+    // A batch containing a missing password changes nothing.
+    let err = db
+        .update_passwords(vec![
+            (old_name_1.clone(), pass_1.to_b64()),
+            ("bm90IGEgcmVhbCBuYW1l".to_owned(), pass_2.to_b64()),
+        ])
+        .unwrap_err();
+    if let rusqlite::Error::QueryReturnedNoRows = err {
+    } else {
+        panic!("Wrong error type");
+    }
+    let stored = db.get_b64_passwords(username).unwrap().unwrap();
+    assert!(stored
+        .iter()
+        .any(|pass| pass.b64_name_ciphertext == old_name_1));
+
+    db.update_passwords(vec![
+        (old_name_1.clone(), pass_1.to_b64()),
+        (old_name_2.clone(), pass_2.to_b64()),
+    ])
+    .unwrap();
+    let stored: Vec<password::Password> = db
+        .get_b64_passwords(username)
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .map(|b64_pass| password::Password::from_b64(b64_pass).unwrap())
+        .collect();
+    assert_eq!(stored.len(), 2);
+    for pass in &stored {
+        let name = pass.encrypted_name().ciphertext_as_b64();
+        assert_ne!(name, old_name_1);
+        assert_ne!(name, old_name_2);
+    }
+    let mut names: Vec<String> = stored
+        .iter()
+        .map(|pass| pass.unlock(&key).unwrap().name().to_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["bank", "email"]);
+}
+
+#[test]
+fn attachment_tests() {
+    common::reset_test_db();
+    let _ = std::fs::remove_file("test_files/attachment_1");
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "attachment_account";
+    let account_password = "attachment_password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let key = account.unlock(account_password).unwrap().key().clone();
+
+    let mut pass =
+        password::Password::new(&account, account_password, "server", "root", "pw", "").unwrap();
+    db.add_new_password(pass.to_b64()).unwrap();
+    let b64_name = pass.encrypted_name().ciphertext_as_b64();
+
+    let attachment = attachment::Attachment::new(
+        &pass,
+        &key,
+        "cert.pem",
+        b"certificate",
+        "test_files/attachment_1",
+    )
+    .unwrap();
+    db.add_new_attachment(attachment.to_b64().unwrap()).unwrap();
+    db.add_new_attachment(attachment.to_b64().unwrap())
+        .unwrap_err();
+    assert_eq!(
+        db.get_b64_attachments(username, &b64_name).unwrap().len(),
+        1
+    );
+
+    // Attachments follow their password when it is re-encrypted.
+    pass.reencrypt(&key).unwrap();
+    let new_b64_name = pass.encrypted_name().ciphertext_as_b64();
+    db.update_passwords(vec![(b64_name.clone(), pass.to_b64())])
+        .unwrap();
+    assert!(db
+        .get_b64_attachments(username, &b64_name)
+        .unwrap()
+        .is_empty());
+    let loaded = attachment::Attachment::from_b64(
+        db.get_b64_attachments(username, &new_b64_name)
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(loaded.name(), "cert.pem");
+    assert_eq!(*loaded.open_decrypted(&key).unwrap(), b"certificate");
+
+    db.delete_attachment(username, &new_b64_name, "cert.pem")
+        .unwrap()
+        .unwrap();
+    assert!(db
+        .delete_attachment(username, &new_b64_name, "cert.pem")
+        .unwrap()
+        .is_none());
+
+    // Deleting a password deletes its attachments along with it.
+    db.add_new_attachment(loaded.to_b64().unwrap()).unwrap();
+    db.delete_password(username, &new_b64_name)
+        .unwrap()
+        .unwrap();
+    assert!(db
+        .get_b64_attachments(username, &new_b64_name)
+        .unwrap()
+        .is_empty());
+    assert!(db
+        .delete_password(username, &new_b64_name)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn password_history_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "history_account";
+    let account_password = "history_password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let key = account.unlock(account_password).unwrap().key().clone();
+
+    let mut pass =
+        password::Password::new(&account, account_password, "server", "root", "pw0", "").unwrap();
+    db.add_new_password(pass.to_b64()).unwrap();
+
+    // Change the content more often than the history keeps.
+    let num_changes = history::MAX_PASSWORD_HISTORY + 5;
+    for i in 1..=num_changes {
+        let b64_name = pass.encrypted_name().ciphertext_as_b64();
+        let entry = history::PasswordHistoryEntry::new(&pass, chrono::Utc::now());
+        let changes = password::PasswordChanges::new().new_content(&format!("pw{i}"));
+        pass.apply_changes(&key, &changes).unwrap();
+        db.update_passwords_with_history(vec![(b64_name, pass.to_b64())], vec![entry.to_b64()])
+            .unwrap();
+    }
+
+    let b64_name = pass.encrypted_name().ciphertext_as_b64();
+    let history: Vec<String> = db
+        .get_b64_password_history(username, &b64_name)
+        .unwrap()
+        .into_iter()
+        .map(|b64_entry| {
+            history::PasswordHistoryEntry::from_b64(b64_entry)
+                .unwrap()
+                .decrypt(&key)
+                .unwrap()
+        })
+        .collect();
+    let expected: Vec<String> = (num_changes - history::MAX_PASSWORD_HISTORY..num_changes)
+        .rev()
+        .map(|i| format!("pw{i}"))
+        .collect();
+    assert_eq!(history, expected);
+
+    // History follows its password when it is renamed.
+    let entry = history::PasswordHistoryEntry::new(&pass, chrono::Utc::now());
+    let changes = password::PasswordChanges::new()
+        .new_name("renamed_server")
+        .new_content("pw_renamed");
+    pass.apply_changes(&key, &changes).unwrap();
+    db.update_passwords_with_history(
+        vec![(b64_name.clone(), pass.to_b64())],
+        vec![entry.to_b64()],
+    )
+    .unwrap();
+    assert!(db
+        .get_b64_password_history(username, &b64_name)
+        .unwrap()
+        .is_empty());
+    let new_b64_name = pass.encrypted_name().ciphertext_as_b64();
+    let history = db
+        .get_b64_password_history(username, &new_b64_name)
+        .unwrap();
+    assert_eq!(history.len(), history::MAX_PASSWORD_HISTORY);
+    assert_eq!(
+        history::PasswordHistoryEntry::from_b64(history.into_iter().next().unwrap())
+            .unwrap()
+            .decrypt(&key)
+            .unwrap(),
+        format!("pw{num_changes}")
+    );
+
+    // Deleting a password deletes its history along with it.
+    db.delete_password(username, &new_b64_name)
+        .unwrap()
+        .unwrap();
+    assert_eq!(db.count_rows(database::Table::PasswordHistory).unwrap(), 0);
+}
+
+#[test]
+fn raw_row_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    for table in database::Table::ALL {
+        assert_eq!(db.count_rows(table).unwrap(), 0);
+        assert!(db.get_raw_row(table, 0).unwrap().is_none());
+    }
+
+    let account = Account::new("raw_row_account", "raw_row_password").unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    assert_eq!(db.count_rows(database::Table::UserCredentials).unwrap(), 1);
+
+    let row = db
+        .get_raw_row(database::Table::UserCredentials, 0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(row.len(), 9);
+    assert_eq!(row[0].0, "username");
+    assert_eq!(
+        row[0].1,
+        rusqlite::types::Value::Text(helpers::bytes_to_b64(b"raw_row_account"))
+    );
+    assert!(db
+        .get_raw_row(database::Table::UserCredentials, 1)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn open_flags_tests() {
+    common::reset_test_db();
+    let db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    assert!(db
+        .open_flags()
+        .contains(rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE));
+    assert!(!db
+        .open_flags()
+        .contains(rusqlite::OpenFlags::SQLITE_OPEN_CREATE));
+    assert!(!db.is_read_only());
+    db.ping().unwrap();
+}
+
+#[test]
+fn config_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    assert!(db.get_config("max_password_age_days").unwrap().is_none());
+
+    db.set_config("max_password_age_days", "90").unwrap();
+    assert_eq!(
+        db.get_config("max_password_age_days").unwrap().unwrap(),
+        "90"
+    );
+
+    db.set_config("max_password_age_days", "30").unwrap();
+    assert_eq!(
+        db.get_config("max_password_age_days").unwrap().unwrap(),
+        "30"
+    );
+    assert_eq!(db.count_rows(database::Table::VaultConfig).unwrap(), 1);
+}
+
+#[test]
+fn concurrent_access_tests() {
+    common::reset_test_db();
+    let mut writer = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    let reader = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let journal_mode: String = rusqlite::Connection::open(common::TEST_DB_PATH)
+        .unwrap()
+        .pragma_query_value(None, "journal_mode", |row| row.get(0))
+        .unwrap();
+    assert_eq!(journal_mode, "wal");
+    assert_eq!(reader.journal_mode().unwrap(), "wal");
+
+    let writer_thread = std::thread::spawn(move || {
+        for i in 0..100 {
+            writer.set_config(&format!("key_{i}"), "value").unwrap();
+        }
+    });
+    while !writer_thread.is_finished() {
+        reader.count_rows(database::Table::VaultConfig).unwrap();
+        reader.get_config("key_0").unwrap();
+    }
+    writer_thread.join().unwrap();
+
+    assert_eq!(
+        reader.count_rows(database::Table::VaultConfig).unwrap(),
+        100
+    );
+}
+
+#[test]
+fn vacuum_tests() {
+    common::reset_test_db();
+    let mut backup_path = common::get_test_dir();
+    backup_path.push("vacuum_tests_backup.db");
+    let _ = std::fs::remove_file(&backup_path);
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let account = Account::new("vacuum_account", "vacuum_password").unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    for i in 0..100 {
+        db.set_config(&format!("key_{i}"), &"value".repeat(100))
+            .unwrap();
+    }
+    db.delete_account("vacuum_account").unwrap().unwrap();
+    db.vacuum().unwrap();
+    assert_eq!(db.count_rows(database::Table::VaultConfig).unwrap(), 100);
+    assert!(!db.account_exists("vacuum_account").unwrap());
+
+    db.vacuum_into(&backup_path).unwrap();
+    let backup = database::Database::connect(&backup_path).unwrap();
+    assert_eq!(
+        backup.count_rows(database::Table::VaultConfig).unwrap(),
+        100
+    );
+    assert_eq!(
+        backup.get_config("key_0").unwrap().unwrap(),
+        "value".repeat(100)
+    );
+
+    drop(backup);
+
+    // VACUUM INTO refuses to overwrite an existing file.
+    assert!(db.vacuum_into(&backup_path).is_err());
+    std::fs::remove_file(&backup_path).unwrap();
+}
+
+#[test]
+fn check_tests() {
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let account = Account::new("check_account", "check_password").unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    assert!(db.integrity_check().unwrap().is_empty());
+    assert!(db.foreign_key_check().unwrap().is_empty());
+
+    // Sidestep foreign key enforcement to leave a file owned by an account that doesn't exist.
+    let raw_connection = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    raw_connection
+        .pragma_update(None, "foreign_keys", false)
+        .unwrap();
+    raw_connection
+        .execute(
+            "INSERT INTO files (path, name, owner_username, content_nonce, file_size)
+            VALUES (?1, ?2, ?3, ?4, 0)",
+            ["orphan_path", "orphan_name", "missing_account", "nonce"],
+        )
+        .unwrap();
+    assert!(db.integrity_check().unwrap().is_empty());
+    let problems = db.foreign_key_check().unwrap();
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].contains("\"files\""));
+    assert!(problems[0].contains("\"user_credentials\""));
+}
+
+#[test]
+fn backup_tests() {
+    common::reset_test_db();
+    let mut backup_path = common::get_test_dir();
+    backup_path.push("backup_tests_backup.db");
+    let _ = std::fs::remove_file(&backup_path);
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let account = Account::new("backup_account", "backup_password").unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    db.backup(&backup_path).unwrap();
+
+    // Unlike VACUUM INTO, a backup overwrites an earlier snapshot.
+    db.set_config("backup_key", "backup_value").unwrap();
+    db.backup(&backup_path).unwrap();
+
+    let backup = database::Database::connect(&backup_path).unwrap();
+    assert!(backup.account_exists("backup_account").unwrap());
+    assert_eq!(
+        backup.get_config("backup_key").unwrap().unwrap(),
+        "backup_value"
+    );
+    assert!(backup.integrity_check().unwrap().is_empty());
+
+    drop(backup);
+    std::fs::remove_file(&backup_path).unwrap();
+}
+
+#[test]
+fn schema_version_tests() {
+    common::reset_test_db();
+    let db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    drop(db);
+
+    // Reconnecting to an up-to-date database leaves it alone. The data version seen by another
+    // connection changes whenever anything is committed.
+    let observer = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    let data_version = |conn: &rusqlite::Connection| {
+        conn.query_row("PRAGMA data_version", [], |row| row.get::<usize, i64>(0))
+            .unwrap()
+    };
+    let data_version_before = data_version(&observer);
+    let db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+    assert_eq!(db.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    drop(db);
+    assert_eq!(data_version(&observer), data_version_before);
+    drop(observer);
+
+    // A database written by a newer dgruft is refused rather than misread.
+    rusqlite::Connection::open(common::TEST_DB_PATH)
+        .unwrap()
+        .execute(
+            "UPDATE schema_version SET version = ?1",
+            [migrations::SCHEMA_VERSION + 1],
+        )
+        .unwrap();
+    assert!(database::Database::connect(common::TEST_DB_PATH).is_err());
+}
+
+#[test]
+fn consistency_tests() {
+    init_test_config();
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "consistency_account";
+    let account_password = "consistency password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    for name in ["bank", "email"] {
+        let pass =
+            password::Password::new(&account, account_password, name, "me", "1234", "").unwrap();
+        db.add_new_password(pass.to_b64()).unwrap();
+    }
+    assert!(check_database_consistency().unwrap().is_empty());
+
+    // Truncate the notes of one password to less than an authentication tag.
+    let conn = rusqlite::Connection::open(common::TEST_DB_PATH).unwrap();
+    let corrupted_name: String = conn
+        .query_row("SELECT encrypted_name FROM passwords LIMIT 1", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    conn.execute(
+        "UPDATE passwords SET encrypted_notes = 'AAAA' WHERE encrypted_name = ?1",
+        [&corrupted_name],
+    )
+    .unwrap();
+
+    let issues = check_database_consistency().unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].table, database::Table::Passwords);
+    assert_eq!(
+        issues[0].primary_key,
+        format!(
+            "({}, {corrupted_name})",
+            helpers::bytes_to_b64(username.as_bytes())
+        )
+    );
+    assert_eq!(issues[0].field, "encrypted_notes");
+    assert_eq!(
+        issues[0].issue,
+        "ciphertext is shorter than the authentication tag"
+    );
+}
+
+#[test]
+fn drain_and_migrate_tests() {
+    let old_db_path = PathBuf::from("dbs/dgruft-migrate-old-test.db");
+    let new_db_path = PathBuf::from("dbs/dgruft-migrate-new-test.db");
+    for path in [
+        "dbs/dgruft-migrate-old-test.db",
+        "dbs/dgruft-migrate-old-test.db-wal",
+        "dbs/dgruft-migrate-old-test.db-shm",
+        "dbs/dgruft-migrate-new-test.db",
+    ] {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let username = "migrate_account";
+    let account_password = "migrate password";
+    std::fs::File::create(&old_db_path).unwrap();
+    {
+        let mut old_db = database::Database::connect(&old_db_path).unwrap();
+        let account = Account::new(username, account_password).unwrap();
+        old_db.add_new_account(account.to_b64()).unwrap();
+        let pass =
+            password::Password::new(&account, account_password, "bank", "me", "1234", "").unwrap();
+        old_db.add_new_password(pass.to_b64()).unwrap();
+    }
+    let old_db_bytes = std::fs::read(&old_db_path).unwrap();
+
+    let err =
+        drain_and_migrate(&old_db_path, &new_db_path, username, "wrong password").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::IncorrectPasswordError)
+    ));
+    assert!(!new_db_path.exists());
+
+    let report = drain_and_migrate(&old_db_path, &new_db_path, username, account_password).unwrap();
+    assert_eq!(report.passwords, 1);
+
+    // The old database is left exactly as it was.
+    assert_eq!(std::fs::read(&old_db_path).unwrap(), old_db_bytes);
+
+    let new_db = database::Database::connect(&new_db_path).unwrap();
+    assert_eq!(
+        new_db
+            .get_b64_account(username)
+            .unwrap()
+            .unwrap()
+            .as_tuple(),
+        database::Database::connect_read_only(&old_db_path)
+            .unwrap()
+            .get_b64_account(username)
+            .unwrap()
+            .unwrap()
+            .as_tuple()
+    );
+    assert_eq!(
+        new_db.get_b64_passwords(username).unwrap().unwrap().len(),
+        1
+    );
+}
+
+#[test]
+fn batch_update_passwords_tests() {
+    init_test_config();
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "batch_account";
+    let account_password = "batch password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    for name in ["bank", "email", "forum"] {
+        let pass =
+            password::Password::new(&account, account_password, name, "me", "1234", "").unwrap();
+        db.add_new_password(pass.to_b64()).unwrap();
+    }
+    let rename = |name: &str, new_name: &str| password::PasswordUpdate {
+        name: name.to_owned(),
+        changes: password::PasswordChanges::new().new_name(new_name),
+    };
+
+    // The same password can't be targeted twice.
+    assert!(batch_update_passwords(
+        username,
+        account_password,
+        vec![rename("bank", "savings"), rename("bank", "checking")],
+    )
+    .is_err());
+
+    // Renaming to a name which is still taken afterwards fails.
+    let err = batch_update_passwords(username, account_password, vec![rename("bank", "email")])
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::PasswordAlreadyExistsError(name)) if name == "email"
+    ));
+    let err = batch_update_passwords(
+        username,
+        account_password,
+        vec![rename("bank", "savings"), rename("email", "savings")],
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::PasswordAlreadyExistsError(name)) if name == "savings"
+    ));
+
+    // Nothing was changed by the rejected updates, and swapping names is fine.
+    let report = batch_update_passwords(
+        username,
+        account_password,
+        vec![rename("bank", "email"), rename("email", "bank")],
+    )
+    .unwrap();
+    assert_eq!(report.updated, 2);
+    assert_eq!(db.get_b64_passwords(username).unwrap().unwrap().len(), 3);
+}
+
+#[test]
+fn delete_file_tests() {
+    init_test_config();
+    common::reset_test_db();
+    let file_path = common::get_test_dir().join("delete_file_1");
+    let blocker_path = common::get_test_dir().join("delete_file_1.deleted");
+    let _ = std::fs::remove_file(&file_path);
+    let _ = std::fs::remove_dir_all(&blocker_path);
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "delete_file_account";
+    let account_password = "delete file password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let sec_fields = account.unlock(account_password).unwrap();
+    let file = FileData::new_with_content_and_key(
+        username,
+        sec_fields.key(),
+        OsString::from("delete_file_1"),
+        b"some secret content",
+        &file_path,
+    )
+    .unwrap();
+    db.add_new_file_data(file.to_b64().unwrap()).unwrap();
+    let path_string = helpers::path_to_string(&file_path).unwrap();
+    let ciphertext = std::fs::read(&file_path).unwrap();
+
+    // If the file can't be moved aside, neither the entry nor the encrypted data is touched.
+    std::fs::create_dir(&blocker_path).unwrap();
+    std::fs::write(blocker_path.join("blocker"), "").unwrap();
+    assert!(delete_file(
+        username.to_owned(),
+        account_password.to_owned(),
+        OsString::from("delete_file_1"),
+        true,
+    )
+    .is_err());
+    assert!(db.get_b64_file_data(&path_string).unwrap().is_some());
+    assert_eq!(std::fs::read(&file_path).unwrap(), ciphertext);
+
+    std::fs::remove_dir_all(&blocker_path).unwrap();
+    delete_file(
+        username.to_owned(),
+        account_password.to_owned(),
+        OsString::from("delete_file_1"),
+        true,
+    )
+    .unwrap();
+    assert!(db.get_b64_file_data(&path_string).unwrap().is_none());
+    assert!(!file_path.exists());
+    assert!(!blocker_path.exists());
+}
+
+#[test]
+fn credentials_csv_tests() {
+    init_test_config();
+    common::reset_test_db();
+    let masked_path = common::get_test_dir().join("credentials_masked.csv");
+    let csv_path = common::get_test_dir().join("credentials.csv");
+    let _ = std::fs::remove_file(&masked_path);
+    let _ = std::fs::remove_file(&csv_path);
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let (source, source_password) = ("csv_source", "csv source password");
+    let (target, target_password) = ("csv_target", "csv target password");
+    let source_account = Account::new(source, source_password).unwrap();
+    let target_account = Account::new(target, target_password).unwrap();
+    db.add_new_account(source_account.to_b64()).unwrap();
+    db.add_new_account(target_account.to_b64()).unwrap();
+    let source_key = source_account.unlock(source_password).unwrap();
+    let mut bank = password::Password::new(
+        &source_account,
+        source_password,
+        "bank",
+        "me",
+        "1234",
+        "pin, \"not\" a password",
+    )
+    .unwrap();
+    bank.apply_changes(
+        source_key.key(),
+        &password::PasswordChanges::new()
+            .new_url("https://bank.example.com")
+            .new_category("finance"),
+    )
+    .unwrap();
+    db.add_new_password(bank.to_b64()).unwrap();
+    for (account, account_password) in [
+        (&source_account, source_password),
+        (&target_account, target_password),
+    ] {
+        let email =
+            password::Password::new(account, account_password, "email", "me", "5678", "").unwrap();
+        db.add_new_password(email.to_b64()).unwrap();
+    }
+
+    // Nothing is written if the account can't be unlocked.
+    assert!(export_credentials_csv(source, "wrong password", Some(&masked_path), true).is_err());
+    assert!(!masked_path.exists());
+
+    export_credentials_csv(source, source_password, Some(&masked_path), true).unwrap();
+    let masked = std::fs::read_to_string(&masked_path).unwrap();
+    assert!(masked.contains("********"));
+    assert!(!masked.contains("1234"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&masked_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // Existing output is never overwritten.
+    assert!(export_credentials_csv(source, source_password, Some(&masked_path), false).is_err());
+
+    // "email" already exists in the target account, so only "bank" is imported.
+    export_credentials_csv(source, source_password, Some(&csv_path), false).unwrap();
+    let report =
+        import_credentials_csv(target, target_password, File::open(&csv_path).unwrap()).unwrap();
+    assert_eq!(report.credentials_added, 1);
+    assert_eq!(report.conflicts_skipped, 1);
+
+    // Optional columns may be left out.
+    let report = import_credentials_csv(
+        target,
+        target_password,
+        "password,name\nhunter2,forum\n".as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(report.credentials_added, 1);
+    assert_eq!(report.conflicts_skipped, 0);
+
+    let target_key = target_account.unlock(target_password).unwrap();
+    let mut imported = std::collections::BTreeMap::new();
+    for b64_password in db.get_b64_passwords(target).unwrap().unwrap() {
+        let stored = password::Password::from_b64(b64_password).unwrap();
+        let fields = stored.unlock(target_key.key()).unwrap();
+        imported.insert(fields.name().to_owned(), (stored, fields));
+    }
+    assert_eq!(imported.len(), 3);
+
+    let (bank, bank_fields) = &imported["bank"];
+    assert_eq!(bank_fields.username(), "me");
+    assert_eq!(bank_fields.content(), "1234");
+    assert_eq!(bank_fields.notes(), "pin, \"not\" a password");
+    assert_eq!(bank_fields.url(), Some("https://bank.example.com"));
+    assert_eq!(bank.category(), Some("finance"));
+    assert_eq!(bank.expires_at(), None);
+
+    let (forum, forum_fields) = &imported["forum"];
+    assert_eq!(forum_fields.username(), "");
+    assert_eq!(forum_fields.content(), "hunter2");
+    assert_eq!(forum_fields.notes(), "");
+    assert_eq!(forum.category(), None);
+
+    // The skipped password keeps its original content.
+    assert_eq!(imported["email"].1.content(), "5678");
+
+    std::fs::remove_file(&masked_path).unwrap();
+    std::fs::remove_file(&csv_path).unwrap();
+}
+
+#[test]
+fn json_reimport_tests() {
+    init_test_config();
+    common::reset_test_db();
+    let export_path = common::get_test_dir().join("reimport_export.json");
+    let _ = std::fs::remove_file(&export_path);
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "reimport_account";
+    let account_password = "reimport password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let bank =
+        password::Password::new(&account, account_password, "bank", "me", "1234", "").unwrap();
+    let email =
+        password::Password::new(&account, account_password, "email", "me", "5678", "").unwrap();
+    db.add_new_password(bank.to_b64()).unwrap();
+    db.add_new_password(email.to_b64()).unwrap();
+
+    // Exporting logs in, so the stored account has a last login time the export doesn't have.
+    let export_key = export_json(username, account_password, &export_path).unwrap();
+    assert!(db
+        .get_b64_account(username)
+        .unwrap()
+        .unwrap()
+        .last_login
+        .is_some());
+    db.delete_password(username, &bank.encrypted_name().ciphertext_as_b64())
+        .unwrap()
+        .unwrap();
+
+    // The exported passwords can't be restored without the account password.
+    assert!(import_json(&export_path, &export_key, "wrong password").is_err());
+    assert_eq!(db.get_b64_passwords(username).unwrap().unwrap().len(), 1);
+
+    // The account is recognised as the exported one, so only the missing password is restored.
+    let report = import_json(&export_path, &export_key, account_password).unwrap();
+    assert_eq!(report.accounts_added, 0);
+    assert_eq!(report.credentials_added, 1);
+    assert_eq!(report.conflicts_skipped, 1);
+    assert_eq!(db.get_b64_passwords(username).unwrap().unwrap().len(), 2);
+
+    // A different account under the same username is left alone.
+    db.delete_account(username).unwrap().unwrap();
+    let other_account = Account::new(username, "another password").unwrap();
+    db.add_new_account(other_account.to_b64()).unwrap();
+    let report = import_json(&export_path, &export_key, account_password).unwrap();
+    assert_eq!(report.credentials_added, 0);
+    assert_eq!(report.conflicts_skipped, 3);
+    assert!(db.get_b64_passwords(username).unwrap().unwrap().is_empty());
+
+    std::fs::remove_file(&export_path).unwrap();
+}
+
+#[test]
+fn duplicate_password_tests() {
+    init_test_config();
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "duplicate_account";
+    let account_password = "duplicate password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    for (name, content) in [
+        ("forum", "hunter2"),
+        ("email", "1234"),
+        ("bank", "1234"),
+        ("shop", "unique"),
+        ("games", "hunter2"),
+        ("empty", ""),
+        ("blank", ""),
+        ("work", "1234"),
+    ] {
+        let pass =
+            password::Password::new(&account, account_password, name, "me", content, "").unwrap();
+        db.add_new_password(pass.to_b64()).unwrap();
+    }
+
+    // Unique and empty contents aren't reported. Groups and the names in them are sorted.
+    assert_eq!(
+        vec![
+            vec!["bank".to_owned(), "email".to_owned(), "work".to_owned()],
+            vec!["forum".to_owned(), "games".to_owned()],
+        ],
+        find_duplicate_passwords(username, account_password).unwrap()
+    );
+
+    assert!(find_duplicate_passwords(username, "wrong password").is_err());
+}
+
+#[test]
+fn show_password_tests() {
+    init_test_config();
+    common::reset_test_db();
+    let mut db = database::Database::connect(common::TEST_DB_PATH).unwrap();
+
+    let username = "show_account";
+    let account_password = "show password";
+    let account = Account::new(username, account_password).unwrap();
+    db.add_new_account(account.to_b64()).unwrap();
+    let pass =
+        password::Password::new(&account, account_password, "bank", "me", "1234", "pin").unwrap();
+    db.add_new_password(pass.to_b64()).unwrap();
+
+    // The content is masked unless revealed.
+    let details = show_password(username, account_password, "bank", false).unwrap();
+    assert_eq!("bank", details.name);
+    assert_eq!("me", details.username);
+    assert_eq!(None, details.password);
+    assert_eq!("pin", details.notes);
+    let text = details.to_string();
+    assert!(text.contains("Password: ********\n"));
+    assert!(!text.contains("1234"));
+
+    let details = show_password(username, account_password, "bank", true).unwrap();
+    assert_eq!(Some("1234"), details.password.as_deref());
+    assert!(details.to_string().contains("Password: 1234\n"));
+
+    let json = serde_json::to_value(&details).unwrap();
+    assert_eq!("bank", json["name"]);
+    assert_eq!("me", json["username"]);
+    assert_eq!("1234", json["password"]);
+    assert_eq!("pin", json["notes"]);
+    assert!(json["url"].is_null());
+    assert!(json["category"].is_null());
+    assert!(json["expires_at"].is_null());
+    assert_eq!(pass.created_at().timestamp(), json["created_at"]);
+    assert_eq!(pass.updated_at().timestamp(), json["updated_at"]);
+    let json =
+        serde_json::to_value(show_password(username, account_password, "bank", false).unwrap())
+            .unwrap();
+    assert!(json["password"].is_null());
+
+    // Single fields are shown unmasked.
+    assert_eq!(
+        "1234",
+        show_password_field(username, account_password, "bank", "password").unwrap()
+    );
+    assert_eq!(
+        "me",
+        show_password_field(username, account_password, "bank", "username").unwrap()
+    );
+    assert_eq!(
+        "",
+        show_password_field(username, account_password, "bank", "url").unwrap()
+    );
+    assert!(show_password_field(username, account_password, "bank", "pin").is_err());
+
+    assert!(show_password(username, account_password, "email", false).is_err());
+    assert!(show_password(username, "wrong password", "bank", false).is_err());
+
+    // Showing a password doesn't count as a login.
+    let loaded = Account::from_b64(db.get_b64_account(username).unwrap().unwrap()).unwrap();
+    assert!(loaded.last_login().is_none());
+}